@@ -0,0 +1,264 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{Attribute, Data, DeriveInput, Fields, GenericParam, Generics, Lit, Meta, NestedMeta, Path, Type, parse_macro_input, parse_quote, ext::IdentExt};
+
+/*
+  struct fields map to keyword keys in a Variable::Map
+  #[variable(rename = "max-hp")]    key name of a field or variant
+  #[variable(default)]              missing key falls back to Default::default()
+  #[variable(default = "path")]     missing key falls back to path()
+  #[variable(tag = "kind")]         enum tag key of the map form, :type by default
+  unit variants are keywords, the others maps tagged with the variant keyword
+*/
+#[proc_macro_derive(IntoVariable, attributes(variable))]
+pub fn derive_into_variable(input:TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_into_variable(&input).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+#[proc_macro_derive(FromVariable, attributes(variable))]
+pub fn derive_from_variable(input:TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_from_variable(&input).unwrap_or_else(|err| err.to_compile_error()).into()
+}
+
+#[derive(Default)]
+struct Attrs {
+    rename:Option<String>,
+    default:Option<Option<Path>>,
+    tag:Option<String>
+}
+
+fn parse_attrs(attrs:&[Attribute]) -> syn::Result<Attrs> {
+    let mut ret = Attrs::default();
+    for attr in attrs.iter().filter(|a| a.path.is_ident("variable")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            other => return Err(syn::Error::new_spanned(other, "expected #[variable(...)]"))
+        };
+        for nested in list.nested.iter() {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("default") => ret.default = Some(None),
+                NestedMeta::Meta(Meta::NameValue(nv)) => {
+                    let value = match &nv.lit {
+                        Lit::Str(s) => s.value(),
+                        other => return Err(syn::Error::new_spanned(other, "expected string literal"))
+                    };
+                    if nv.path.is_ident("rename") {
+                        ret.rename = Some(value);
+                    } else if nv.path.is_ident("tag") {
+                        ret.tag = Some(value);
+                    } else if nv.path.is_ident("default") {
+                        ret.default = Some(Some(syn::parse_str::<Path>(&value)?));
+                    } else {
+                        return Err(syn::Error::new_spanned(&nv.path, "unknown variable attribute"));
+                    }
+                },
+                other => return Err(syn::Error::new_spanned(other, "unknown variable attribute"))
+            }
+        }
+    }
+    Ok(ret)
+}
+
+fn is_option(ty:&Type) -> bool {
+    match ty {
+        Type::Path(type_path) => type_path.path.segments.last().map(|s| s.ident == "Option").unwrap_or(false),
+        _ => false
+    }
+}
+
+fn add_bounds(generics:&Generics,bound:TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+    for param in generics.params.iter_mut() {
+        if let GenericParam::Type(type_param) = param {
+            type_param.bounds.push(parse_quote!(#bound));
+        }
+    }
+    generics
+}
+
+fn key_name(attrs:&Attrs,ident:&syn::Ident) -> String {
+    attrs.rename.clone().unwrap_or_else(|| ident.unraw().to_string())
+}
+
+fn expand_into_variable(input:&DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let generics = add_bounds(&input.generics, quote!(::lite_clojure_eval::IntoVariable));
+    let (impl_generics,ty_generics,where_clause) = generics.split_for_impl();
+    let container = parse_attrs(&input.attrs)?;
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (pattern,expr) = into_fields(&data.fields)?;
+            quote! { let #name #pattern = self; #expr }
+        },
+        Data::Enum(data) => {
+            let tag = container.tag.unwrap_or_else(|| "type".to_string());
+            let mut arms:Vec<TokenStream2> = vec![];
+            for variant in data.variants.iter() {
+                let attrs = parse_attrs(&variant.attrs)?;
+                let ident = &variant.ident;
+                let key = key_name(&attrs, ident);
+                let arm = match &variant.fields {
+                    Fields::Unit => quote! { #name::#ident => ::lite_clojure_eval::derive_support::keyword(#key) },
+                    Fields::Named(_) => {
+                        let (pattern,entries) = named_entries(&variant.fields)?;
+                        quote! {
+                            #name::#ident #pattern => ::lite_clojure_eval::derive_support::new_map(::std::vec![
+                                (#tag,::lite_clojure_eval::derive_support::keyword(#key)), #(#entries),*
+                            ])
+                        }
+                    },
+                    Fields::Unnamed(_) => {
+                        let (pattern,value) = into_fields(&variant.fields)?;
+                        quote! {
+                            #name::#ident #pattern => ::lite_clojure_eval::derive_support::new_map(::std::vec![
+                                (#tag,::lite_clojure_eval::derive_support::keyword(#key)), ("value",#value)
+                            ])
+                        }
+                    }
+                };
+                arms.push(arm);
+            }
+            quote! { match self { #(#arms),* } }
+        },
+        Data::Union(_) => return Err(syn::Error::new_spanned(input, "unions are not supported"))
+    };
+    Ok(quote! {
+        impl #impl_generics ::lite_clojure_eval::IntoVariable for #name #ty_generics #where_clause {
+            fn into_variable(self) -> ::lite_clojure_eval::Variable {
+                #body
+            }
+        }
+    })
+}
+
+//binds the fields to locals and returns (pattern, expression building the variable)
+fn into_fields(fields:&Fields) -> syn::Result<(TokenStream2,TokenStream2)> {
+    match fields {
+        Fields::Named(_) => {
+            let (pattern,entries) = named_entries(fields)?;
+            Ok((pattern,quote! { ::lite_clojure_eval::derive_support::new_map(::std::vec![#(#entries),*]) }))
+        },
+        Fields::Unnamed(unnamed) => {
+            let vars:Vec<syn::Ident> = (0..unnamed.unnamed.len()).map(|i| format_ident!("v{}",i)).collect();
+            let expr = if vars.len() == 1 {
+                quote! { ::lite_clojure_eval::IntoVariable::into_variable(v0) }
+            } else {
+                quote! {
+                    ::lite_clojure_eval::IntoVariable::into_variable(
+                        ::std::vec![#(::lite_clojure_eval::IntoVariable::into_variable(#vars)),*] as ::std::vec::Vec<::lite_clojure_eval::Variable>)
+                }
+            };
+            Ok((quote! { (#(#vars),*) },expr))
+        },
+        Fields::Unit => Ok((quote! {},quote! { ::lite_clojure_eval::Variable::Nil }))
+    }
+}
+
+fn named_entries(fields:&Fields) -> syn::Result<(TokenStream2,Vec<TokenStream2>)> {
+    let mut idents:Vec<&syn::Ident> = vec![];
+    let mut entries:Vec<TokenStream2> = vec![];
+    for field in fields.iter() {
+        let ident = field.ident.as_ref().unwrap();
+        let key = key_name(&parse_attrs(&field.attrs)?, ident);
+        entries.push(quote! { (#key,::lite_clojure_eval::IntoVariable::into_variable(#ident)) });
+        idents.push(ident);
+    }
+    Ok((quote! { { #(#idents),* } },entries))
+}
+
+fn expand_from_variable(input:&DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let generics = add_bounds(&input.generics, quote!(::lite_clojure_eval::FromVariable));
+    let (impl_generics,ty_generics,where_clause) = generics.split_for_impl();
+    let container = parse_attrs(&input.attrs)?;
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let build = from_fields(quote!(#name), &data.fields)?;
+            quote! { #build }
+        },
+        Data::Enum(data) => {
+            let tag = container.tag.unwrap_or_else(|| "type".to_string());
+            let mut arms:Vec<TokenStream2> = vec![];
+            for variant in data.variants.iter() {
+                let attrs = parse_attrs(&variant.attrs)?;
+                let ident = &variant.ident;
+                let key = key_name(&attrs, ident);
+                let build = match &variant.fields {
+                    Fields::Unit => quote! { ::core::result::Result::Ok(#name::#ident) },
+                    Fields::Named(_) => from_fields(quote!(#name::#ident), &variant.fields)?,
+                    Fields::Unnamed(_) => {
+                        let build = from_fields(quote!(#name::#ident), &variant.fields)?;
+                        quote! {
+                            let map = ::lite_clojure_eval::derive_support::expect_map(var)?;
+                            let value = ::lite_clojure_eval::derive_support::field::<::lite_clojure_eval::Variable>(&map,"value")?;
+                            let var = &::lite_clojure_eval::derive_support::required(value,"value")?;
+                            (|| -> ::core::result::Result<Self,::lite_clojure_eval::EvalError> { #build })().map_err(|err| err.at_path("value"))
+                        }
+                    }
+                };
+                arms.push(quote! { #key => { #build } });
+            }
+            quote! {
+                let variant = ::lite_clojure_eval::derive_support::variant_name(var,#tag)?;
+                match variant.as_str() {
+                    #(#arms,)*
+                    other => ::core::result::Result::Err(::lite_clojure_eval::EvalError::UnknownVariant(::std::string::ToString::to_string(other)))
+                }
+            }
+        },
+        Data::Union(_) => return Err(syn::Error::new_spanned(input, "unions are not supported"))
+    };
+    Ok(quote! {
+        impl #impl_generics ::lite_clojure_eval::FromVariable for #name #ty_generics #where_clause {
+            fn from_variable(var:&::lite_clojure_eval::Variable) -> ::core::result::Result<Self,::lite_clojure_eval::EvalError> {
+                #body
+            }
+        }
+    })
+}
+
+//expression reading `var` into the struct or variant at path
+fn from_fields(path:TokenStream2,fields:&Fields) -> syn::Result<TokenStream2> {
+    match fields {
+        Fields::Named(named) => {
+            let mut inits:Vec<TokenStream2> = vec![];
+            for field in named.named.iter() {
+                let ident = field.ident.as_ref().unwrap();
+                let ty = &field.ty;
+                let attrs = parse_attrs(&field.attrs)?;
+                let key = key_name(&attrs, ident);
+                let value = quote! { ::lite_clojure_eval::derive_support::field::<#ty>(&map,#key)? };
+                let init = match &attrs.default {
+                    Some(Some(default_fn)) => quote! { #value.unwrap_or_else(#default_fn) },
+                    Some(None) => quote! { #value.unwrap_or_default() },
+                    None if is_option(ty) => quote! { #value.unwrap_or_default() },
+                    None => quote! { ::lite_clojure_eval::derive_support::required(#value,#key)? }
+                };
+                inits.push(quote! { #ident: #init });
+            }
+            Ok(quote! {
+                let map = ::lite_clojure_eval::derive_support::expect_map(var)?;
+                ::core::result::Result::Ok(#path { #(#inits),* })
+            })
+        },
+        Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => {
+            let ty = &unnamed.unnamed[0].ty;
+            Ok(quote! { ::core::result::Result::Ok(#path(<#ty as ::lite_clojure_eval::FromVariable>::from_variable(var)?)) })
+        },
+        Fields::Unnamed(unnamed) => {
+            let len = unnamed.unnamed.len();
+            let elems:Vec<TokenStream2> = unnamed.unnamed.iter().enumerate().map(|(idx,field)| {
+                let ty = &field.ty;
+                quote! { ::lite_clojure_eval::derive_support::element::<#ty>(&lst,#idx)? }
+            }).collect();
+            Ok(quote! {
+                let lst = ::lite_clojure_eval::derive_support::expect_vec(var,#len)?;
+                ::core::result::Result::Ok(#path(#(#elems),*))
+            })
+        },
+        Fields::Unit => Ok(quote! { ::core::result::Result::Ok(#path) })
+    }
+}
@@ -1,13 +1,16 @@
-use std::{collections::HashMap};
+use std::{collections::HashSet, convert::TryFrom};
 
 
-use crate::{Variable,  variable::ExecScope, GcRefCell};
+use gc::Gc;
+
+use crate::{EvalError, GcRefCell, PersistentHashMap, PersistentVector, Variable, lazy_seq::{is_lazy, lazy_eq, realize_all, walk}, seq_fn::seq_items, variable::{ExInfo, ExecScope}};
  
-pub fn print(_:&mut ExecScope,args:Vec<Variable>,is_line:bool) -> Variable {
+pub fn print(scope:&mut ExecScope,args:Vec<Variable>,is_line:bool) -> Result<Variable,EvalError> {
     let mut out_string = String::default();
     let mut idx = 0;
     let args_len = args.len();
     for var in args {
+        realize_all(scope, &var)?;
         out_string.push_str(var.show_str().as_str());
         idx += 1;
         if idx != args_len {
@@ -19,132 +22,160 @@ pub fn print(_:&mut ExecScope,args:Vec<Variable>,is_line:bool) -> Variable {
     } else {
         print!("{}",out_string);
     }
-    Variable::Nil
+    Ok(Variable::Nil)
+}
+
+pub(crate) fn check_args(fn_name:&str,args:&[Variable],min:usize,max:Option<usize>) -> Result<(),EvalError> {
+    if args.len() < min || max.map(|max| args.len() > max).unwrap_or(false) {
+        return Err(EvalError::ArityError(fn_name.to_string(),args.len()));
+    }
+    Ok(())
+}
+
+pub(crate) fn type_error(fn_name:&str,var:&Variable) -> EvalError {
+    EvalError::ArgTypeError(fn_name.to_string(),var.type_name().to_string())
 }
 
-fn is_number_all_int(args:&Vec<Variable>) -> bool  {
+fn is_number_all_int(fn_name:&str,args:&[Variable]) -> Result<bool,EvalError>  {
+    let mut is_int = true;
     for arg in args {
         match arg {
-          Variable::Float(_) => { return false },
+          Variable::Float(_) => is_int = false,
           Variable::Int(_) => (),
-          err_var => {
-              log::error!("err number type {:?}",err_var); 
-              return  false; 
-          }
+          err_var => return Err(type_error(fn_name, err_var))
         } 
     }
-    return true;
+    Ok(is_int)
 }
 
-fn number_op(args:&Vec<Variable>,fint:fn(i64,i64) -> i64,ffloat:fn(f64,f64) -> f64) -> Variable {
-    let is_int = is_number_all_int( args);
+//int results are checked, None from fint is an overflow
+fn number_op(fn_name:&str,args:&[Variable],fint:fn(i64,i64) -> Option<i64>,ffloat:fn(f64,f64) -> f64) -> Result<Variable,EvalError> {
+    let is_int = is_number_all_int(fn_name,args)?;
     let mut iter = args.iter();
     if is_int {
         let mut cur:i64 = iter.next().unwrap().cast_int().unwrap_or(0i64);
-        while let Some(v) = iter.next() {
+        for v in iter {
             let vnum = v.cast_int().unwrap();
-            cur = fint(cur,vnum);
+            cur = fint(cur,vnum).ok_or_else(|| EvalError::Overflow(fn_name.to_string()))?;
         }
-        return Variable::Int(cur);
+        Ok(Variable::Int(cur))
     } else {
         let mut cur:f64 = iter.next().unwrap().cast_float().unwrap_or(0f64);
-        while let Some(v) = iter.next() {
+        for v in iter {
             let vnum = v.cast_float().unwrap();
             cur = ffloat(cur,vnum);
         }
-        return Variable::Float(cur);
+        Ok(Variable::Float(cur))
     }
-   
 }
 
-pub fn num_add(_:&mut ExecScope,args:Vec<Variable>) -> Variable {
+pub fn num_add(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
     if args.len() == 0 {
-        return Variable::Int(0);
+        return Ok(Variable::Int(0));
     }
-    return number_op( &args, |a,b| a + b, |a,b| a + b);
+    number_op("+", &args, i64::checked_add, |a,b| a + b)
 }
 
-pub fn num_sub(_:&mut ExecScope,args:Vec<Variable>) -> Variable {
-    if args.len() == 0 {
-       panic!("sum number zero args");
+pub fn num_sub(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("-", &args, 1, None)?;
+    if args.len() == 1 {
+        return number_op("-", &[Variable::Int(0),args[0].clone()], i64::checked_sub, |a,b| a - b);
     }
-    return number_op( &args, |a,b| a - b, |a,b| a - b);
+    number_op("-", &args, i64::checked_sub, |a,b| a - b)
 }
 
-pub fn num_mul(_:&mut ExecScope,args:Vec<Variable>) -> Variable {
+pub fn num_mul(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
     if args.len() == 0 {
-        return Variable::Int(1);
+        return Ok(Variable::Int(1));
     }
-    return number_op( &args, |a,b| a * b, |a,b| a * b);
+    number_op("*", &args, i64::checked_mul, |a,b| a * b)
 }
 
-pub fn num_div(_:&mut ExecScope,args:Vec<Variable>) -> Variable {
-    if args.len() == 0 {
-        panic!("num_div number zero args");
+pub fn num_div(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("/", &args, 1, None)?;
+    //floats divide by zero to infinity, ints raise
+    if is_number_all_int("/", &args)? && args[1..].iter().any(|v| v.cast_int() == Some(0)) {
+        return Err(EvalError::DivideByZero);
     }
-    return number_op( &args, |a,b| a / b, |a,b| a / b);
+    number_op("/", &args, i64::checked_div, |a,b| a / b)
 }
 
-pub fn num_lt(_:&mut ExecScope,args:Vec<Variable>) -> Variable {
-    if args.len() < 2 {
-        panic!("num_lt error");
+fn num_cmp(fn_name:&str,args:&[Variable],cmp:fn(f64,f64) -> bool) -> Result<Variable,EvalError> {
+    check_args(fn_name, args, 1, None)?;
+    let mut nums:Vec<f64> = vec![];
+    for arg in args {
+        nums.push(arg.cast_float().ok_or_else(|| type_error(fn_name, arg))?);
     }
-    let a = args[0].cast_float().unwrap();
-    let b = args[1].cast_float().unwrap();
-    Variable::Bool(a < b)
+    Ok(Variable::Bool(nums.windows(2).all(|ab| cmp(ab[0],ab[1]))))
 }
 
-pub fn num_gt(_:&mut ExecScope,args:Vec<Variable>) -> Variable {
-    if args.len() < 2 {
-        panic!("num_gt error");
-    }
-    let a = args[0].cast_float().unwrap();
-    let b = args[1].cast_float().unwrap();
-    Variable::Bool(a > b)
+pub fn num_lt(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    num_cmp("<", &args, |a,b| a < b)
+}
+
+pub fn num_gt(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    num_cmp(">", &args, |a,b| a > b)
+}
+
+pub fn num_le(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    num_cmp("<=", &args, |a,b| a <= b)
 }
 
-pub fn num_le(_:&mut ExecScope,args:Vec<Variable>) -> Variable {
-    if args.len() < 2 {
-        panic!("num_le error");
+pub fn num_ge(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    num_cmp(">=", &args, |a,b| a >= b)
+}
+
+//a lazy seq arg as a list of its items, so fns over collections take any seq
+fn realized_arg(scope:&mut ExecScope,fn_name:&str,var:Variable) -> Result<Variable,EvalError> {
+    if !is_lazy(&var) {
+        return Ok(var);
     }
-    let a = args[0].cast_float().unwrap();
-    let b = args[1].cast_float().unwrap();
-    Variable::Bool(a <= b)
+    Ok(Variable::List(GcRefCell::new(seq_items(scope, fn_name, &var)?.into())))
 }
 
-pub fn num_ge(_:&mut ExecScope,args:Vec<Variable>) -> Variable {
-    if args.len() < 2 {
-        panic!("num_ge error");
+//walks a lazy seq only up to the index
+fn nth_lazy(scope:&mut ExecScope,args:&[Variable]) -> Result<Variable,EvalError> {
+    let idx = args[1].cast_int().ok_or_else(|| type_error("nth", &args[1]))?;
+    let mut count = 0;
+    let mut found = None;
+    if idx >= 0 {
+        walk(scope, "nth", &args[0], |_,item| {
+            if count == idx as usize { found = Some(item); return Ok(false); }
+            count += 1;
+            Ok(true)
+        })?;
+    }
+    match (found,args.get(2)) {
+        (Some(item),_) => Ok(item),
+        (None,Some(default)) => Ok(default.clone()),
+        (None,None) => Err(EvalError::IndexOutOfBounds(idx,count))
     }
-    let a = args[0].cast_float().unwrap();
-    let b = args[1].cast_float().unwrap();
-    Variable::Bool(a >= b)
 }
 
-pub fn nth(_:&mut ExecScope,args:Vec<Variable>) -> Variable {
-    if args.len() < 2 {
-        panic!("nth error");
+pub fn nth(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("nth", &args, 2, Some(3))?;
+    if let Variable::Nil = args[0] {
+        return Ok(args.get(2).cloned().unwrap_or(Variable::Nil));
     }
-    let lst = args[0].cast_vec().unwrap();
-    let lst_ref:&Vec<Variable> = &lst.borrow();
-   
-    
-    let idx = args[1].cast_int().unwrap() as usize;
-    if idx < lst_ref.len() {
-        return lst_ref[idx].clone()
+    if is_lazy(&args[0]) {
+        return nth_lazy(scope, &args);
+    }
+    let lst = args[0].cast_vec().or(args[0].cast_list()).ok_or_else(|| type_error("nth", &args[0]))?;
+    let lst_ref:&PersistentVector = &lst.borrow();
+    let idx = args[1].cast_int().ok_or_else(|| type_error("nth", &args[1]))?;
+    if idx >= 0 && (idx as usize) < lst_ref.len() {
+        return Ok(lst_ref[idx as usize].clone())
     }
     if args.len() > 2 {
-       return args[2].clone();
+       return Ok(args[2].clone());
     }
-    panic!("index out range");
+    Err(EvalError::IndexOutOfBounds(idx,lst_ref.len()))
 }
 
-pub fn var_set(rt:&mut ExecScope,mut args: Vec<Variable>) -> Variable {
-   if args.len() < 2 {
-       panic!("var_set error");
-   }
-   let var_name = args.remove(0).cast_var().unwrap();
-   let set_val = args.remove(0);
+pub fn var_set(rt:&mut ExecScope,mut args: Vec<Variable>) -> Result<Variable,EvalError> {
+   check_args("var-set", &args, 2, Some(2))?;
+   let var_name = args[0].cast_var().ok_or_else(|| type_error("var-set", &args[0]))?;
+   let set_val = args.remove(1);
    let len = rt.context.sym_maps.list.len();
    let scope = &rt.context.sym_maps.list[len - 2];
    
@@ -157,123 +188,457 @@ pub fn var_set(rt:&mut ExecScope,mut args: Vec<Variable>) -> Variable {
            rt.context.stack[sym.index()] = set_val;
        }
    } else {
-       eprintln!("not found var {}",&var_name);
+       return Err(EvalError::NotFoundSymbol(var_name));
    }
-   Variable::Nil
+   Ok(Variable::Nil)
 }
 
-pub fn get(_:&mut ExecScope,args:Vec<Variable>) -> Variable {
-    let default = if args.len() > 2 { Some(args[2].clone()) } else {None };
-    match &args[0] {
+fn lookup(coll:&Variable,key:&Variable) -> Option<Variable> {
+    match coll {
         Variable::Array(arr) => {
-            let idx = args[1].cast_int().unwrap() as usize;
-            let arr_ref:&Vec<Variable> = &arr.borrow();
-            if idx as usize >= arr_ref.len() {
-                default.unwrap_or(Variable::Nil) 
-            } else {
-                arr_ref[idx].clone()
-            }
+            let arr_ref:&PersistentVector = &arr.borrow();
+            key.cast_int().filter(|idx| *idx >= 0).and_then(|idx| arr_ref.get(idx as usize)).cloned()
         },
-        Variable::Map(hash_map) => {
-            let key = &args[1];
-            let map_ref:&HashMap<Variable,Variable> = &hash_map.borrow();
-            match map_ref.get(key) {
-                Some(v) => v.clone(),
-                None => default.unwrap_or(Variable::Nil)
-            } 
+        Variable::Map(hash_map) => hash_map.borrow().get(key).cloned(),
+        _ => None
+    }
+}
+
+pub fn get(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("get", &args, 2, Some(3))?;
+    let default = args.get(2).cloned().unwrap_or(Variable::Nil);
+    Ok(lookup(&args[0], &args[1]).unwrap_or(default))
+}
+
+pub fn nthnext(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("nthnext", &args, 2, Some(2))?;
+    let idx = args[1].cast_int().ok_or_else(|| type_error("nthnext", &args[1]))?.max(0) as usize;
+    match &args[0] {
+        Variable::Array(arr) | Variable::List(arr) => {
+            let arr_ref:&PersistentVector = &arr.borrow();
+            if idx >= arr_ref.len() { return Ok(Variable::Nil); }
+            Ok(Variable::List(GcRefCell::new(arr_ref.iter().skip(idx).cloned().collect())))
         },
+        Variable::Nil => Ok(Variable::Nil),
+        other => Err(type_error("nthnext", other))
+    }
+}
+
+pub fn eq(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("=", &args, 1, None)?;
+    for ab in args.windows(2) {
+        if !lazy_eq(scope, &ab[0], &ab[1])? {
+            return Ok(Variable::Bool(false));
+        }
+    }
+    Ok(Variable::Bool(true))
+}
+
+pub fn require(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+   check_args("require", &args, 1, None)?;
+   let mod_name = args[0].cast_string().ok_or_else(|| type_error("require", &args[0]))?;
+   scope.modules.require_mod(mod_name.borrow().as_str());
+   Ok(Variable::Nil)
+}
+
+
+pub fn is_nil(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("nil?", &args, 1, Some(1))?;
+    Ok(Variable::Bool(matches!(args[0],Variable::Nil)))
+}
+
+pub fn not(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("not", &args, 1, Some(1))?;
+    Ok(Variable::Bool(!args[0].is_truthy()))
+}
+
+pub fn ex_info(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("ex-info", &args, 2, Some(3))?;
+    let message = match &args[0] {
+        Variable::String(s) => s.borrow().clone(),
+        other => other.show_str()
+    };
+    let cause = args.get(2).cloned().unwrap_or(Variable::Nil);
+    Ok(Variable::ExInfo(Gc::new(ExInfo { message, data:args[1].clone(), cause })))
+}
+
+pub fn ex_data(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("ex-data", &args, 1, Some(1))?;
+    Ok(match &args[0] {
+        Variable::ExInfo(ex) => ex.data.clone(),
         _ => Variable::Nil
-    } 
+    })
 }
 
-pub fn eq(_:&mut ExecScope,args:Vec<Variable>) -> Variable {
-    Variable::Bool(args[0] == args[1])
+pub fn ex_message(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("ex-message", &args, 1, Some(1))?;
+    Ok(match &args[0] {
+        Variable::ExInfo(ex) => Variable::String(GcRefCell::new(ex.message.clone())),
+        _ => Variable::Nil
+    })
 }
 
-pub fn require(scope:&mut ExecScope,args:Vec<Variable>) -> Variable {
-   if args.len()  == 0 {
-       log::error!("require error: zero args");
-       return Variable::Nil; 
-   }
-   if let Some(mod_name) = args[0].cast_string() {
-       scope.modules.require_mod(mod_name.borrow().as_str());
-   } else {
-       log::error!("require error: arg is not string");
-   }
-   Variable::Nil
+pub fn ex_cause(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("ex-cause", &args, 1, Some(1))?;
+    Ok(match &args[0] {
+        Variable::ExInfo(ex) => ex.cause.clone(),
+        _ => Variable::Nil
+    })
 }
 
+pub fn concat(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    let mut new_arr:Vec<Variable> = vec![];
+    for arg in args.into_iter() {
+        let arg = &realized_arg(scope, "concat", arg)?;
+        match arg {
+            Variable::Array(arr) | Variable::List(arr) => {
+                for item in arr.borrow().iter() {
+                    new_arr.push(item.clone());
+                }
+            },
+            Variable::Set(set) => new_arr.extend(set.borrow().iter().cloned()),
+            Variable::Nil => {},
+            other => return Err(type_error("concat", other))
+        }
+    }
+    Ok(Variable::Array(GcRefCell::new(new_arr.into())))
+}
 
-pub fn is_nil(_:&mut ExecScope,args:Vec<Variable>) -> Variable {
-    if args.len() == 0 { return Variable::Bool(true); }
-    match args[0] {
-        Variable::Nil => Variable::Bool(true),
-        _ => Variable::Bool(false)
+pub fn list(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    Ok(Variable::List(GcRefCell::new(args.into())))
+}
+
+pub fn vec(scope:&mut ExecScope,mut args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("vec", &args, 1, Some(1))?;
+    match &realized_arg(scope, "vec", args.remove(0))? {
+        Variable::Array(arr) | Variable::List(arr) => {
+            let arr_ref:&PersistentVector = &arr.borrow();
+            Ok(Variable::Array(GcRefCell::new(arr_ref.clone())))
+        },
+        Variable::Set(set) => Ok(Variable::Array(GcRefCell::new(set.borrow().iter().cloned().collect()))),
+        Variable::Nil => Ok(Variable::Array(GcRefCell::new(PersistentVector::new()))),
+        other => Err(type_error("vec", other))
+    }
+}
+
+pub fn hash_map(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    if !args.len().is_multiple_of(2) {
+        return Err(EvalError::ArityError("hash-map".to_string(),args.len()));
+    }
+    let mut map = PersistentHashMap::new();
+    for kv in args.chunks(2) {
+        map.insert(kv[0].clone(), kv[1].clone());
+    }
+    Ok(Variable::Map(GcRefCell::new(map)))
+}
+
+pub fn apply(scope:&mut ExecScope,mut args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("apply", &args, 1, None)?;
+    let f = args.remove(0);
+    let mut call_args:Vec<Variable> = vec![];
+    if let Some(last) = args.pop() {
+        let last = realized_arg(scope, "apply", last)?;
+        call_args.extend(args);
+        match &last {
+            Variable::Array(arr) | Variable::List(arr) => call_args.extend(arr.borrow().iter().cloned()),
+            Variable::Set(set) => call_args.extend(set.borrow().iter().cloned()),
+            Variable::Nil => {},
+            _ => call_args.push(last)
+        }
     }
+    scope.context.invoke_func2(&f, call_args, scope.modules)
+}
+
+pub fn hash_set(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    Ok(Variable::Set(GcRefCell::new(args.into_iter().collect())))
 }
 
-pub fn concat(_:&mut ExecScope,args:Vec<Variable>) -> Variable {
-    if args.len() == 0 { return Variable::Array(GcRefCell::new(vec![])); }
-    match args[0] {
-        Variable::Map(_) => {
-            todo!()
+pub fn set(scope:&mut ExecScope,mut args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("set", &args, 1, Some(1))?;
+    match &realized_arg(scope, "set", args.remove(0))? {
+        Variable::Array(arr) | Variable::List(arr) => Ok(Variable::Set(GcRefCell::new(arr.borrow().iter().cloned().collect()))),
+        Variable::Set(set) => Ok(Variable::Set(GcRefCell::new(set.borrow().clone()))),
+        Variable::Nil => Ok(Variable::Set(GcRefCell::new(HashSet::new()))),
+        other => Err(type_error("set", other))
+    }
+}
+
+pub fn contains(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("contains?", &args, 2, Some(2))?;
+    let key = &args[1];
+    let is_contains = match &args[0] {
+        Variable::Map(map) => map.borrow().contains_key(key),
+        Variable::Set(set) => set.borrow().contains(key),
+        Variable::Array(arr) => key.cast_int().map(|idx| idx >= 0 && (idx as usize) < arr.borrow().len()).unwrap_or(false),
+        _ => false
+    };
+    Ok(Variable::Bool(is_contains))
+}
+
+pub fn conj(scope:&mut ExecScope,mut args:Vec<Variable>) -> Result<Variable,EvalError> {
+    if args.is_empty() { return Ok(Variable::Array(GcRefCell::new(PersistentVector::new()))); }
+    let coll = realized_arg(scope, "conj", args.remove(0))?;
+    match &coll {
+        Variable::Array(arr) => {
+            let mut new_arr = arr.borrow().clone();
+            new_arr.extend(args);
+            Ok(Variable::Array(GcRefCell::new(new_arr)))
         },
-        Variable::Array(_) => {
-            let mut new_arr:Vec<Variable> = vec![];
-            for arg in args.iter() {
-                match arg {
-                    Variable::Array(arr) => {
-                        for item in arr.borrow().iter() {
-                            new_arr.push(item.clone());
-                        }
+        Variable::List(_) | Variable::Nil => {
+            let mut new_list:PersistentVector = args.into_iter().rev().collect();
+            if let Variable::List(lst) = &coll {
+                new_list.extend(lst.borrow().iter().cloned());
+            }
+            Ok(Variable::List(GcRefCell::new(new_list)))
+        },
+        Variable::Set(set) => {
+            let mut new_set = set.borrow().clone();
+            new_set.extend(args);
+            Ok(Variable::Set(GcRefCell::new(new_set)))
+        },
+        Variable::Map(map) => {
+            let mut new_map = map.borrow().clone();
+            for arg in args {
+                match &arg {
+                    Variable::Array(kv) if kv.borrow().len() == 2 => {
+                        let kv_ref = kv.borrow();
+                        new_map.insert(kv_ref[0].clone(), kv_ref[1].clone());
+                    },
+                    Variable::Map(other) => {
+                        new_map.extend(other.borrow().iter().map(|(k,v)| (k.clone(),v.clone())));
                     },
-                    _ => {}
+                    other => return Err(type_error("conj", other))
                 }
             }
-            return Variable::Array(GcRefCell::new(new_arr));
+            Ok(Variable::Map(GcRefCell::new(new_map)))
         },
-        _ => Variable::Nil
+        other => Err(type_error("conj", other))
     }
 }
 
-pub fn dissoc_mut(_:&mut ExecScope,mut args:Vec<Variable>) -> Variable {
-    if args.len() == 0 { 
-        log::error!("dissoc! error:zero args");
-        return Variable::Nil 
+pub fn disj(_:&mut ExecScope,mut args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("disj", &args, 1, None)?;
+    match &args.remove(0) {
+        Variable::Set(set) => {
+            let mut new_set = set.borrow().clone();
+            for arg in args.iter() {
+                new_set.remove(arg);
+            }
+            Ok(Variable::Set(GcRefCell::new(new_set)))
+        },
+        Variable::Nil => Ok(Variable::Nil),
+        other => Err(type_error("disj", other))
     }
-    let map_var = args.remove(0);
-    if let Some(map) = map_var.cast_map() {
-        for arg in args.iter() {
-            map.borrow_mut().remove(arg);
+}
+
+fn set_args(fn_name:&str,args:&[Variable]) -> Result<Vec<HashSet<Variable>>,EvalError> {
+    check_args(fn_name, args, 1, None)?;
+    let mut sets:Vec<HashSet<Variable>> = vec![];
+    for arg in args {
+        match arg {
+            Variable::Set(set) => sets.push(set.borrow().clone()),
+            Variable::Nil => sets.push(HashSet::new()),
+            other => return Err(type_error(fn_name, other))
         }
     }
-    map_var
+    Ok(sets)
+}
+
+pub fn union(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    let sets = set_args("union", &args)?;
+    Ok(Variable::Set(GcRefCell::new(sets.into_iter().flatten().collect())))
+}
+
+pub fn intersection(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    let mut sets = set_args("intersection", &args)?;
+    let mut ret = sets.remove(0);
+    for set in sets.iter() {
+        ret.retain(|v| set.contains(v));
+    }
+    Ok(Variable::Set(GcRefCell::new(ret)))
 }
 
-pub fn assoc_mut(_:&mut ExecScope,mut args:Vec<Variable>) -> Variable {
+pub fn difference(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    let mut sets = set_args("difference", &args)?;
+    let mut ret = sets.remove(0);
+    for set in sets.iter() {
+        ret.retain(|v| !set.contains(v));
+    }
+    Ok(Variable::Set(GcRefCell::new(ret)))
+}
+
+pub fn char(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("char", &args, 1, Some(1))?;
+    match &args[0] {
+        Variable::Char(chr) => Ok(Variable::Char(*chr)),
+        Variable::Int(n) => {
+            u32::try_from(*n).ok().and_then(char::from_u32).map(Variable::Char)
+                             .ok_or_else(|| EvalError::NativeError(format!("char value out of range:{}",n)))
+        },
+        other => Err(type_error("char", other))
+    }
+}
+
+pub fn int(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("int", &args, 1, Some(1))?;
+    match &args[0] {
+        Variable::Char(chr) => Ok(Variable::Int(*chr as i64)),
+        Variable::Int(n) => Ok(Variable::Int(*n)),
+        Variable::Float(f) => Ok(Variable::Int(*f as i64)),
+        other => Err(type_error("int", other))
+    }
+}
+
+//conj!, assoc! and dissoc! change the collection in place and return it,
+//every holder of the same value sees the edit, transient gives a private copy to change
+pub fn dissoc_mut(_:&mut ExecScope,mut args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("dissoc!", &args, 1, None)?;
+    let map_var = args.remove(0);
+    let map = map_var.cast_map().ok_or_else(|| type_error("dissoc!", &map_var))?;
+    for arg in args.iter() {
+        map.borrow_mut().remove(arg);
+    }
+    Ok(map_var)
+}
+
+pub fn assoc_mut(_:&mut ExecScope,mut args:Vec<Variable>) -> Result<Variable,EvalError> {
     if args.len() < 3 || ((args.len() - 1) % 2) != 0 { 
-        log::error!("assoc! error:error args count:{}",args.len());
-        return Variable::Nil 
+        return Err(EvalError::ArityError("assoc!".to_string(),args.len()));
     }
     let map_var = args.remove(0);
-    if let Some(map) = map_var.cast_map() {
-        for (index,key) in args.iter().step_by(2).enumerate() {
-            let value = args[index * 2 + 1].clone();
-            map.borrow_mut().insert(key.clone(), value);
-        }
+    let map = map_var.cast_map().ok_or_else(|| type_error("assoc!", &map_var))?;
+    for (index,key) in args.iter().step_by(2).enumerate() {
+        let value = args[index * 2 + 1].clone();
+        map.borrow_mut().insert(key.clone(), value);
     }
-    map_var
+    Ok(map_var)
 }
 
-pub fn conj_mut(_:&mut ExecScope,mut args:Vec<Variable>) -> Variable {
-    if args.len() == 0 { return Variable::Array(GcRefCell::new(vec![])); }
-    if args.len() == 1 { return args.remove(0); }
-    if let Some(list) = args.remove(0).cast_vec() {
-        for arg in args.iter() {
-            list.borrow_mut().push(arg.clone());
+pub fn conj_mut(_:&mut ExecScope,mut args:Vec<Variable>) -> Result<Variable,EvalError> {
+    if args.len() == 0 { return Ok(Variable::Array(GcRefCell::new(PersistentVector::new()))); }
+    let list_var = args.remove(0);
+    let list = list_var.cast_vec().ok_or_else(|| type_error("conj!", &list_var))?;
+    for arg in args.iter() {
+        list.borrow_mut().push(arg.clone());
+    }
+    Ok(list_var)
+}
+
+//returns a new collection, vectors accept an index up to their length
+fn assoc_one(fn_name:&str,coll:&Variable,key:Variable,value:Variable) -> Result<Variable,EvalError> {
+    match coll {
+        Variable::Map(map) => {
+            let mut new_map = map.borrow().clone();
+            new_map.insert(key, value);
+            Ok(Variable::Map(GcRefCell::new(new_map)))
+        },
+        Variable::Nil => {
+            let mut new_map = PersistentHashMap::new();
+            new_map.insert(key, value);
+            Ok(Variable::Map(GcRefCell::new(new_map)))
+        },
+        Variable::Array(arr) => {
+            let mut new_arr = arr.borrow().clone();
+            let idx = key.cast_int().ok_or_else(|| type_error(fn_name, &key))?;
+            match usize::try_from(idx) {
+                Ok(i) if i < new_arr.len() => new_arr.set(i, value),
+                Ok(i) if i == new_arr.len() => new_arr.push(value),
+                _ => return Err(EvalError::IndexOutOfBounds(idx,new_arr.len()))
+            }
+            Ok(Variable::Array(GcRefCell::new(new_arr)))
+        },
+        other => Err(type_error(fn_name, other))
+    }
+}
+
+pub fn assoc(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    if args.len() < 3 || !(args.len() - 1).is_multiple_of(2) {
+        return Err(EvalError::ArityError("assoc".to_string(),args.len()));
+    }
+    let mut ret = args[0].clone();
+    for kv in args[1..].chunks(2) {
+        ret = assoc_one("assoc", &ret, kv[0].clone(), kv[1].clone())?;
+    }
+    Ok(ret)
+}
+
+pub fn dissoc(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("dissoc", &args, 1, None)?;
+    match &args[0] {
+        Variable::Map(map) => {
+            let mut new_map = map.borrow().clone();
+            for key in args[1..].iter() {
+                new_map.remove(key);
+            }
+            Ok(Variable::Map(GcRefCell::new(new_map)))
+        },
+        Variable::Nil => Ok(Variable::Nil),
+        other => Err(type_error("dissoc", other))
+    }
+}
+
+pub fn update(scope:&mut ExecScope,mut args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("update", &args, 3, None)?;
+    let mut call_args = args.split_off(3);
+    let f = args.pop().unwrap();
+    let key = args.pop().unwrap();
+    call_args.insert(0, lookup(&args[0], &key).unwrap_or(Variable::Nil));
+    let value = scope.context.invoke_func2(&f, call_args, scope.modules)?;
+    assoc_one("update", &args[0], key, value)
+}
+
+fn assoc_in_keys(coll:&Variable,keys:&[Variable],value:Variable) -> Result<Variable,EvalError> {
+    match keys {
+        [] => Ok(value),
+        [key,rest @ ..] => {
+            let child = lookup(coll, key).unwrap_or(Variable::Nil);
+            let new_child = assoc_in_keys(&child, rest, value)?;
+            assoc_one("assoc-in", coll, key.clone(), new_child)
         }
+    }
+}
+
+pub fn assoc_in(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("assoc-in", &args, 3, Some(3))?;
+    let keys = seq_items(scope, "assoc-in", &args[1])?;
+    assoc_in_keys(&args[0], &keys, args[2].clone())
+}
+
+fn update_in_keys(scope:&mut ExecScope,coll:&Variable,keys:&[Variable],f:&Variable,args:&[Variable]) -> Result<Variable,EvalError> {
+    let (key,rest) = keys.split_first().ok_or_else(|| EvalError::ArityError("update-in".to_string(),0))?;
+    let child = lookup(coll, key).unwrap_or(Variable::Nil);
+    let new_child = if rest.is_empty() {
+        let mut call_args = vec![child];
+        call_args.extend(args.iter().cloned());
+        scope.context.invoke_func2(f, call_args, scope.modules)?
     } else {
-        log::error!("conj! fst type error");
+        update_in_keys(scope, &child, rest, f, args)?
+    };
+    assoc_one("update-in", coll, key.clone(), new_child)
+}
+
+pub fn update_in(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("update-in", &args, 3, None)?;
+    let keys = seq_items(scope, "update-in", &args[1])?;
+    update_in_keys(scope, &args[0], &keys, &args[2], &args[3..])
+}
+
+//copies the collection into a new cell, cheap for vectors and maps since their nodes are shared
+fn new_cell(fn_name:&str,var:&Variable) -> Result<Variable,EvalError> {
+    match var {
+        Variable::Array(arr) => Ok(Variable::Array(GcRefCell::new(arr.borrow().clone()))),
+        Variable::Map(map) => Ok(Variable::Map(GcRefCell::new(map.borrow().clone()))),
+        Variable::Set(set) => Ok(Variable::Set(GcRefCell::new(set.borrow().clone()))),
+        other => Err(type_error(fn_name, other))
     }
-    Variable::Nil
-}
\ No newline at end of file
+}
+
+pub fn transient(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("transient", &args, 1, Some(1))?;
+    new_cell("transient", &args[0])
+}
+
+pub fn persistent(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("persistent!", &args, 1, Some(1))?;
+    new_cell("persistent!", &args[0])
+}
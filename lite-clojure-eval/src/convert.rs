@@ -0,0 +1,289 @@
+use std::{collections::{HashMap, HashSet}, hash::Hash, rc::Rc};
+
+use crate::{EvalError, ExecScope, GcRefCell, UserData, Variable, variable::NativeClosureFn};
+
+pub trait FromVariable: Sized {
+    //trailing optional args of a registered fn may be left out and read as nil
+    const OPTIONAL:bool = false;
+
+    fn from_variable(var:&Variable) -> Result<Self,EvalError>;
+}
+
+pub trait IntoVariable {
+    fn into_variable(self) -> Variable;
+}
+
+fn mismatch(expected:&str,var:&Variable) -> EvalError {
+    EvalError::TypeMismatch(expected.to_string(),var.type_name().to_string())
+}
+
+impl FromVariable for Variable {
+    fn from_variable(var:&Variable) -> Result<Self,EvalError> {
+        Ok(var.clone())
+    }
+}
+
+impl FromVariable for i64 {
+    fn from_variable(var:&Variable) -> Result<Self,EvalError> {
+        var.cast_int().ok_or_else(|| mismatch("int", var))
+    }
+}
+
+impl FromVariable for f64 {
+    fn from_variable(var:&Variable) -> Result<Self,EvalError> {
+        var.cast_float().ok_or_else(|| mismatch("float", var))
+    }
+}
+
+impl FromVariable for bool {
+    fn from_variable(var:&Variable) -> Result<Self,EvalError> {
+        var.cast_bool().ok_or_else(|| mismatch("bool", var))
+    }
+}
+
+impl FromVariable for char {
+    fn from_variable(var:&Variable) -> Result<Self,EvalError> {
+        match var {
+            Variable::Char(chr) => Ok(*chr),
+            other => Err(mismatch("char", other))
+        }
+    }
+}
+
+impl FromVariable for String {
+    fn from_variable(var:&Variable) -> Result<Self,EvalError> {
+        var.cast_string().map(|s| s.borrow().clone()).ok_or_else(|| mismatch("string", var))
+    }
+}
+
+impl FromVariable for Rc<UserData> {
+    fn from_variable(var:&Variable) -> Result<Self,EvalError> {
+        var.cast_userdata().ok_or_else(|| mismatch("userdata", var))
+    }
+}
+
+impl<T:FromVariable> FromVariable for Option<T> {
+    const OPTIONAL:bool = true;
+
+    fn from_variable(var:&Variable) -> Result<Self,EvalError> {
+        match var {
+            Variable::Nil => Ok(None),
+            other => T::from_variable(other).map(Some)
+        }
+    }
+}
+
+impl<T:FromVariable> FromVariable for Vec<T> {
+    fn from_variable(var:&Variable) -> Result<Self,EvalError> {
+        match var {
+            Variable::Array(lst) | Variable::List(lst) => {
+                lst.borrow().iter().enumerate()
+                   .map(|(idx,v)| T::from_variable(v).map_err(|err| err.at_path(&format!("[{}]",idx))))
+                   .collect()
+            },
+            other => Err(mismatch("vector", other))
+        }
+    }
+}
+
+impl<T:FromVariable + Eq + Hash> FromVariable for HashSet<T> {
+    fn from_variable(var:&Variable) -> Result<Self,EvalError> {
+        match var {
+            Variable::Set(set) => set.borrow().iter().map(T::from_variable).collect(),
+            other => Err(mismatch("set", other))
+        }
+    }
+}
+
+impl<K:FromVariable + Eq + Hash,V:FromVariable> FromVariable for HashMap<K,V> {
+    fn from_variable(var:&Variable) -> Result<Self,EvalError> {
+        match var {
+            Variable::Map(map) => {
+                map.borrow().iter().map(|(k,v)| {
+                    let value = V::from_variable(v).map_err(|err| err.at_path(&k.show_str()))?;
+                    Ok((K::from_variable(k)?,value))
+                }).collect()
+            },
+            other => Err(mismatch("map", other))
+        }
+    }
+}
+
+impl IntoVariable for Variable {
+    fn into_variable(self) -> Variable { self }
+}
+
+impl IntoVariable for () {
+    fn into_variable(self) -> Variable { Variable::Nil }
+}
+
+impl IntoVariable for i64 {
+    fn into_variable(self) -> Variable { Variable::Int(self) }
+}
+
+impl IntoVariable for f64 {
+    fn into_variable(self) -> Variable { Variable::Float(self) }
+}
+
+impl IntoVariable for bool {
+    fn into_variable(self) -> Variable { Variable::Bool(self) }
+}
+
+impl IntoVariable for char {
+    fn into_variable(self) -> Variable { Variable::Char(self) }
+}
+
+impl IntoVariable for String {
+    fn into_variable(self) -> Variable { Variable::from(self) }
+}
+
+impl IntoVariable for &str {
+    fn into_variable(self) -> Variable { Variable::from(self) }
+}
+
+impl IntoVariable for UserData {
+    fn into_variable(self) -> Variable { Variable::from(self) }
+}
+
+impl IntoVariable for Rc<UserData> {
+    fn into_variable(self) -> Variable { Variable::UserData(self) }
+}
+
+impl<T:IntoVariable> IntoVariable for Option<T> {
+    fn into_variable(self) -> Variable {
+        self.map(T::into_variable).unwrap_or(Variable::Nil)
+    }
+}
+
+impl<T:IntoVariable> IntoVariable for Vec<T> {
+    fn into_variable(self) -> Variable {
+        Variable::Array(GcRefCell::new(self.into_iter().map(T::into_variable).collect()))
+    }
+}
+
+impl<T:IntoVariable> IntoVariable for HashSet<T> {
+    fn into_variable(self) -> Variable {
+        Variable::Set(GcRefCell::new(self.into_iter().map(T::into_variable).collect()))
+    }
+}
+
+impl<K:IntoVariable,V:IntoVariable> IntoVariable for HashMap<K,V> {
+    fn into_variable(self) -> Variable {
+        Variable::Map(GcRefCell::new(self.into_iter().map(|(k,v)| (k.into_variable(),v.into_variable())).collect()))
+    }
+}
+
+//errors a registered fn may return, EvalError passes through so catch clauses still see its kind
+pub trait IntoEvalError {
+    fn into_eval_error(self,fn_name:&str) -> EvalError;
+}
+
+impl IntoEvalError for EvalError {
+    fn into_eval_error(self,_:&str) -> EvalError { self }
+}
+
+impl IntoEvalError for anyhow::Error {
+    fn into_eval_error(self,fn_name:&str) -> EvalError {
+        EvalError::NativeError(format!("native func {} error:{}",fn_name,self))
+    }
+}
+
+pub trait NativeFunc<Args> {
+    fn into_native(self,fn_name:&str) -> Rc<NativeClosureFn>;
+}
+
+macro_rules! impl_native_func {
+    ($count:expr $(,$arg:ident)*) => {
+        impl<F,R,E $(,$arg)*> NativeFunc<($($arg,)*)> for F
+        where F:Fn($($arg),*) -> Result<R,E> + 'static,
+              R:IntoVariable,
+              E:IntoEvalError,
+              $($arg:FromVariable,)*
+        {
+            #[allow(non_snake_case,unused_mut,unused_variables)]
+            fn into_native(self,fn_name:&str) -> Rc<NativeClosureFn> {
+                let fn_name = fn_name.to_string();
+                let optional:[bool;$count] = [$($arg::OPTIONAL),*];
+                let required = $count - optional.iter().rev().take_while(|opt| **opt).count();
+                Rc::new(move |_:&mut ExecScope,args:Vec<Variable>| {
+                    if args.len() < required || args.len() > $count {
+                        return Err(EvalError::ArityError(fn_name.clone(),args.len()));
+                    }
+                    let nil = Variable::Nil;
+                    let mut iter = args.iter().chain(std::iter::repeat(&nil)).enumerate();
+                    $(
+                        let (idx,var) = iter.next().unwrap();
+                        let $arg = $arg::from_variable(var).map_err(|err| EvalError::ArgError(fn_name.clone(),idx,Box::new(err)))?;
+                    )*
+                    self($($arg),*).map(R::into_variable).map_err(|err| err.into_eval_error(&fn_name))
+                })
+            }
+        }
+    };
+}
+
+impl_native_func!(0);
+impl_native_func!(1,A);
+impl_native_func!(2,A,B);
+impl_native_func!(3,A,B,C);
+impl_native_func!(4,A,B,C,D);
+impl_native_func!(5,A,B,C,D,G);
+impl_native_func!(6,A,B,C,D,G,H);
+
+//used by the code #[derive(FromVariable, IntoVariable)] expands to
+#[doc(hidden)]
+pub mod derive_support {
+    use crate::{EvalError, GcRefCell, PersistentHashMap, Variable};
+    use super::{FromVariable, mismatch};
+
+    pub fn keyword(name:&str) -> Variable {
+        Variable::Keyword(GcRefCell::new(format!(":{}",name)))
+    }
+
+    pub fn new_map(entries:Vec<(&str,Variable)>) -> Variable {
+        let map:PersistentHashMap = entries.into_iter().map(|(k,v)| (keyword(k),v)).collect();
+        Variable::Map(GcRefCell::new(map))
+    }
+
+    pub fn expect_map(var:&Variable) -> Result<PersistentHashMap,EvalError> {
+        match var {
+            Variable::Map(map) => Ok(map.borrow().clone()),
+            other => Err(mismatch("map", other))
+        }
+    }
+
+    pub fn expect_vec(var:&Variable,len:usize) -> Result<Vec<Variable>,EvalError> {
+        match var {
+            Variable::Array(lst) | Variable::List(lst) if lst.borrow().len() == len => Ok(lst.borrow().to_vec()),
+            other => Err(mismatch(&format!("vector of {}",len), other))
+        }
+    }
+
+    //None when the key is absent, conversion errors carry the field name
+    pub fn field<T:FromVariable>(map:&PersistentHashMap,name:&str) -> Result<Option<T>,EvalError> {
+        match map.get(&keyword(name)) {
+            Some(var) => T::from_variable(var).map(Some).map_err(|err| err.at_path(name)),
+            None => Ok(None)
+        }
+    }
+
+    pub fn required<T>(value:Option<T>,name:&str) -> Result<T,EvalError> {
+        value.ok_or_else(|| EvalError::MissingField.at_path(name))
+    }
+
+    pub fn element<T:FromVariable>(lst:&[Variable],idx:usize) -> Result<T,EvalError> {
+        T::from_variable(&lst[idx]).map_err(|err| err.at_path(&format!("[{}]",idx)))
+    }
+
+    //unit variants are keywords, the others a map tagged by tag_key
+    pub fn variant_name(var:&Variable,tag_key:&str) -> Result<String,EvalError> {
+        let tag = match var {
+            Variable::Map(map) => map.borrow().get(&keyword(tag_key)).cloned().ok_or_else(|| EvalError::MissingField.at_path(tag_key))?,
+            other => other.clone()
+        };
+        match &tag {
+            Variable::Keyword(k) => Ok(k.borrow().trim_start_matches(':').to_string()),
+            other => Err(mismatch("keyword", other))
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::fmt::Display;
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, EnumAccess, Expected, IntoDeserializer, MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor};
+
+use crate::{EvalError, PersistentHashMap, Variable, convert::derive_support::keyword};
+
+/*
+  keywords and symbols read as strings without the leading ':'
+  vectors, lists and sets read as sequences, nil as none or unit
+  lazy seqs read as sequences once realized, a pending one is an Unrealized error
+  enums read from a keyword (unit variant) or a map tagged {:type :Variant ...} as written by the derive,
+  struct variant fields sit beside the tag, newtype and tuple variants keep their value under :value
+*/
+pub fn from_variable<T:DeserializeOwned>(var:Variable) -> Result<T,EvalError> {
+    T::deserialize(Deserializer::new(&var))
+}
+
+impl de::Error for EvalError {
+    fn custom<T:Display>(msg:T) -> Self {
+        EvalError::NativeError(msg.to_string())
+    }
+
+    fn invalid_type(unexp:Unexpected,exp:&dyn Expected) -> Self {
+        let found = match unexp {
+            Unexpected::Bool(_) => "bool".to_string(),
+            Unexpected::Signed(_) | Unexpected::Unsigned(_) => "int".to_string(),
+            Unexpected::Float(_) => "float".to_string(),
+            Unexpected::Char(_) => "char".to_string(),
+            Unexpected::Str(_) => "string".to_string(),
+            Unexpected::Unit | Unexpected::Option => "nil".to_string(),
+            Unexpected::Seq => "vector".to_string(),
+            Unexpected::Map => "map".to_string(),
+            other => other.to_string()
+        };
+        EvalError::TypeMismatch(exp.to_string(),found)
+    }
+
+    fn unknown_variant(variant:&str,_:&'static [&'static str]) -> Self {
+        EvalError::UnknownVariant(variant.to_string())
+    }
+
+    fn missing_field(field:&'static str) -> Self {
+        EvalError::MissingField.at_path(field)
+    }
+}
+
+pub struct Deserializer<'a> {
+    var:&'a Variable
+}
+
+impl<'a> Deserializer<'a> {
+    pub fn new(var:&'a Variable) -> Self {
+        Deserializer { var }
+    }
+}
+
+//path segment of a map key, keywords drop the ':'
+pub(crate) fn key_segment(key:&Variable) -> String {
+    match key {
+        Variable::Keyword(k) => k.borrow().trim_start_matches(':').to_string(),
+        Variable::String(s) => s.borrow().clone(),
+        other => other.show_str()
+    }
+}
+
+impl<'de,'a> de::Deserializer<'de> for Deserializer<'a> {
+    type Error = EvalError;
+
+    fn deserialize_any<V:Visitor<'de>>(self,visitor:V) -> Result<V::Value,EvalError> {
+        match self.var {
+            Variable::Nil => visitor.visit_unit(),
+            Variable::Bool(b) => visitor.visit_bool(*b),
+            Variable::Int(n) => visitor.visit_i64(*n),
+            Variable::Float(n) => visitor.visit_f64(*n),
+            Variable::Char(chr) => visitor.visit_char(*chr),
+            Variable::String(s) => visitor.visit_string(s.borrow().clone()),
+            Variable::Keyword(k) => visitor.visit_string(k.borrow().trim_start_matches(':').to_string()),
+            Variable::Symbol(_) => visitor.visit_string(self.var.show_str()),
+            Variable::Array(lst) | Variable::List(lst) => visitor.visit_seq(SeqDeserializer::new(lst.borrow().to_vec())),
+            Variable::LazySeq(lazy) => match lazy.realized_items() {
+                (items,false) => visitor.visit_seq(SeqDeserializer::new(items)),
+                (_,true) => Err(EvalError::Unrealized)
+            },
+            Variable::Set(set) => visitor.visit_seq(SeqDeserializer::new(set.borrow().iter().cloned().collect())),
+            Variable::Map(map) => visitor.visit_map(MapDeserializer::new(map.borrow().iter().map(|(k,v)| (k.clone(),v.clone())).collect())),
+            other => Err(EvalError::TypeMismatch("data".to_string(),other.type_name().to_string()))
+        }
+    }
+
+    fn deserialize_option<V:Visitor<'de>>(self,visitor:V) -> Result<V::Value,EvalError> {
+        match self.var {
+            Variable::Nil => visitor.visit_none(),
+            _ => visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V:Visitor<'de>>(self,_:&'static str,visitor:V) -> Result<V::Value,EvalError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V:Visitor<'de>>(self,_:&'static str,_:&'static [&'static str],visitor:V) -> Result<V::Value,EvalError> {
+        match self.var {
+            Variable::Keyword(_) | Variable::String(_) | Variable::Symbol(_) => {
+                visitor.visit_enum(EnumDeserializer { variant:self.var.clone(), map:None })
+            },
+            Variable::Map(map) => {
+                let mut map = map.borrow().clone();
+                let variant = map.remove(&keyword(ENUM_TAG)).ok_or_else(|| EvalError::MissingField.at_path(ENUM_TAG))?;
+                visitor.visit_enum(EnumDeserializer { variant, map:Some(map) })
+            },
+            other => Err(EvalError::TypeMismatch("keyword or tagged map".to_string(),other.type_name().to_string()))
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct SeqDeserializer {
+    iter:std::iter::Enumerate<std::vec::IntoIter<Variable>>,
+    len:usize
+}
+
+impl SeqDeserializer {
+    fn new(lst:Vec<Variable>) -> Self {
+        SeqDeserializer { len:lst.len(), iter:lst.into_iter().enumerate() }
+    }
+}
+
+impl<'de> SeqAccess<'de> for SeqDeserializer {
+    type Error = EvalError;
+
+    fn next_element_seed<T:DeserializeSeed<'de>>(&mut self,seed:T) -> Result<Option<T::Value>,EvalError> {
+        match self.iter.next() {
+            Some((idx,var)) => {
+                seed.deserialize(Deserializer::new(&var)).map(Some).map_err(|err| err.at_path(&format!("[{}]",idx)))
+            },
+            None => Ok(None)
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+struct MapDeserializer {
+    iter:std::vec::IntoIter<(Variable,Variable)>,
+    value:Option<(String,Variable)>,
+    len:usize
+}
+
+impl MapDeserializer {
+    fn new(entries:Vec<(Variable,Variable)>) -> Self {
+        MapDeserializer { len:entries.len(), iter:entries.into_iter(), value:None }
+    }
+}
+
+impl<'de> MapAccess<'de> for MapDeserializer {
+    type Error = EvalError;
+
+    fn next_key_seed<K:DeserializeSeed<'de>>(&mut self,seed:K) -> Result<Option<K::Value>,EvalError> {
+        match self.iter.next() {
+            Some((k,v)) => {
+                let segment = key_segment(&k);
+                let key = seed.deserialize(Deserializer::new(&k)).map_err(|err| err.at_path(&segment))?;
+                self.value = Some((segment,v));
+                Ok(Some(key))
+            },
+            None => Ok(None)
+        }
+    }
+
+    fn next_value_seed<V:DeserializeSeed<'de>>(&mut self,seed:V) -> Result<V::Value,EvalError> {
+        let (segment,var) = self.value.take().ok_or_else(|| EvalError::NativeError("map value before key".to_string()))?;
+        seed.deserialize(Deserializer::new(&var)).map_err(|err| err.at_path(&segment))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+//the tag key of enum maps and the key holding newtype and tuple variant values
+pub(crate) const ENUM_TAG:&str = "type";
+pub(crate) const ENUM_VALUE:&str = "value";
+
+struct EnumDeserializer {
+    variant:Variable,
+    map:Option<PersistentHashMap>
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = EvalError;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V:DeserializeSeed<'de>>(self,seed:V) -> Result<(V::Value,VariantDeserializer),EvalError> {
+        let name:de::value::StringDeserializer<EvalError> = match &self.variant {
+            Variable::Keyword(_) | Variable::String(_) | Variable::Symbol(_) => key_segment(&self.variant).into_deserializer(),
+            other => return Err(EvalError::TypeMismatch("keyword".to_string(),other.type_name().to_string()).at_path(ENUM_TAG))
+        };
+        let variant = seed.deserialize(name)?;
+        Ok((variant,VariantDeserializer { map:self.map }))
+    }
+}
+
+//the rest of the tagged map, None when the variant was a bare keyword
+struct VariantDeserializer {
+    map:Option<PersistentHashMap>
+}
+
+impl VariantDeserializer {
+    fn deserialize_value<T>(self,f:impl FnOnce(Deserializer) -> Result<T,EvalError>) -> Result<T,EvalError> {
+        let map = self.map.ok_or_else(|| EvalError::TypeMismatch("tagged map".to_string(),"keyword".to_string()))?;
+        let var = map.get(&keyword(ENUM_VALUE)).ok_or_else(|| EvalError::MissingField.at_path(ENUM_VALUE))?;
+        f(Deserializer::new(var)).map_err(|err| err.at_path(ENUM_VALUE))
+    }
+}
+
+impl<'de> VariantAccess<'de> for VariantDeserializer {
+    type Error = EvalError;
+
+    fn unit_variant(self) -> Result<(),EvalError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T:DeserializeSeed<'de>>(self,seed:T) -> Result<T::Value,EvalError> {
+        self.deserialize_value(|de| seed.deserialize(de))
+    }
+
+    fn tuple_variant<V:Visitor<'de>>(self,_:usize,visitor:V) -> Result<V::Value,EvalError> {
+        self.deserialize_value(|de| de::Deserializer::deserialize_seq(de,visitor))
+    }
+
+    fn struct_variant<V:Visitor<'de>>(self,_:&'static [&'static str],visitor:V) -> Result<V::Value,EvalError> {
+        let map = self.map.ok_or_else(|| EvalError::TypeMismatch("tagged map".to_string(),"keyword".to_string()))?;
+        visitor.visit_map(MapDeserializer::new(map.iter().map(|(k,v)| (k.clone(),v.clone())).collect()))
+    }
+}
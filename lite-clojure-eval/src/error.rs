@@ -1,8 +1,110 @@
+use std::{collections::HashMap, fmt::{Display, Formatter}};
+
+use gc::Gc;
+
+use crate::variable::{ExInfo, GcRefCell, Variable};
+
 #[derive(Debug)]
 pub enum EvalError {
     ZeroFnList,
     NotFoundSymbol(String),
     TypeCastError,
     ListFirstMustFunction,
-    FunctionArgCountError
-}
\ No newline at end of file
+    FunctionArgCountError,
+    ArityError(String,usize),
+    ArgTypeError(String,String),
+    IndexOutOfBounds(i64,usize),
+    DivideByZero,
+    Overflow(String),
+    NativeError(String),
+    TypeMismatch(String,String),
+    ArgError(String,usize,Box<EvalError>),
+    MissingField,
+    UnknownVariant(String),
+    AtPath(String,Box<EvalError>),
+    Unrealized,
+    Thrown(Variable)
+}
+
+impl EvalError {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EvalError::ZeroFnList => "ZeroFnList",
+            EvalError::NotFoundSymbol(_) => "NotFoundSymbol",
+            EvalError::TypeCastError => "TypeCastError",
+            EvalError::ListFirstMustFunction => "ListFirstMustFunction",
+            EvalError::FunctionArgCountError => "FunctionArgCountError",
+            EvalError::ArityError(_,_) => "ArityError",
+            EvalError::ArgTypeError(_,_) => "ArgTypeError",
+            EvalError::IndexOutOfBounds(_,_) => "IndexOutOfBounds",
+            EvalError::DivideByZero => "DivideByZero",
+            EvalError::Overflow(_) => "Overflow",
+            EvalError::NativeError(_) => "NativeError",
+            EvalError::TypeMismatch(_,_) => "TypeMismatch",
+            EvalError::ArgError(_,_,_) => "ArgError",
+            EvalError::MissingField => "MissingField",
+            EvalError::UnknownVariant(_) => "UnknownVariant",
+            EvalError::AtPath(_,err) => err.name(),
+            EvalError::Unrealized => "Unrealized",
+            EvalError::Thrown(_) => "Thrown"
+        }
+    }
+
+    //prefixes the path of a conversion error, a segment is a field name or [index]
+    pub fn at_path(self,segment:&str) -> EvalError {
+        match self {
+            EvalError::AtPath(path,err) => {
+                let sep = if path.starts_with('[') { "" } else { "." };
+                EvalError::AtPath(format!("{}{}{}",segment,sep,path),err)
+            },
+            err => EvalError::AtPath(segment.to_string(),Box::new(err))
+        }
+    }
+
+    //(catch Exception e ..) catches everything, (catch ExceptionInfo e ..) only ex-info values
+    pub fn is_match(&self,class:&str) -> bool {
+        match class {
+            "Exception" | "Throwable" | "Error" | ":default" => true,
+            "ExceptionInfo" | "clojure.lang.ExceptionInfo" => matches!(self,EvalError::Thrown(Variable::ExInfo(_))),
+            "ArithmeticException" => matches!(self,EvalError::DivideByZero | EvalError::Overflow(_)),
+            name => self.name() == name
+        }
+    }
+
+    //value bound by a catch clause, internal errors become ex-info with a :type in the data
+    pub fn to_variable(&self) -> Variable {
+        match self {
+            EvalError::Thrown(var) => var.clone(),
+            err => {
+                let mut data:HashMap<Variable,Variable> = HashMap::new();
+                data.insert(Variable::Keyword(GcRefCell::new(":type".to_string())),
+                            Variable::Keyword(GcRefCell::new(format!(":{}",err.name()))));
+                Variable::ExInfo(Gc::new(ExInfo { message:err.to_string(), data:Variable::from(data), cause:Variable::Nil }))
+            }
+        }
+    }
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::NotFoundSymbol(name) => write!(f,"not found symbol:{}",name),
+            EvalError::ArityError(name,count) => write!(f,"wrong number of args ({}) passed to {}",count,name),
+            EvalError::ArgTypeError(name,type_name) => write!(f,"{} does not accept {}",name,type_name),
+            EvalError::IndexOutOfBounds(idx,len) => write!(f,"index {} out of bounds for length {}",idx,len),
+            EvalError::DivideByZero => write!(f,"divide by zero"),
+            EvalError::Overflow(name) => write!(f,"integer overflow in {}",name),
+            EvalError::NativeError(msg) => write!(f,"{}",msg),
+            EvalError::TypeMismatch(expected,found) => write!(f,"expected {}, found {}",expected,found),
+            EvalError::ArgError(name,idx,err) => write!(f,"{} arg {}: {}",name,idx,err),
+            EvalError::MissingField => write!(f,"missing field"),
+            EvalError::UnknownVariant(name) => write!(f,"unknown variant {}",name),
+            EvalError::AtPath(path,err) => write!(f,"{}: {}",path,err),
+            EvalError::Unrealized => write!(f,"lazy seq is not realized"),
+            EvalError::Thrown(var) => write!(f,"thrown:{}",var.show_str()),
+            err => write!(f,"{}",err.name())
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
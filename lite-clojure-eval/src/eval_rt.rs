@@ -1,5 +1,6 @@
 use std::path::PathBuf;
-use crate::{Variable, exec_context::ExecContext,EvalError, module::EvalModules};
+use lite_clojure_parser::macros::MacroRegistry;
+use crate::{Variable, exec_context::ExecContext,EvalError, module::EvalModules, convert::NativeFunc, lazy_seq::realize_all, math_fn::Rng, variable::ExecScope};
 
 
 pub struct EvalRT {
@@ -32,6 +33,12 @@ impl EvalRT {
         self.main_ctx.invoke_func2(fn_var, args, &mut self.modules)
     }
     
+    //realizes every lazy seq inside var, e.g. before converting it to json
+    pub fn realize(&mut self,var:&Variable) -> Result<(),EvalError> {
+        let mut scope = ExecScope { context:&mut self.main_ctx, modules:&mut self.modules };
+        realize_all(&mut scope, var)
+    }
+
     pub fn eval_string(&mut self,file_name:String,code_string:&str) -> Option<Variable> {
        self.main_ctx.eval_string(file_name, code_string, &mut self.modules)
     }
@@ -41,6 +48,11 @@ impl EvalRT {
         self.eval_string(String::from(path), &code)
     }
 
+    //same as (set-rand-seed! seed), rand and rand-int replay the same numbers for a seed
+    pub fn set_rand_seed(&mut self,seed:u64) {
+        self.modules.rng = Rng::new(seed);
+    }
+
     pub fn main_context(&mut self) -> &mut ExecContext {
         &mut self.main_ctx
     }
@@ -49,6 +61,16 @@ impl EvalRT {
         &mut self.modules.prelude
     }
 
+    //args and the return value are converted with FromVariable/IntoVariable
+    pub fn register_fn<Args>(&mut self,name:&str,f:impl NativeFunc<Args>) {
+        let native = f.into_native(name);
+        self.global_context().push_native_closure(name, move |scope,args| native(scope,args));
+    }
+
+    pub fn macros(&self) -> &MacroRegistry {
+        &self.modules.macros
+    }
+
     pub fn add_module(&mut self,mod_name:&str,code_string:&str) {
         self.modules.require_mod_str(mod_name,code_string)
     }
@@ -1,4 +1,6 @@
 use crate::Variable;
+use crate::PersistentHashMap;
+use crate::quote;
 use crate::EvalError;
 use crate::module::EvalModules;
 use crate::sym_scope::SymbolScope;
@@ -15,8 +17,9 @@ use std::usize;
 use gc::Gc;
 use gc::GcCell;
 use anyhow::Result;
-use lite_clojure_parser::ast::parse_ast;
-use lite_clojure_parser::expr::Expr;
+use lite_clojure_parser::ast::{parse_cexprs, TranslateToAST};
+use lite_clojure_parser::cexpr::CExpr;
+use lite_clojure_parser::expr::{Catch, Expr, ExprKind, FnArity};
 use lite_clojure_parser::ast::ASTModule;
 use lite_clojure_parser::cexpr::Number;
 use lite_clojure_parser::value::{Symbol as ASTSymbol};
@@ -24,7 +27,8 @@ use lite_clojure_parser::value::{Symbol as ASTSymbol};
 pub struct ExecContext {
     pub(crate) stack: Vec<Variable>,
     call_stack:Vec<Callstack>,
-    pub sym_maps:SymbolScopes
+    pub sym_maps:SymbolScopes,
+    macros:HashMap<String,Variable>
 }
 
 #[derive(Debug,Clone)]
@@ -35,12 +39,22 @@ struct Callstack {
     pub(crate) index: usize,
 }
 
+//state to restore when an error unwinds past it
+#[derive(Debug,Clone,Copy)]
+struct SavePoint {
+    stack_len:usize,
+    call_len:usize,
+    scope_len:usize,
+    let_len:usize
+}
+
 impl Default for ExecContext {
     fn default() -> Self {
         ExecContext { 
             stack: vec![], 
             call_stack: vec![Callstack {index : 0 ,need_loop:false,is_recur:false,is_let:false}], 
-            sym_maps: SymbolScopes::new() 
+            sym_maps: SymbolScopes::new(),
+            macros: HashMap::new()
         }
     }
 }
@@ -74,7 +88,7 @@ impl ExecContext {
     pub fn push_native_fn(
         &mut self,
         name: &str,
-        f: fn(&mut ExecScope, Vec<Variable>) -> Variable
+        f: fn(&mut ExecScope, Vec<Variable>) -> Result<Variable,EvalError>
     ) {
         let f_var = Variable::Function(Gc::new(Function::NativeFn(f)));
         self.stack.push(f_var);
@@ -82,6 +96,18 @@ impl ExecContext {
         self.sym_maps.top_scope().push_sym(fn_sym);
     }
 
+    //captured state is not traced by the gc, so it should not own script values
+    pub fn push_native_closure(
+        &mut self,
+        name: &str,
+        f: impl Fn(&mut ExecScope, Vec<Variable>) -> Result<Variable,EvalError> + 'static
+    ) {
+        let f_var = Variable::Function(Gc::new(Function::NativeClosure(Rc::new(f))));
+        self.stack.push(f_var);
+        let fn_sym = Symbol::val(name.to_string(), self.stack.len() - 1);
+        self.sym_maps.top_scope().push_sym(fn_sym);
+    }
+
     pub fn eval_ast_module(&mut self,ast_module:ASTModule,modules:&mut EvalModules) -> Option<Variable> {
         if ast_module.exprs.len() == 0 {
             return  None;
@@ -89,51 +115,101 @@ impl ExecContext {
         let last_idx = ast_module.exprs.len() - 1;
         for idx in 0..ast_module.exprs.len() {
             let expr = &ast_module.exprs[idx];
+            let point = self.save_point();
             if let Err(err) = self.eval_expr(expr,idx == last_idx,modules) {
-                log::error!("{} error:{:?}",&ast_module.file_name,err);
+                self.unwind(point);
+                log::error!("{} error:{}",&expr.span,err);
             }
         }
         self.stack.last().map(|v| v.clone())
     }
 
     pub fn eval_string(&mut self,file_name:String,code_string:&str,modules:&mut EvalModules) -> Option<Variable> {
-        match parse_ast(file_name.clone(), code_string) {
-            Ok(ast_module) => {
-                self.eval_ast_module(ast_module,modules)
-            },
+        //forms are translated one by one, so a defmacro is visible to the forms after it
+        let (cexprs,meta_table) = match parse_cexprs(&file_name, code_string) {
+            Ok(parsed) => parsed,
             Err(err) => {
                 log::error!("{} parse error:{:?}",&file_name,err);
-                None
+                return None;
+            }
+        };
+        let forms:Vec<CExpr> = cexprs.into_iter().filter(|e| !e.is_comment()).collect();
+        if forms.is_empty() {
+            return None;
+        }
+        let last_idx = forms.len() - 1;
+        let mut trans = TranslateToAST::with_macros(file_name, vec![], meta_table, modules.macros.clone());
+        let mut ret = None;
+        for (idx,cexpr) in forms.into_iter().enumerate() {
+            let az = trans.translate_form(cexpr, &mut ExecScope {context:self,modules });
+            match az {
+                Some(Ok(expr)) => {
+                    let point = self.save_point();
+                    match self.eval_expr(&expr,idx == last_idx,modules) {
+                        //a def leaves its value on the stack as the slot of the new symbol
+                        Ok(()) if idx == last_idx && matches!(expr.kind,ExprKind::Def(..)) => ret = self.stack.last().cloned(),
+                        Ok(()) if idx == last_idx => ret = self.stack.pop(),
+                        Ok(()) => (),
+                        Err(err) => {
+                            self.unwind(point);
+                            log::error!("{} error:{}",&expr.span,err);
+                        }
+                    }
+                },
+                Some(Err(err)) => log::error!("analyze error:{}",err),
+                None => ()
             }
         }
+        ret
     }
 
     pub fn invoke_func(&mut self,fn_name:&str,args:Vec<Variable>,modules:&mut EvalModules) -> Result<Variable,EvalError> {
         let fn_var = self.find_symbol(None,fn_name,modules).ok_or(EvalError::NotFoundSymbol(fn_name.to_string()))?;
         let f = fn_var.cast_function().ok_or(EvalError::TypeCastError)?;
+        let point = self.save_point();
         let start_index = self.stack.len();
         self.stack.push(fn_var);
         for arg in args.iter() {
             self.stack.push(arg.clone());
         }
         
-        self.run_function(&f, start_index, true, args, start_index,modules)?;
+        if let Err(err) = self.run_function(&f, start_index, true, args, start_index,modules) {
+            self.unwind(point);
+            return Err(err);
+        }
         Ok(self.stack.pop().unwrap())
     }
 
     pub fn invoke_func2(&mut self,fn_var:&Variable,args:Vec<Variable>,modules:&mut EvalModules) -> Result<Variable,EvalError> {
         if let Variable::Function(f) = fn_var {
+            let point = self.save_point();
             let start_index = self.stack.len();
             self.stack.push(fn_var.clone());
             for arg in args.iter() {
                 self.stack.push(arg.clone());
             }
-            self.run_function(&f, start_index, true, args, start_index,modules)?;
+            if let Err(err) = self.run_function(&f, start_index, true, args, start_index,modules) {
+                self.unwind(point);
+                return Err(err);
+            }
             return Ok(self.stack.pop().unwrap())
         }
         Err(EvalError::TypeCastError)
     }
 
+    pub fn find_local_macro(&self,name:&str) -> Option<Variable> {
+        self.macros.get(name).cloned()
+    }
+
+    pub fn find_macro(&self,qual:Option<&str>,name:&str,modules:&EvalModules) -> Option<Variable> {
+        if qual.is_none() {
+            if let Some(macro_fn) = self.find_local_macro(name) {
+                return Some(macro_fn);
+            }
+        }
+        modules.find_macro(qual, name)
+    }
+
     pub fn find_local_symbol(&self,name:&str) -> Option<Variable> {
         let symbol = self.sym_maps.find_local_or_top(&name.to_string())?;
         if let Some(inner) = &symbol.bind_value {
@@ -158,36 +234,54 @@ impl ExecContext {
    
 
     fn eval_expr(&mut self,expr:&Expr,is_push_stack:bool,modules:&mut EvalModules) -> Result<(),EvalError> {
-        match expr {
-            Expr::Boolean(b) => if is_push_stack {self.stack.push(Variable::Bool(*b))},
-            Expr::Nil => if is_push_stack { self.stack.push(Variable::Nil) },
-            Expr::Number(Number::Int(inum)) => {
+        match &expr.kind {
+            ExprKind::Boolean(b) => if is_push_stack {self.stack.push(Variable::Bool(*b))},
+            ExprKind::Nil => if is_push_stack { self.stack.push(Variable::Nil) },
+            ExprKind::Number(Number::Int(inum)) => {
                 if is_push_stack { self.stack.push(Variable::Int(*inum)) };
             },
-            Expr::Number(Number::Float(fnum)) => {
+            ExprKind::Number(Number::Float(fnum)) => {
                 if is_push_stack { self.stack.push(Variable::Float(*fnum)) };
             },
-            Expr::String(str) => {
+            ExprKind::String(str) => {
                 if is_push_stack { self.stack.push(Variable::String(GcRefCell::new(str.to_owned()))) };
             },
-            Expr::Def(doc,sym,val) => {
+            ExprKind::Char(chr) => if is_push_stack { self.stack.push(Variable::Char(*chr)) },
+            ExprKind::Def(doc,sym,val) => {
                 self.eval_def(sym, val, doc,modules)?;
             },
-            Expr::Invoke(lst) => { self.eval_invoke(lst,is_push_stack,modules)?;  },
-            Expr::Symbol(sym) => { self.relsove_sym(sym,modules)?; },
-            Expr::Fn(syms,form) => {self.eval_fn(syms, form)?; },
-            Expr::Let(binds,body,is_loop) => { self.eval_let(binds,body,*is_loop,is_push_stack,modules)?; }
-            Expr::Body(lst) => {self.eval_body(lst,modules)?; },
-            Expr::If(cond,expr_true,expr_false) => {self.eval_if(cond,expr_true,expr_false,is_push_stack,modules)?; },
-            Expr::Vector(lst) => {self.eval_vector(lst, is_push_stack,modules)?; },
-            Expr::Map(lst) => {self.eval_map(lst,is_push_stack,modules)?; },
-            Expr::QuoteVar(s) => if is_push_stack { 
+            ExprKind::DefMacro(sym,fn_expr) => {
+                self.eval_expr(fn_expr, true,modules)?;
+                let macro_fn = self.stack.pop().unwrap();
+                self.macros.insert(sym.name.clone(), macro_fn);
+                if is_push_stack { self.stack.push(Variable::Nil) };
+            },
+            ExprKind::Invoke(lst) => { self.eval_invoke(lst,is_push_stack,modules)?;  },
+            ExprKind::Symbol(sym) => { self.relsove_sym(sym,modules)?; },
+            ExprKind::Fn(name,arities) => {self.eval_fn(name, arities)?; },
+            ExprKind::Let(binds,body,is_loop) => { self.eval_let(binds,body,*is_loop,is_push_stack,modules)?; }
+            ExprKind::Body(lst) => {self.eval_body(lst,modules)?; },
+            ExprKind::If(cond,expr_true,expr_false) => {self.eval_if(cond,expr_true,expr_false,is_push_stack,modules)?; },
+            ExprKind::And(lst) => {self.eval_and_or(lst,true,is_push_stack,modules)?; },
+            ExprKind::Or(lst) => {self.eval_and_or(lst,false,is_push_stack,modules)?; },
+            ExprKind::Try(body,catches,finally) => {self.eval_try(body,catches,finally,is_push_stack,modules)?; },
+            ExprKind::Throw(e) => {
+                self.eval_expr(e, true,modules)?;
+                return Err(EvalError::Thrown(self.stack.pop().unwrap()));
+            },
+            ExprKind::Vector(lst) => {self.eval_vector(lst, is_push_stack,modules)?; },
+            ExprKind::Map(lst) => {self.eval_map(lst,is_push_stack,modules)?; },
+            ExprKind::Set(lst) => {self.eval_set(lst,is_push_stack,modules)?; },
+            ExprKind::QuoteVar(s) => if is_push_stack { 
                 let str_name = s.name.to_owned();
                 let var =  Variable::Var(str_name );
                 if is_push_stack {self.stack.push(var); }
             },
-            Expr::Recur(args) => { self.eval_recur(args,modules)?; },
-            Expr::Keyword(k) => {
+            ExprKind::Recur(args) => { self.eval_recur(args,modules)?; },
+            ExprKind::Quote(cexpr) => {
+                if is_push_stack { self.stack.push(quote::cexpr_to_var(cexpr)) };
+            },
+            ExprKind::Keyword(k) => {
                 let str = &k.sym.name;
                 if is_push_stack { self.stack.push(Variable::Keyword(GcRefCell::new(str.to_owned()))) };
             }
@@ -197,7 +291,7 @@ impl ExecContext {
     }
 
     fn eval_map(&mut self,lst:&Vec<Expr>,is_push_stack:bool,modules:&mut EvalModules)  -> Result<(),EvalError> {
-        let mut hash_map:HashMap<Variable,Variable> = HashMap::new();
+        let mut hash_map = PersistentHashMap::new();
         for idx in 0..lst.len() / 2 {
             let index = idx * 2;
             let key = &lst[index];
@@ -251,7 +345,17 @@ impl ExecContext {
         }
 
         let var_lst:Vec<Variable> = self.stack.drain(idx..).collect();
-        if is_push_stack { self.stack.push(Variable::Array(GcRefCell::new(var_lst))) };
+        if is_push_stack { self.stack.push(Variable::Array(GcRefCell::new(var_lst.into()))) };
+        Ok(())
+    }
+
+    fn eval_set(&mut self,lst:&Vec<Expr>,is_push_stack:bool,modules:&mut EvalModules) -> Result<(),EvalError> {
+        let idx = self.stack.len();
+        for expr in lst {
+            self.eval_expr(expr, true,modules)?;
+        }
+        let var_set:HashSet<Variable> = self.stack.drain(idx..).collect();
+        if is_push_stack { self.stack.push(Variable::Set(GcRefCell::new(var_set))) };
         Ok(())
     }
 
@@ -270,8 +374,7 @@ impl ExecContext {
         self.eval_expr(cond, true,modules)?;
         
         let last_var = self.stack.pop().unwrap();
-        let is_true = last_var.cast_bool().unwrap();  
-        if is_true {
+        if last_var.is_truthy() {
             self.eval_expr(expr_true, is_push_stack,modules)?;
         } else {
             self.eval_expr(expr_false, is_push_stack,modules)?;
@@ -279,6 +382,68 @@ impl ExecContext {
         Ok(())
     }
 
+    fn eval_and_or(&mut self,lst:&Vec<Expr>,is_and:bool,is_push_stack:bool,modules:&mut EvalModules) -> Result<(),EvalError> {
+        let mut last_var = if is_and { Variable::Bool(true) } else { Variable::Nil };
+        for expr in lst {
+            self.eval_expr(expr, true,modules)?;
+            last_var = self.stack.pop().unwrap();
+            if last_var.is_truthy() != is_and {
+                break;
+            }
+        }
+        if is_push_stack { self.stack.push(last_var) };
+        Ok(())
+    }
+
+    fn eval_try(&mut self,body:&Expr,catches:&[Catch],finally:&Option<Box<Expr>>,is_push_stack:bool,modules:&mut EvalModules) -> Result<(),EvalError> {
+        let point = self.save_point();
+        let mut ret = self.eval_expr(body, true,modules).map(|_| self.stack.pop().unwrap());
+        if let Err(err) = ret {
+            self.unwind(point);
+            ret = match catches.iter().find(|c| err.is_match(&c.class)) {
+                Some(catch) => self.eval_catch(catch, err.to_variable(),modules),
+                None => Err(err)
+            };
+            if ret.is_err() {
+                self.unwind(point);
+            }
+        }
+        if let Some(finally) = finally {
+            self.eval_expr(finally, true,modules)?;
+            self.stack.truncate(point.stack_len);
+        }
+        let var = ret?;
+        if is_push_stack { self.stack.push(var) };
+        Ok(())
+    }
+
+    fn eval_catch(&mut self,catch:&Catch,err_var:Variable,modules:&mut EvalModules) -> Result<Variable,EvalError> {
+        self.enter_let(false);
+        self.stack.push(err_var);
+        let err_sym = Symbol::val(catch.bind.name.clone(), self.stack.len() - 1);
+        self.sym_maps.last_scope().push_sym(err_sym);
+        self.eval_expr(&catch.body, true,modules)?;
+        let var = self.stack.pop().unwrap();
+        self.exit_let(false);
+        Ok(var)
+    }
+
+    fn save_point(&mut self) -> SavePoint {
+        SavePoint {
+            stack_len:self.stack.len(),
+            call_len:self.call_stack.len(),
+            scope_len:self.sym_maps.list.len(),
+            let_len:self.sym_maps.last_scope().let_len()
+        }
+    }
+
+    fn unwind(&mut self,point:SavePoint) {
+        self.call_stack.truncate(point.call_len);
+        self.sym_maps.list.truncate(point.scope_len);
+        self.sym_maps.last_scope().truncate_lets(point.let_len);
+        self.stack.truncate(point.stack_len);
+    }
+
     fn eval_let(&mut self,binds:&Vec<Expr>,body:&Box<Expr>,is_loop:bool,is_push_stack:bool,modules:&mut EvalModules) -> Result<(),EvalError> {
         self.enter_let(is_loop);
         //let 放入let变量
@@ -286,8 +451,8 @@ impl ExecContext {
             let index = idx * 2;
             let s = &binds[index];
             self.eval_expr(&binds[index + 1], true,modules)?;
-            match s {
-                Expr::Symbol(s) => {
+            match &s.kind {
+                ExprKind::Symbol(s) => {
                    let new_sym = Symbol::val(s.name.clone(), self.stack.len() - 1);
                    self.sym_maps.last_scope().push_sym(new_sym);
                 }
@@ -318,14 +483,9 @@ impl ExecContext {
         Ok(())
     }
 
-    fn eval_fn(&mut self,ast_syms:&Vec<ASTSymbol>,form:&Vec<Expr>) -> Result<(),EvalError> {
-        let mut syms:Vec<Symbol> = vec![];
-        for ast_sym in ast_syms {
-            let  sym = Symbol::val(ast_sym.name.clone(), 0);
-            syms.push(sym);
-        }
-        let mut closure_data = ClosureData {args:syms,body:form.clone(),cap_vars: None};
-        if let Some(cap_map) = self.az_closure_syms(ast_syms,form)? {
+    fn eval_fn(&mut self,name:&Option<ASTSymbol>,arities:&Vec<FnArity>) -> Result<(),EvalError> {
+        let mut closure_data = ClosureData {name:name.as_ref().map(|s| s.name.clone()),arities:arities.clone(),cap_vars: None};
+        if let Some(cap_map) = self.az_closure_syms(name,arities)? {
             closure_data.cap_vars = Some(cap_map);
             
         }
@@ -344,15 +504,17 @@ impl ExecContext {
          )
       )
     */
-    fn az_closure_syms(&mut self,ast_syms:&Vec<ASTSymbol>,forms:&Vec<Expr>) -> Result<Option<HashMap<String,Symbol>>,EvalError>  {
-        let mut fn_scope = SymbolScope::default();
+    fn az_closure_syms(&mut self,name:&Option<ASTSymbol>,arities:&Vec<FnArity>) -> Result<Option<HashMap<String,Symbol>>,EvalError>  {
         let mut not_found_syms:HashSet<ASTSymbol> = HashSet::new();
-        for sym in ast_syms {
-            let sym = Symbol::val(String::from(sym.sym_name()), 0);
-            fn_scope.push_sym(sym);
-        }
-        for expr in forms {
-            self.az_expr(&mut fn_scope, expr, &mut not_found_syms)
+        for arity in arities {
+            let mut fn_scope = SymbolScope::default();
+            for sym in name.iter().chain(arity.args.iter()).chain(arity.rest.iter()) {
+                let sym = Symbol::val(String::from(sym.sym_name()), 0);
+                fn_scope.push_sym(sym);
+            }
+            for expr in arity.body.iter() {
+                self.az_expr(&mut fn_scope, expr, &mut not_found_syms)
+            }
         }
         
         if not_found_syms.is_empty() {
@@ -374,22 +536,22 @@ impl ExecContext {
 
     fn az_expr(&mut self,scope:&mut SymbolScope,expr:&Expr,not_found_syms:&mut HashSet<ASTSymbol>) {
        
-        match expr {
-            Expr::Body(lst) => {
+        match &expr.kind {
+            ExprKind::Body(lst) => {
                 lst.iter().for_each(|e|  self.az_expr(scope, e, not_found_syms))
             },
-            Expr::If(cond,e_true,e_false) => {
+            ExprKind::If(cond,e_true,e_false) => {
                 self.az_expr(scope, cond, not_found_syms);
                 self.az_expr(scope, e_true, not_found_syms);
                 self.az_expr(scope, e_false, not_found_syms);
             },
-            Expr::Invoke(froms) => {
+            ExprKind::Invoke(froms) => {
                 froms.iter().for_each(|e|  self.az_expr(scope, e, not_found_syms))
             },
-            Expr::Vector(lst) => {
+            ExprKind::Vector(lst) | ExprKind::Set(lst) | ExprKind::And(lst) | ExprKind::Or(lst) => {
                 lst.iter().for_each(|e|  self.az_expr(scope, e, not_found_syms))
             },
-            Expr::Let(binds,body,_) => {
+            ExprKind::Let(binds,body,_) => {
                 scope.push_let();
                 for idx in 0..binds.len() / 2 {
                     let cur_expr = binds[idx * 2].clone();
@@ -400,7 +562,20 @@ impl ExecContext {
                 self.az_expr(scope, body, not_found_syms);
                 scope.pop_let();
             },
-            Expr::Symbol(sym) => {
+            ExprKind::Try(body,catches,finally) => {
+                self.az_expr(scope, body, not_found_syms);
+                for catch in catches {
+                    scope.push_let();
+                    scope.push_sym(Symbol::val(catch.bind.name.clone(), 0));
+                    self.az_expr(scope, &catch.body, not_found_syms);
+                    scope.pop_let();
+                }
+                if let Some(finally) = finally {
+                    self.az_expr(scope, finally, not_found_syms);
+                }
+            },
+            ExprKind::Throw(e) => self.az_expr(scope, e, not_found_syms),
+            ExprKind::Symbol(sym) => {
                 let top_scope = self.sym_maps.top_scope_ref();
                 if scope.find(&sym.name).is_none() && top_scope.find(&sym.name).is_none() {
                     if !not_found_syms.contains(&sym) {not_found_syms.insert(sym.clone()); };
@@ -452,13 +627,20 @@ impl ExecContext {
                 Variable::Function(f) => f.clone(),
                 Variable::Map(hmap) => {
                     let key = &self.stack[fn_index + 1];
-                    let h_map_ref:&HashMap<Variable,Variable> = &hmap.borrow();
+                    let h_map_ref:&PersistentHashMap = &hmap.borrow();
                     let get_var = h_map_ref.get(key).map(|v|v.clone()).unwrap_or(Variable::Nil);
                     self.stack.drain(start_index..);
                     if is_push_stack {self.stack.push(get_var) }
                    
                     return Ok(())
                 },
+                Variable::Set(set) => {
+                    let key = &self.stack[fn_index + 1];
+                    let get_var = if set.borrow().contains(key) { key.clone() } else { Variable::Nil };
+                    self.stack.drain(start_index..);
+                    if is_push_stack {self.stack.push(get_var) }
+                    return Ok(())
+                },
                 _ => {
                     return Err(EvalError::ListFirstMustFunction)
                 }
@@ -475,14 +657,14 @@ impl ExecContext {
         self.run_function(&func, start_index, is_push_stack, args,fn_index,modules)
     }
 
-    fn eval_closure(&mut self,closure_data:&ClosureData,modules:&mut EvalModules) -> Result<(),EvalError> {
-        if closure_data.body.len() == 0 {
+    fn eval_closure(&mut self,arity:&FnArity,modules:&mut EvalModules) -> Result<(),EvalError> {
+        if arity.body.len() == 0 {
             self.stack.push(Variable::Nil);
             return Ok(()) 
         }
         let mut idx = 0;
-        let form_len = closure_data.body.len() - 1;
-        for form_expr in &closure_data.body {
+        let form_len = arity.body.len() - 1;
+        for form_expr in &arity.body {
            self.eval_expr(&form_expr,form_len == idx,modules)?;
            idx += 1;
         }
@@ -494,18 +676,33 @@ impl ExecContext {
         match func_ref {
             Function::NativeFn(nf) => {
                 self.enter_function(start_index);
-                let ret = nf(&mut ExecScope {context:self,modules },args);
+                let ret = nf(&mut ExecScope {context:self,modules },args)?;
+                if is_push_stack { self.stack.push(ret) };
+            },
+            Function::NativeClosure(nf) => {
+                self.enter_function(start_index);
+                let ret = nf(&mut ExecScope {context:self,modules },args)?;
                 if is_push_stack { self.stack.push(ret) };
             },
             Function::ClosureFn(closure_data) => {
-                if args.len() != closure_data.args.len() {
-                    return Err(EvalError::FunctionArgCountError);
-                }
+                //fixed arities take precedence over the variadic one
+                let arity = closure_data.arities.iter().find(|a| a.rest.is_none() && a.is_match(args.len()))
+                                        .or_else(|| closure_data.arities.iter().find(|a| a.is_match(args.len())))
+                                        .ok_or(EvalError::FunctionArgCountError)?;
                 self.enter_function(start_index);
+                if arity.rest.is_some() {
+                    let rest_args:Vec<Variable> = self.stack.drain(fn_index + 1 + arity.args.len()..).collect();
+                    let rest_var = if rest_args.is_empty() { Variable::Nil } else { Variable::List(GcRefCell::new(rest_args.into())) };
+                    self.stack.push(rest_var);
+                }
+                //(fn self-name [..]) 指向函数自身
+                if let Some(name) = &closure_data.name {
+                    self.sym_maps.last_scope().push_sym(Symbol::val(name.clone(), fn_index));
+                }
                 let mut  var_idx = 1;
                 //把函数参数push入栈
-                for sym in &closure_data.args {
-                    let new_sym = Symbol::val(sym.var_name.to_string(), fn_index + var_idx); 
+                for sym in arity.args.iter().chain(arity.rest.iter()) {
+                    let new_sym = Symbol::val(sym.name.to_string(), fn_index + var_idx); 
                     self.sym_maps.last_scope().push_sym(new_sym);
                     var_idx += 1;
                 }
@@ -518,11 +715,11 @@ impl ExecContext {
                     }
                 }
                 let body_index = self.stack.len();
-                self.eval_closure(closure_data,modules)?;
+                self.eval_closure(arity,modules)?;
                 let mut need_loop = self.call_stack.last().unwrap().need_loop;
                 while need_loop {
                     self.call_stack.last_mut().unwrap().need_loop = false;
-                    self.eval_closure(closure_data,modules)?;
+                    self.eval_closure(arity,modules)?;
                     need_loop = self.call_stack.last().unwrap().need_loop;
                     let last = self.stack.drain(body_index..).last();
                     if need_loop == false {
@@ -0,0 +1,468 @@
+use std::cell::RefCell;
+
+use gc::{Finalize, Gc, GcCell, Trace};
+
+use crate::{EvalError, PersistentVector, Variable, buildin_fn::{check_args, type_error}, seq_fn::{call, coll_items, count_arg, seq_items}, variable::ExecScope};
+
+const CHUNK:usize = 32;
+
+//how the next chunk of a seq is produced, kept as data so everything it holds is traced
+#[derive(Debug,Clone,Finalize,Trace)]
+pub(crate) enum Step {
+    //fn of no args returning a seqable, from (lazy-seq body)
+    Thunk(Variable),
+    //f and the last item produced
+    Iterate(Variable,Variable),
+    Repeat(Variable,Option<usize>),
+    Cycle(Vec<Variable>),
+    //next number, exclusive end (none for (range)) and step
+    Range(i64,Option<i64>,i64),
+    //a vector or list from the offset on
+    Slice(PersistentVector,usize),
+    Map(Variable,Vec<Variable>),
+    Filter(Variable,Variable,bool)
+}
+
+#[derive(Debug,Finalize)]
+enum State {
+    Pending(Step),
+    //the realized chunk and the rest of the seq, nil at the end
+    Realized(Vec<Variable>,Variable)
+}
+
+thread_local! {
+    //rests waiting to be traced while a chain of realized chunks is being marked
+    static TRACE_QUEUE:RefCell<Option<Vec<*const Gc<LazySeq>>>> = const { RefCell::new(None) };
+}
+
+/*
+  a realized seq is a chain of chunks linked by their rest, one node per item for (lazy-seq (cons x ..)).
+  gc marks recursively, so the rest is queued and the outermost chain traces the queue in a loop.
+*/
+unsafe impl Trace for State {
+    unsafe fn trace(&self) {
+        match self {
+            State::Pending(step) => step.trace(),
+            State::Realized(items,Variable::LazySeq(rest)) => {
+                items.trace();
+                trace_rest(rest);
+            },
+            State::Realized(items,rest) => {
+                items.trace();
+                rest.trace();
+            }
+        }
+    }
+
+    unsafe fn root(&self) {
+        match self {
+            State::Pending(step) => step.root(),
+            State::Realized(items,rest) => {
+                items.root();
+                rest.root();
+            }
+        }
+    }
+
+    unsafe fn unroot(&self) {
+        match self {
+            State::Pending(step) => step.unroot(),
+            State::Realized(items,rest) => {
+                items.unroot();
+                rest.unroot();
+            }
+        }
+    }
+
+    fn finalize_glue(&self) {
+        Finalize::finalize(self);
+        match self {
+            State::Pending(step) => step.finalize_glue(),
+            State::Realized(items,rest) => {
+                items.finalize_glue();
+                rest.finalize_glue();
+            }
+        }
+    }
+}
+
+unsafe fn trace_rest(rest:&Gc<LazySeq>) {
+    let queued = TRACE_QUEUE.with(|queue| match &mut *queue.borrow_mut() {
+        Some(queue) => {
+            queue.push(rest as *const Gc<LazySeq>);
+            true
+        },
+        None => false
+    });
+    if queued { return; }
+    TRACE_QUEUE.with(|queue| *queue.borrow_mut() = Some(vec![rest as *const Gc<LazySeq>]));
+    //nothing is freed or mutated while marking, so the queued pointers stay valid
+    while let Some(next) = TRACE_QUEUE.with(|queue| queue.borrow_mut().as_mut().and_then(|queue| queue.pop())) {
+        (*next).trace();
+    }
+    TRACE_QUEUE.with(|queue| *queue.borrow_mut() = None);
+}
+
+/*
+  seq realized a chunk at a time, every chunk is cached so the realized prefix is shared by all holders.
+  show_str prints ... for the pending part, the json and serde conversions fail with Unrealized,
+  doall or EvalRT::realize realize the rest first.
+*/
+#[derive(Debug,Finalize,Trace)]
+pub struct LazySeq {
+    state:GcCell<State>
+}
+
+impl LazySeq {
+    pub(crate) fn pending(step:Step) -> Variable {
+        Variable::LazySeq(Gc::new(LazySeq { state:GcCell::new(State::Pending(step)) }))
+    }
+
+    pub(crate) fn realized(items:Vec<Variable>,rest:Variable) -> Variable {
+        Variable::LazySeq(Gc::new(LazySeq { state:GcCell::new(State::Realized(items,rest)) }))
+    }
+
+    //the step runs without holding the borrow, it may call back into script code
+    fn chunk(&self,scope:&mut ExecScope) -> Result<(Vec<Variable>,Variable),EvalError> {
+        let step = match &*self.state.borrow() {
+            State::Pending(step) => step.clone(),
+            State::Realized(items,rest) => return Ok((items.clone(),rest.clone()))
+        };
+        let (items,rest) = step.run(scope)?;
+        *self.state.borrow_mut() = State::Realized(items.clone(),rest.clone());
+        Ok((items,rest))
+    }
+
+    //items realized so far and whether anything is still pending
+    pub fn realized_items(&self) -> (Vec<Variable>,bool) {
+        let mut items = vec![];
+        let mut rest = match &*self.state.borrow() {
+            State::Pending(_) => return (items,true),
+            State::Realized(chunk,rest) => {
+                items.extend(chunk.iter().cloned());
+                rest.clone()
+            }
+        };
+        loop {
+            rest = match &rest {
+                Variable::LazySeq(lazy) => match &*lazy.state.borrow() {
+                    State::Pending(_) => return (items,true),
+                    State::Realized(chunk,next) => {
+                        items.extend(chunk.iter().cloned());
+                        next.clone()
+                    }
+                },
+                other => {
+                    items.extend(coll_items("lazy-seq", other).unwrap_or_default());
+                    return (items,false);
+                }
+            };
+        }
+    }
+
+    pub(crate) fn eq_realized(&self,other:&Variable) -> bool {
+        let (items,pending) = self.realized_items();
+        if pending { return false; }
+        match other {
+            Variable::LazySeq(lazy) => lazy.realized_items() == (items,false),
+            Variable::Array(arr) | Variable::List(arr) => arr.borrow().to_vec() == items,
+            _ => false
+        }
+    }
+}
+
+impl Step {
+    fn run(&self,scope:&mut ExecScope) -> Result<(Vec<Variable>,Variable),EvalError> {
+        match self {
+            //a thunk returning another lazy seq takes over its first chunk, so nested thunks run in a loop
+            Step::Thunk(f) => {
+                let mut ret = call(scope, f, vec![])?;
+                loop {
+                    let lazy = match &ret {
+                        Variable::LazySeq(lazy) => lazy.clone(),
+                        other => return coll_chunk("lazy-seq", other)
+                    };
+                    let thunk = match &*lazy.state.borrow() {
+                        State::Pending(Step::Thunk(g)) => Some(g.clone()),
+                        _ => None
+                    };
+                    match thunk {
+                        Some(g) => {
+                            ret = call(scope, &g, vec![])?;
+                            *lazy.state.borrow_mut() = State::Realized(vec![],ret.clone());
+                        },
+                        None => return lazy.chunk(scope)
+                    }
+                }
+            },
+            //one item per step, f may be expensive or have side effects
+            Step::Iterate(f,x) => {
+                let x = call(scope, f, vec![x.clone()])?;
+                Ok((vec![x.clone()],LazySeq::pending(Step::Iterate(f.clone(),x))))
+            },
+            Step::Repeat(x,None) => Ok((vec![x.clone();CHUNK],LazySeq::pending(self.clone()))),
+            Step::Repeat(x,Some(n)) => {
+                let len = (*n).min(CHUNK);
+                let rest = if *n > len { LazySeq::pending(Step::Repeat(x.clone(),Some(n - len))) } else { Variable::Nil };
+                Ok((vec![x.clone();len],rest))
+            },
+            Step::Cycle(items) => Ok((items.clone(),LazySeq::pending(self.clone()))),
+            //a step past i64::MAX or i64::MIN ends the range instead of overflowing
+            Step::Range(start,end,step) => {
+                let in_range = |n:&i64| match end {
+                    Some(end) => (*step > 0 && n < end) || (*step < 0 && n > end),
+                    None => true
+                };
+                let mut items = Vec::with_capacity(CHUNK);
+                let mut cur = Some(*start).filter(in_range);
+                while let Some(n) = cur {
+                    if items.len() == CHUNK {
+                        return Ok((items,LazySeq::pending(Step::Range(n,*end,*step))));
+                    }
+                    items.push(Variable::Int(n));
+                    cur = n.checked_add(*step).filter(in_range);
+                }
+                Ok((items,Variable::Nil))
+            },
+            Step::Slice(vec,offset) => Ok(slice_chunk(vec.clone(), *offset)),
+            Step::Map(f,colls) => {
+                let mut chunks = Vec::with_capacity(colls.len());
+                for coll in colls.iter() {
+                    match next_chunk(scope, "map", coll)? {
+                        Some(chunk) => chunks.push(chunk),
+                        None => return Ok((vec![],Variable::Nil))
+                    }
+                }
+                let len = chunks.iter().map(|(items,_)| items.len()).min().unwrap_or(0);
+                let mut items = Vec::with_capacity(len);
+                for idx in 0..len {
+                    items.push(call(scope, f, chunks.iter().map(|(c,_)| c[idx].clone()).collect())?);
+                }
+                let rests = chunks.into_iter().map(|(mut chunk,rest)| {
+                    if chunk.len() > len { LazySeq::realized(chunk.split_off(len), rest) } else { rest }
+                }).collect();
+                Ok((items,LazySeq::pending(Step::Map(f.clone(),rests))))
+            },
+            Step::Filter(pred,coll,keep) => {
+                let (chunk,rest) = match next_chunk(scope, "filter", coll)? {
+                    Some(chunk) => chunk,
+                    None => return Ok((vec![],Variable::Nil))
+                };
+                let mut items = vec![];
+                for item in chunk {
+                    if call(scope, pred, vec![item.clone()])?.is_truthy() == *keep {
+                        items.push(item);
+                    }
+                }
+                Ok((items,LazySeq::pending(Step::Filter(pred.clone(),rest,*keep))))
+            }
+        }
+    }
+}
+
+fn slice_chunk(vec:PersistentVector,offset:usize) -> (Vec<Variable>,Variable) {
+    let end = vec.len().min(offset + CHUNK);
+    let items = (offset..end).map(|idx| vec[idx].clone()).collect();
+    let rest = if end < vec.len() { LazySeq::pending(Step::Slice(vec,end)) } else { Variable::Nil };
+    (items,rest)
+}
+
+//vectors and lists are walked a chunk at a time, other seqables in one go
+fn coll_chunk(fn_name:&str,var:&Variable) -> Result<(Vec<Variable>,Variable),EvalError> {
+    match var {
+        Variable::Array(arr) | Variable::List(arr) => Ok(slice_chunk(arr.borrow().clone(), 0)),
+        other => Ok((coll_items(fn_name, other)?,Variable::Nil))
+    }
+}
+
+//first non empty chunk of any seqable and the rest after it, None once exhausted
+pub(crate) fn next_chunk(scope:&mut ExecScope,fn_name:&str,var:&Variable) -> Result<Option<(Vec<Variable>,Variable)>,EvalError> {
+    let mut cur = var.clone();
+    loop {
+        let (items,rest) = match &cur {
+            Variable::LazySeq(lazy) => lazy.chunk(scope)?,
+            other => coll_chunk(fn_name, other)?
+        };
+        if !items.is_empty() {
+            return Ok(Some((items,rest)));
+        }
+        if let Variable::Nil = rest {
+            return Ok(None);
+        }
+        cur = rest;
+    }
+}
+
+//calls f on each item until it returns false, realizing only the chunks reached
+pub(crate) fn walk(scope:&mut ExecScope,fn_name:&str,var:&Variable,mut f:impl FnMut(&mut ExecScope,Variable) -> Result<bool,EvalError>) -> Result<(),EvalError> {
+    let mut cur = var.clone();
+    while let Some((items,rest)) = next_chunk(scope, fn_name, &cur)? {
+        for item in items {
+            if !f(scope, item)? { return Ok(()); }
+        }
+        cur = rest;
+    }
+    Ok(())
+}
+
+//(drop n lazy) realizes the dropped part and keeps the rest lazy
+pub(crate) fn drop_lazy(scope:&mut ExecScope,n:usize,var:&Variable) -> Result<Variable,EvalError> {
+    let mut n = n;
+    let mut cur = var.clone();
+    while n > 0 {
+        match next_chunk(scope, "drop", &cur)? {
+            Some((mut items,rest)) if items.len() > n => return Ok(LazySeq::realized(items.split_off(n), rest)),
+            Some((items,rest)) => {
+                n -= items.len();
+                cur = rest;
+            },
+            None => return Ok(LazySeq::realized(vec![], Variable::Nil))
+        }
+    }
+    Ok(match cur {
+        Variable::Nil => LazySeq::realized(vec![], Variable::Nil),
+        other => other
+    })
+}
+
+//items of any seqable one at a time, realizing a chunk only when it is reached
+struct Cursor {
+    chunk:std::vec::IntoIter<Variable>,
+    rest:Variable
+}
+
+impl Cursor {
+    fn new(var:&Variable) -> Self {
+        Cursor { chunk:vec![].into_iter(), rest:var.clone() }
+    }
+
+    fn next(&mut self,scope:&mut ExecScope,fn_name:&str) -> Result<Option<Variable>,EvalError> {
+        if let Some(item) = self.chunk.next() {
+            return Ok(Some(item));
+        }
+        match next_chunk(scope, fn_name, &self.rest)? {
+            Some((items,rest)) => {
+                self.chunk = items.into_iter();
+                self.rest = rest;
+                Ok(self.chunk.next())
+            },
+            None => {
+                self.rest = Variable::Nil;
+                Ok(None)
+            }
+        }
+    }
+}
+
+//= for values that may hold lazy seqs, seqs are compared item by item and stop at the first difference
+pub(crate) fn lazy_eq(scope:&mut ExecScope,a:&Variable,b:&Variable) -> Result<bool,EvalError> {
+    let is_seq = |var:&Variable| matches!(var,Variable::LazySeq(_) | Variable::Array(_) | Variable::List(_));
+    match (a,b) {
+        (Variable::LazySeq(l0),Variable::LazySeq(l1)) if Gc::ptr_eq(l0, l1) => Ok(true),
+        (Variable::LazySeq(_),_) | (_,Variable::LazySeq(_)) if is_seq(a) && is_seq(b) => {
+            let (mut xs,mut ys) = (Cursor::new(a),Cursor::new(b));
+            loop {
+                match (xs.next(scope, "=")?,ys.next(scope, "=")?) {
+                    (Some(x),Some(y)) => if !lazy_eq(scope, &x, &y)? { return Ok(false); },
+                    (None,None) => return Ok(true),
+                    _ => return Ok(false)
+                }
+            }
+        },
+        (Variable::Array(a_arr),Variable::Array(b_arr)) | (Variable::List(a_arr),Variable::List(b_arr)) |
+        (Variable::Array(a_arr),Variable::List(b_arr)) | (Variable::List(a_arr),Variable::Array(b_arr)) => {
+            let (a_items,b_items) = (a_arr.borrow().to_vec(),b_arr.borrow().to_vec());
+            if a_items.len() != b_items.len() { return Ok(false); }
+            for (x,y) in a_items.iter().zip(b_items.iter()) {
+                if !lazy_eq(scope, x, y)? { return Ok(false); }
+            }
+            Ok(true)
+        },
+        (Variable::Map(a_map),Variable::Map(b_map)) => {
+            let a_kvs:Vec<(Variable,Variable)> = a_map.borrow().iter().map(|(k,v)| (k.clone(),v.clone())).collect();
+            if a_kvs.len() != b_map.borrow().len() { return Ok(false); }
+            for (k,v) in a_kvs.iter() {
+                let other = b_map.borrow().get(k).cloned();
+                match other {
+                    Some(other) => if !lazy_eq(scope, v, &other)? { return Ok(false); },
+                    None => return Ok(false)
+                }
+            }
+            Ok(true)
+        },
+        _ => Ok(a == b)
+    }
+}
+
+//realizes lazy seqs anywhere inside var, used before printing
+pub(crate) fn realize_all(scope:&mut ExecScope,var:&Variable) -> Result<(),EvalError> {
+    let items = match var {
+        Variable::LazySeq(_) => seq_items(scope, "realize", var)?,
+        Variable::Array(arr) | Variable::List(arr) => arr.borrow().to_vec(),
+        Variable::Set(set) => set.borrow().iter().cloned().collect(),
+        Variable::Map(map) => map.borrow().iter().flat_map(|(k,v)| vec![k.clone(),v.clone()]).collect(),
+        _ => return Ok(())
+    };
+    for item in items.iter() {
+        realize_all(scope, item)?;
+    }
+    Ok(())
+}
+
+//(lazy-seq body) expands to (lazy-seq* (fn [] body))
+pub fn lazy_seq(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("lazy-seq", &args, 1, Some(1))?;
+    match &args[0] {
+        Variable::Function(_) => Ok(LazySeq::pending(Step::Thunk(args[0].clone()))),
+        other => Err(type_error("lazy-seq", other))
+    }
+}
+
+//(iterate f x) is x, (f x), (f (f x)) ..
+pub fn iterate(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("iterate", &args, 2, Some(2))?;
+    let step = Step::Iterate(args[0].clone(),args[1].clone());
+    Ok(LazySeq::realized(vec![args[1].clone()], LazySeq::pending(step)))
+}
+
+//(repeat x) or (repeat n x)
+pub fn repeat(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("repeat", &args, 1, Some(2))?;
+    let step = match args.as_slice() {
+        [x] => Step::Repeat(x.clone(),None),
+        [n,x] => Step::Repeat(x.clone(),Some(count_arg("repeat", n)?)),
+        _ => unreachable!()
+    };
+    Ok(LazySeq::pending(step))
+}
+
+pub fn cycle(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("cycle", &args, 1, Some(1))?;
+    let items = seq_items(scope, "cycle", &args[0])?;
+    if items.is_empty() {
+        return Ok(LazySeq::realized(vec![], Variable::Nil));
+    }
+    Ok(LazySeq::pending(Step::Cycle(items)))
+}
+
+pub fn doall(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("doall", &args, 1, Some(1))?;
+    seq_items(scope, "doall", &args[0])?;
+    Ok(args[0].clone())
+}
+
+pub(crate) fn is_lazy(var:&Variable) -> bool {
+    matches!(var,Variable::LazySeq(_))
+}
+
+pub(crate) fn lazy_map(f:Variable,colls:Vec<Variable>) -> Variable {
+    LazySeq::pending(Step::Map(f,colls))
+}
+
+pub(crate) fn lazy_filter(pred:Variable,coll:Variable,keep:bool) -> Variable {
+    LazySeq::pending(Step::Filter(pred,coll,keep))
+}
+
+pub(crate) fn lazy_range(start:i64,end:Option<i64>,step:i64) -> Variable {
+    LazySeq::pending(Step::Range(start,end,step))
+}
@@ -3,22 +3,35 @@ mod variable;
 mod error;
 mod sym_scope;
 mod buildin_fn;
+mod seq_fn;
+mod string_fn;
+mod math_fn;
 mod value;
 mod module;
 mod exec_context;
+mod quote;
+mod convert;
+mod de;
+mod ser;
+mod persistent_vector;
+mod persistent_hash_map;
+mod lazy_seq;
 
-pub use variable::{Variable,GcRefCell,ExecScope};
+pub use variable::{Variable,GcRefCell,ExecScope,ExInfo,NativeClosureFn,UserData};
 pub use lite_clojure_parser as parser;
 pub use eval_rt::EvalRT;
 pub use error::EvalError;
+pub use convert::{FromVariable, IntoVariable, IntoEvalError, NativeFunc};
+pub use de::{from_variable, Deserializer};
+pub use ser::{to_variable, Serializer};
+pub use persistent_vector::PersistentVector;
+pub use persistent_hash_map::PersistentHashMap;
+pub use lazy_seq::LazySeq;
+#[doc(hidden)]
+pub use convert::derive_support;
+pub use lite_clojure_derive::{FromVariable, IntoVariable};
 use anyhow::{Result};
 
-pub fn run_native_fn(name:&str,scope:&mut ExecScope,args:Vec<Variable>,f:fn(&mut ExecScope,args:Vec<Variable>) -> Result<Variable>) -> Variable {
-    match f(scope,args) {
-        Ok(var) => var,
-        Err(err) => {
-            log::error!("native func {} error:{}",name,err);
-            Variable::Nil
-        }
-    }
+pub fn run_native_fn(name:&str,scope:&mut ExecScope,args:Vec<Variable>,f:fn(&mut ExecScope,args:Vec<Variable>) -> Result<Variable>) -> Result<Variable,EvalError> {
+    f(scope,args).map_err(|err| EvalError::NativeError(format!("native func {} error:{}",name,err)))
 }
\ No newline at end of file
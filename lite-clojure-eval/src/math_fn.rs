@@ -0,0 +1,233 @@
+use crate::{EvalError, Variable, buildin_fn::{check_args, type_error}, variable::ExecScope};
+
+//splitmix64, the same seed always replays the same numbers
+#[derive(Debug)]
+pub(crate) struct Rng {
+    state:u64
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Rng::new(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+impl Rng {
+    pub(crate) fn new(seed:u64) -> Self {
+        Rng { state:seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    //uniform in [0,1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn overflow(fn_name:&str) -> EvalError {
+    EvalError::Overflow(fn_name.to_string())
+}
+
+fn int_arg(fn_name:&str,var:&Variable) -> Result<i64,EvalError> {
+    var.cast_int().ok_or_else(|| type_error(fn_name, var))
+}
+
+fn float_arg(fn_name:&str,var:&Variable) -> Result<f64,EvalError> {
+    var.cast_float().ok_or_else(|| type_error(fn_name, var))
+}
+
+//ints stay ints, a float on either side makes the result a float,
+//floats follow IEEE like / does, a zero divisor gives inf or NaN instead of an error
+fn int_or_float(fn_name:&str,args:&[Variable],fint:impl Fn(i64,i64) -> Result<i64,EvalError>,ffloat:fn(f64,f64) -> f64) -> Result<Variable,EvalError> {
+    check_args(fn_name, args, 2, Some(2))?;
+    match (&args[0],&args[1]) {
+        (Variable::Int(a),Variable::Int(b)) => Ok(Variable::Int(fint(*a,*b)?)),
+        (a,b) => {
+            let (a,b) = (float_arg(fn_name, a)?,float_arg(fn_name, b)?);
+            Ok(Variable::Float(ffloat(a,b)))
+        }
+    }
+}
+
+pub fn inc(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("inc", &args, 1, Some(1))?;
+    match &args[0] {
+        Variable::Int(n) => n.checked_add(1).map(Variable::Int).ok_or_else(|| overflow("inc")),
+        Variable::Float(n) => Ok(Variable::Float(n + 1f64)),
+        other => Err(type_error("inc", other))
+    }
+}
+
+pub fn dec(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("dec", &args, 1, Some(1))?;
+    match &args[0] {
+        Variable::Int(n) => n.checked_sub(1).map(Variable::Int).ok_or_else(|| overflow("dec")),
+        Variable::Float(n) => Ok(Variable::Float(n - 1f64)),
+        other => Err(type_error("dec", other))
+    }
+}
+
+//truncating division
+pub fn quot(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    int_or_float("quot", &args, |a,b| {
+        if b == 0 { return Err(EvalError::DivideByZero); }
+        a.checked_div(b).ok_or_else(|| overflow("quot"))
+    }, |a,b| (a / b).trunc())
+}
+
+//remainder with the sign of the dividend
+pub fn rem(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    int_or_float("rem", &args, |a,b| {
+        if b == 0 { return Err(EvalError::DivideByZero); }
+        Ok(a.wrapping_rem(b))
+    }, |a,b| a % b)
+}
+
+//modulus with the sign of the divisor
+pub fn modulo(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    int_or_float("mod", &args, |a,b| {
+        if b == 0 { return Err(EvalError::DivideByZero); }
+        let r = a.wrapping_rem(b);
+        Ok(if r != 0 && (r < 0) != (b < 0) { r + b } else { r })
+    }, |a,b| {
+        let r = a % b;
+        if r != 0f64 && (r < 0f64) != (b < 0f64) { r + b } else { r }
+    })
+}
+
+pub fn abs(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("abs", &args, 1, Some(1))?;
+    match &args[0] {
+        Variable::Int(n) => n.checked_abs().map(Variable::Int).ok_or_else(|| overflow("abs")),
+        Variable::Float(n) => Ok(Variable::Float(n.abs())),
+        other => Err(type_error("abs", other))
+    }
+}
+
+//returns the winning argument itself, so ints stay ints
+fn pick(fn_name:&str,args:&[Variable],better:fn(f64,f64) -> bool) -> Result<Variable,EvalError> {
+    check_args(fn_name, args, 1, None)?;
+    let mut ret = &args[0];
+    let mut ret_num = float_arg(fn_name, ret)?;
+    for arg in args[1..].iter() {
+        let num = float_arg(fn_name, arg)?;
+        if better(num,ret_num) {
+            ret = arg;
+            ret_num = num;
+        }
+    }
+    Ok(ret.clone())
+}
+
+pub fn min(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    pick("min", &args, |a,b| a < b)
+}
+
+pub fn max(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    pick("max", &args, |a,b| a > b)
+}
+
+fn bit_op(fn_name:&str,args:&[Variable],f:fn(i64,i64) -> i64) -> Result<Variable,EvalError> {
+    check_args(fn_name, args, 2, None)?;
+    let mut cur = int_arg(fn_name, &args[0])?;
+    for arg in args[1..].iter() {
+        cur = f(cur,int_arg(fn_name, arg)?);
+    }
+    Ok(Variable::Int(cur))
+}
+
+pub fn bit_and(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    bit_op("bit-and", &args, |a,b| a & b)
+}
+
+pub fn bit_or(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    bit_op("bit-or", &args, |a,b| a | b)
+}
+
+pub fn bit_xor(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    bit_op("bit-xor", &args, |a,b| a ^ b)
+}
+
+pub fn bit_not(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("bit-not", &args, 1, Some(1))?;
+    Ok(Variable::Int(!int_arg("bit-not", &args[0])?))
+}
+
+//shift counts use the low 6 bits like the jvm
+pub fn bit_shift_left(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("bit-shift-left", &args, 2, Some(2))?;
+    let n = int_arg("bit-shift-left", &args[1])?;
+    Ok(Variable::Int(int_arg("bit-shift-left", &args[0])?.wrapping_shl(n as u32)))
+}
+
+pub fn bit_shift_right(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("bit-shift-right", &args, 2, Some(2))?;
+    let n = int_arg("bit-shift-right", &args[1])?;
+    Ok(Variable::Int(int_arg("bit-shift-right", &args[0])?.wrapping_shr(n as u32)))
+}
+
+//(rand) in [0,1), (rand n) in [0,n)
+pub fn rand(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("rand", &args, 0, Some(1))?;
+    let n = match args.first() {
+        Some(n) => float_arg("rand", n)?,
+        None => 1f64
+    };
+    Ok(Variable::Float(scope.modules.rng.next_f64() * n))
+}
+
+pub fn rand_int(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("rand-int", &args, 1, Some(1))?;
+    let n = int_arg("rand-int", &args[0])?;
+    Ok(Variable::Int((scope.modules.rng.next_f64() * n as f64).floor() as i64))
+}
+
+pub fn set_rand_seed(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("set-rand-seed!", &args, 1, Some(1))?;
+    scope.modules.rng = Rng::new(int_arg("set-rand-seed!", &args[0])? as u64);
+    Ok(Variable::Nil)
+}
+
+fn float_fn(fn_name:&str,args:&[Variable],f:fn(f64) -> f64) -> Result<Variable,EvalError> {
+    check_args(fn_name, args, 1, Some(1))?;
+    Ok(Variable::Float(f(float_arg(fn_name, &args[0])?)))
+}
+
+pub fn sqrt(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> { float_fn("sqrt", &args, f64::sqrt) }
+pub fn floor(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> { float_fn("floor", &args, f64::floor) }
+pub fn ceil(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> { float_fn("ceil", &args, f64::ceil) }
+pub fn sin(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> { float_fn("sin", &args, f64::sin) }
+pub fn cos(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> { float_fn("cos", &args, f64::cos) }
+pub fn tan(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> { float_fn("tan", &args, f64::tan) }
+pub fn asin(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> { float_fn("asin", &args, f64::asin) }
+pub fn acos(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> { float_fn("acos", &args, f64::acos) }
+pub fn atan(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> { float_fn("atan", &args, f64::atan) }
+pub fn exp(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> { float_fn("exp", &args, f64::exp) }
+pub fn log(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> { float_fn("log", &args, f64::ln) }
+pub fn log10(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> { float_fn("log10", &args, f64::log10) }
+
+pub fn pow(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("pow", &args, 2, Some(2))?;
+    Ok(Variable::Float(float_arg("pow", &args[0])?.powf(float_arg("pow", &args[1])?)))
+}
+
+pub fn atan2(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("atan2", &args, 2, Some(2))?;
+    Ok(Variable::Float(float_arg("atan2", &args[0])?.atan2(float_arg("atan2", &args[1])?)))
+}
+
+//half up to an int like Math/round
+pub fn round(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("round", &args, 1, Some(1))?;
+    match &args[0] {
+        Variable::Int(n) => Ok(Variable::Int(*n)),
+        other => Ok(Variable::Int((float_arg("round", other)? + 0.5).floor() as i64))
+    }
+}
@@ -1,10 +1,13 @@
 use std::{collections::HashMap, path::PathBuf};
-use crate::{exec_context::ExecContext, buildin_fn, Variable};
+use lite_clojure_parser::{macros::MacroRegistry, value::CORE_NS};
+use crate::{exec_context::ExecContext, buildin_fn, lazy_seq, math_fn::{self, Rng}, seq_fn, string_fn, Variable};
 #[derive(Default)]
 pub struct EvalModules {
     pub search_path:Vec<PathBuf>,
     modules:HashMap<String,FileModule>,
-    pub(crate) prelude: ExecContext
+    pub(crate) prelude: ExecContext,
+    pub(crate) macros: MacroRegistry,
+    pub(crate) rng: Rng
 }
 
 impl EvalModules {
@@ -26,19 +29,144 @@ impl EvalModules {
         self.prelude.push_native_fn(">=", buildin_fn::num_ge);
 
         self.prelude.push_native_fn("nth", buildin_fn::nth);
+        self.prelude.push_native_fn("nthnext", buildin_fn::nthnext);
         self.prelude.push_native_fn("get", buildin_fn::get);
         self.prelude.push_native_fn("=", buildin_fn::eq);
         self.prelude.push_native_fn("nil?", buildin_fn::is_nil);
+        self.prelude.push_native_fn("not", buildin_fn::not);
+        self.prelude.push_native_fn("ex-info", buildin_fn::ex_info);
+        self.prelude.push_native_fn("ex-data", buildin_fn::ex_data);
+        self.prelude.push_native_fn("ex-message", buildin_fn::ex_message);
+        self.prelude.push_native_fn("ex-cause", buildin_fn::ex_cause);
+        self.prelude.push_native_fn("char", buildin_fn::char);
+        self.prelude.push_native_fn("int", buildin_fn::int);
         self.prelude.push_native_fn("concat", buildin_fn::concat);
+        self.prelude.push_native_fn("list", buildin_fn::list);
+        self.prelude.push_native_fn("vec", buildin_fn::vec);
+        self.prelude.push_native_fn("hash-map", buildin_fn::hash_map);
+        self.prelude.push_native_fn("apply", buildin_fn::apply);
+        //set
+        self.prelude.push_native_fn("hash-set", buildin_fn::hash_set);
+        self.prelude.push_native_fn("set", buildin_fn::set);
+        self.prelude.push_native_fn("contains?", buildin_fn::contains);
+        self.prelude.push_native_fn("conj", buildin_fn::conj);
+        self.prelude.push_native_fn("disj", buildin_fn::disj);
+        self.prelude.push_native_fn("union", buildin_fn::union);
+        self.prelude.push_native_fn("intersection", buildin_fn::intersection);
+        self.prelude.push_native_fn("difference", buildin_fn::difference);
+        //persistent map and vector
+        self.prelude.push_native_fn("assoc", buildin_fn::assoc);
+        self.prelude.push_native_fn("dissoc", buildin_fn::dissoc);
+        self.prelude.push_native_fn("update", buildin_fn::update);
+        self.prelude.push_native_fn("assoc-in", buildin_fn::assoc_in);
+        self.prelude.push_native_fn("update-in", buildin_fn::update_in);
+        self.prelude.push_native_fn("transient", buildin_fn::transient);
+        self.prelude.push_native_fn("persistent!", buildin_fn::persistent);
+        //seq
+        self.prelude.push_native_fn("first", seq_fn::first);
+        self.prelude.push_native_fn("rest", seq_fn::rest);
+        self.prelude.push_native_fn("cons", seq_fn::cons);
+        self.prelude.push_native_fn("count", seq_fn::count);
+        self.prelude.push_native_fn("seq", seq_fn::seq);
+        self.prelude.push_native_fn("map", seq_fn::map);
+        self.prelude.push_native_fn("filter", seq_fn::filter);
+        self.prelude.push_native_fn("remove", seq_fn::remove);
+        self.prelude.push_native_fn("reduce", seq_fn::reduce);
+        self.prelude.push_native_fn("range", seq_fn::range);
+        self.prelude.push_native_fn("take", seq_fn::take);
+        self.prelude.push_native_fn("drop", seq_fn::drop);
+        self.prelude.push_native_fn("into", seq_fn::into);
+        self.prelude.push_native_fn("some", seq_fn::some);
+        self.prelude.push_native_fn("every?", seq_fn::every);
+        self.prelude.push_native_fn("keys", seq_fn::keys);
+        self.prelude.push_native_fn("vals", seq_fn::vals);
+        self.prelude.push_native_fn("merge", seq_fn::merge);
+        self.prelude.push_native_fn("select-keys", seq_fn::select_keys);
+        self.prelude.push_native_fn("frequencies", seq_fn::frequencies);
+        //lazy seq
+        self.prelude.push_native_fn("lazy-seq*", lazy_seq::lazy_seq);
+        self.prelude.push_native_fn("iterate", lazy_seq::iterate);
+        self.prelude.push_native_fn("repeat", lazy_seq::repeat);
+        self.prelude.push_native_fn("cycle", lazy_seq::cycle);
+        self.prelude.push_native_fn("doall", lazy_seq::doall);
+        //math
+        self.prelude.push_native_fn("inc", math_fn::inc);
+        self.prelude.push_native_fn("dec", math_fn::dec);
+        self.prelude.push_native_fn("quot", math_fn::quot);
+        self.prelude.push_native_fn("rem", math_fn::rem);
+        self.prelude.push_native_fn("mod", math_fn::modulo);
+        self.prelude.push_native_fn("abs", math_fn::abs);
+        self.prelude.push_native_fn("min", math_fn::min);
+        self.prelude.push_native_fn("max", math_fn::max);
+        self.prelude.push_native_fn("bit-and", math_fn::bit_and);
+        self.prelude.push_native_fn("bit-or", math_fn::bit_or);
+        self.prelude.push_native_fn("bit-xor", math_fn::bit_xor);
+        self.prelude.push_native_fn("bit-not", math_fn::bit_not);
+        self.prelude.push_native_fn("bit-shift-left", math_fn::bit_shift_left);
+        self.prelude.push_native_fn("bit-shift-right", math_fn::bit_shift_right);
+        self.prelude.push_native_fn("rand", math_fn::rand);
+        self.prelude.push_native_fn("rand-int", math_fn::rand_int);
+        self.prelude.push_native_fn("set-rand-seed!", math_fn::set_rand_seed);
+        //string
+        self.prelude.push_native_fn("str", string_fn::str);
+        self.prelude.push_native_fn("format", string_fn::format);
         //mut list
         self.prelude.push_native_fn("conj!", buildin_fn::conj_mut);
         //mut map
         self.prelude.push_native_fn("assoc!", buildin_fn::assoc_mut);
         self.prelude.push_native_fn("dissoc!", buildin_fn::dissoc_mut);
+
+        self.init_string_mod();
+        self.init_math_mod();
+    }
+
+    //clojure.math holds the float functions, the integer ones stay in core
+    fn init_math_mod(&mut self) {
+        let mut context = ExecContext::new();
+        context.push_var("PI", std::f64::consts::PI);
+        context.push_var("E", std::f64::consts::E);
+        context.push_native_fn("sqrt", math_fn::sqrt);
+        context.push_native_fn("pow", math_fn::pow);
+        context.push_native_fn("floor", math_fn::floor);
+        context.push_native_fn("ceil", math_fn::ceil);
+        context.push_native_fn("round", math_fn::round);
+        context.push_native_fn("sin", math_fn::sin);
+        context.push_native_fn("cos", math_fn::cos);
+        context.push_native_fn("tan", math_fn::tan);
+        context.push_native_fn("asin", math_fn::asin);
+        context.push_native_fn("acos", math_fn::acos);
+        context.push_native_fn("atan", math_fn::atan);
+        context.push_native_fn("atan2", math_fn::atan2);
+        context.push_native_fn("exp", math_fn::exp);
+        context.push_native_fn("log", math_fn::log);
+        context.push_native_fn("log10", math_fn::log10);
+        self.modules.insert(String::from("clojure.math"), FileModule { context });
+    }
+
+    //clojure.string is built in, require finds it already loaded
+    fn init_string_mod(&mut self) {
+        let mut context = ExecContext::new();
+        context.push_native_fn("str", string_fn::str);
+        context.push_native_fn("subs", string_fn::subs);
+        context.push_native_fn("split", string_fn::split);
+        context.push_native_fn("join", string_fn::join);
+        context.push_native_fn("trim", string_fn::trim);
+        context.push_native_fn("upper-case", string_fn::upper_case);
+        context.push_native_fn("lower-case", string_fn::lower_case);
+        context.push_native_fn("replace", string_fn::replace);
+        context.push_native_fn("starts-with?", string_fn::starts_with);
+        context.push_native_fn("ends-with?", string_fn::ends_with);
+        context.push_native_fn("includes?", string_fn::includes);
+        context.push_native_fn("index-of", string_fn::index_of);
+        context.push_native_fn("blank?", string_fn::is_blank);
+        self.modules.insert(String::from("clojure.string"), FileModule { context });
     }
 
     pub fn find_symbol(&self,qual:Option<&str>,name:&str) -> Option<Variable> {
        if let Some(qual) = qual {
+           if qual == CORE_NS {
+               return self.prelude.find_local_symbol(name);
+           }
            if let Some(f_mod) = self.modules.get(qual) {
               return f_mod.context.find_local_symbol(name);
            } else {
@@ -50,6 +178,14 @@ impl EvalModules {
        }
     }
 
+    pub fn find_macro(&self,qual:Option<&str>,name:&str) -> Option<Variable> {
+        match qual {
+            None => self.prelude.find_local_macro(name),
+            Some(CORE_NS) => self.prelude.find_local_macro(name),
+            Some(qual) => self.modules.get(qual).and_then(|f_mod| f_mod.context.find_local_macro(name))
+        }
+    }
+
     pub fn require_mod(&mut self,mod_name:&str) {
         if self.modules.contains_key(mod_name) {
             return;
@@ -0,0 +1,337 @@
+use std::{collections::{HashMap, hash_map::DefaultHasher}, hash::{Hash, Hasher}, iter::FromIterator};
+
+use gc::{Finalize, Gc, Trace};
+
+use crate::Variable;
+
+const BITS:u32 = 5;
+const MASK:u64 = (1 << BITS) - 1;
+
+#[derive(Debug,Finalize,Trace)]
+enum Node {
+    //bit n of the bitmap is set when the slot for hash fragment n is used, entries keep slot order
+    Bitmap(u32,Vec<Entry>),
+    //keys whose 64 hash bits are all equal
+    Collision(Vec<(Variable,Variable)>)
+}
+
+#[derive(Debug,Clone,Finalize,Trace)]
+enum Entry {
+    Pair(Variable,Variable),
+    Node(Gc<Node>)
+}
+
+/*
+  hash array mapped trie, nodes are shared between clones and an edit copies only its path,
+  so clone + insert is the persistent assoc and insert on a value in place is the transient one.
+*/
+#[derive(Debug,Clone,Default,Finalize,Trace)]
+pub struct PersistentHashMap {
+    len:usize,
+    root:Option<Gc<Node>>
+}
+
+fn hash_of(key:&Variable) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish() & hash_mask()
+}
+
+#[cfg(not(test))]
+fn hash_mask() -> u64 {
+    u64::MAX
+}
+
+//tests narrow the hash to force collision nodes
+#[cfg(test)]
+thread_local! {
+    static HASH_MASK:std::cell::Cell<u64> = const { std::cell::Cell::new(u64::MAX) };
+}
+
+#[cfg(test)]
+fn hash_mask() -> u64 {
+    HASH_MASK.with(|mask| mask.get())
+}
+
+fn bit_of(hash:u64,shift:u32) -> u32 {
+    1 << ((hash >> shift) & MASK)
+}
+
+fn slot_of(bitmap:u32,bit:u32) -> usize {
+    (bitmap & (bit - 1)).count_ones() as usize
+}
+
+impl PersistentHashMap {
+    pub fn new() -> Self {
+        PersistentHashMap::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self,key:&Variable) -> Option<&Variable> {
+        let hash = hash_of(key);
+        let mut node:&Node = self.root.as_ref()?;
+        let mut shift = 0;
+        loop {
+            match node {
+                Node::Bitmap(bitmap,entries) => {
+                    let bit = bit_of(hash, shift);
+                    if bitmap & bit == 0 { return None; }
+                    match &entries[slot_of(*bitmap, bit)] {
+                        Entry::Pair(k,v) => return if k == key { Some(v) } else { None },
+                        Entry::Node(child) => {
+                            node = child;
+                            shift += BITS;
+                        }
+                    }
+                },
+                Node::Collision(pairs) => return pairs.iter().find(|(k,_)| k == key).map(|(_,v)| v)
+            }
+        }
+    }
+
+    pub fn contains_key(&self,key:&Variable) -> bool {
+        self.get(key).is_some()
+    }
+
+    //returns the replaced value
+    pub fn insert(&mut self,key:Variable,value:Variable) -> Option<Variable> {
+        let hash = hash_of(&key);
+        let (node,old) = match &self.root {
+            Some(root) => insert_in(root, 0, hash, key, value),
+            None => (Node::Bitmap(bit_of(hash, 0),vec![Entry::Pair(key,value)]),None)
+        };
+        self.root = Some(Gc::new(node));
+        if old.is_none() { self.len += 1; }
+        old
+    }
+
+    pub fn remove(&mut self,key:&Variable) -> Option<Variable> {
+        let (node,old) = remove_in(self.root.as_ref()?, 0, hash_of(key), key)?;
+        self.root = node.map(Gc::new);
+        self.len -= 1;
+        Some(old)
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { stack:self.root.iter().map(|node| (&**node,0)).collect(), remain:self.len }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Variable> {
+        self.iter().map(|(k,_)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Variable> {
+        self.iter().map(|(_,v)| v)
+    }
+}
+
+fn insert_in(node:&Node,shift:u32,hash:u64,key:Variable,value:Variable) -> (Node,Option<Variable>) {
+    match node {
+        Node::Collision(pairs) => {
+            let mut pairs = pairs.clone();
+            let old = match pairs.iter_mut().find(|(k,_)| *k == key) {
+                Some(pair) => Some(std::mem::replace(&mut pair.1, value)),
+                None => { pairs.push((key,value)); None }
+            };
+            (Node::Collision(pairs),old)
+        },
+        Node::Bitmap(bitmap,entries) => {
+            let bit = bit_of(hash, shift);
+            let slot = slot_of(*bitmap, bit);
+            let mut entries = entries.clone();
+            if bitmap & bit == 0 {
+                entries.insert(slot, Entry::Pair(key,value));
+                return (Node::Bitmap(bitmap | bit,entries),None);
+            }
+            let (entry,old) = match &entries[slot] {
+                Entry::Pair(k,v) if *k == key => (Entry::Pair(key,value),Some(v.clone())),
+                Entry::Pair(k,v) => {
+                    let node = merge(shift + BITS, (k.clone(),v.clone()), hash_of(k), (key,value), hash);
+                    (Entry::Node(Gc::new(node)),None)
+                },
+                Entry::Node(child) => {
+                    let (node,old) = insert_in(child, shift + BITS, hash, key, value);
+                    (Entry::Node(Gc::new(node)),old)
+                }
+            };
+            entries[slot] = entry;
+            (Node::Bitmap(*bitmap,entries),old)
+        }
+    }
+}
+
+//node holding two pairs that share the hash bits below shift
+fn merge(shift:u32,pair0:(Variable,Variable),hash0:u64,pair1:(Variable,Variable),hash1:u64) -> Node {
+    if shift >= 64 {
+        return Node::Collision(vec![pair0,pair1]);
+    }
+    let (bit0,bit1) = (bit_of(hash0, shift),bit_of(hash1, shift));
+    if bit0 == bit1 {
+        let child = merge(shift + BITS, pair0, hash0, pair1, hash1);
+        return Node::Bitmap(bit0,vec![Entry::Node(Gc::new(child))]);
+    }
+    let mut entries = vec![Entry::Pair(pair0.0,pair0.1),Entry::Pair(pair1.0,pair1.1)];
+    if bit0 > bit1 { entries.swap(0, 1); }
+    Node::Bitmap(bit0 | bit1,entries)
+}
+
+//None when the key is absent, otherwise the new node (None once empty) and the removed value
+fn remove_in(node:&Node,shift:u32,hash:u64,key:&Variable) -> Option<(Option<Node>,Variable)> {
+    match node {
+        Node::Collision(pairs) => {
+            let pos = pairs.iter().position(|(k,_)| k == key)?;
+            let mut pairs = pairs.clone();
+            let (_,old) = pairs.remove(pos);
+            Some((if pairs.is_empty() { None } else { Some(Node::Collision(pairs)) },old))
+        },
+        Node::Bitmap(bitmap,entries) => {
+            let bit = bit_of(hash, shift);
+            if bitmap & bit == 0 { return None; }
+            let slot = slot_of(*bitmap, bit);
+            let mut entries = entries.clone();
+            let mut bitmap = *bitmap;
+            let old = match &entries[slot] {
+                Entry::Pair(k,v) if k == key => {
+                    let old = v.clone();
+                    entries.remove(slot);
+                    bitmap &= !bit;
+                    old
+                },
+                Entry::Pair(_,_) => return None,
+                Entry::Node(child) => {
+                    let (child,old) = remove_in(child, shift + BITS, hash, key)?;
+                    match child {
+                        Some(child) => entries[slot] = Entry::Node(Gc::new(child)),
+                        None => {
+                            entries.remove(slot);
+                            bitmap &= !bit;
+                        }
+                    }
+                    old
+                }
+            };
+            Some((if entries.is_empty() { None } else { Some(Node::Bitmap(bitmap,entries)) },old))
+        }
+    }
+}
+
+pub struct Iter<'a> {
+    stack:Vec<(&'a Node,usize)>,
+    remain:usize
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a Variable,&'a Variable);
+
+    fn next(&mut self) -> Option<(&'a Variable,&'a Variable)> {
+        loop {
+            let (node,idx) = self.stack.last_mut()?;
+            let node:&'a Node = node;
+            let cur = *idx;
+            *idx += 1;
+            match node {
+                Node::Bitmap(_,entries) if cur < entries.len() => match &entries[cur] {
+                    Entry::Pair(k,v) => {
+                        self.remain -= 1;
+                        return Some((k,v));
+                    },
+                    Entry::Node(child) => self.stack.push((child,0))
+                },
+                Node::Collision(pairs) if cur < pairs.len() => {
+                    self.remain -= 1;
+                    return Some((&pairs[cur].0,&pairs[cur].1));
+                },
+                _ => { self.stack.pop(); }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize,Option<usize>) {
+        (self.remain,Some(self.remain))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+impl<'a> IntoIterator for &'a PersistentHashMap {
+    type Item = (&'a Variable,&'a Variable);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl Extend<(Variable,Variable)> for PersistentHashMap {
+    fn extend<I:IntoIterator<Item = (Variable,Variable)>>(&mut self,iter:I) {
+        for (k,v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl FromIterator<(Variable,Variable)> for PersistentHashMap {
+    fn from_iter<I:IntoIterator<Item = (Variable,Variable)>>(iter:I) -> Self {
+        let mut map = PersistentHashMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl From<HashMap<Variable,Variable>> for PersistentHashMap {
+    fn from(map:HashMap<Variable,Variable>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl PartialEq for PersistentHashMap {
+    fn eq(&self,other:&Self) -> bool {
+        self.len == other.len && self.iter().all(|(k,v)| other.get(k) == Some(v))
+    }
+}
+
+#[test]
+fn test_persistent_hash_map() {
+    let mut map:PersistentHashMap = (0..3000).map(|i| (Variable::Int(i),Variable::Int(i * 2))).collect();
+    //vectors hash by their items, equal vectors find the same entry
+    map.insert(Variable::from(vec![Variable::Int(1)]), Variable::Bool(true));
+    map.insert(Variable::from(vec![Variable::Int(2)]), Variable::Bool(false));
+    let old = map.clone();
+    for i in (0..3000).step_by(2) {
+        assert_eq!(map.remove(&Variable::Int(i)), Some(Variable::Int(i * 2)));
+    }
+    assert_eq!(map.remove(&Variable::Int(0)), None);
+    assert_eq!(map.remove(&Variable::from(vec![Variable::Int(1)])), Some(Variable::Bool(true)));
+    assert_eq!(map.insert(Variable::Int(1), Variable::Nil), Some(Variable::Int(2)));
+    assert_eq!(map.len(), 1501);
+    assert_eq!(map.iter().count(), 1501);
+    assert_eq!(old.len(), 3002);
+    assert_eq!(old.get(&Variable::Int(1)), Some(&Variable::Int(2)));
+    assert_eq!(old.get(&Variable::from(vec![Variable::Int(2)])), Some(&Variable::Bool(false)));
+    assert!((0..3000).all(|i| old.get(&Variable::Int(i)) == Some(&Variable::Int(i * 2))));
+    assert!(map.get(&Variable::from(vec![Variable::Int(2)])).is_some());
+}
+
+#[test]
+fn test_hash_collision() {
+    //only 4 hash bits left, so every key shares its hash with ~190 others
+    HASH_MASK.with(|mask| mask.set(0xF));
+    let mut map:PersistentHashMap = (0..3000).map(|i| (Variable::Int(i),Variable::Int(i * 2))).collect();
+    let old = map.clone();
+    for i in (0..3000).step_by(2) {
+        assert_eq!(map.remove(&Variable::Int(i)), Some(Variable::Int(i * 2)));
+    }
+    assert_eq!(map.remove(&Variable::Int(0)), None);
+    assert_eq!(map.len(), 1500);
+    assert_eq!(map.iter().count(), 1500);
+    assert!((0..3000).all(|i| old.get(&Variable::Int(i)) == Some(&Variable::Int(i * 2))));
+    assert!((1..3000).step_by(2).all(|i| map.get(&Variable::Int(i)) == Some(&Variable::Int(i * 2))));
+    HASH_MASK.with(|mask| mask.set(u64::MAX));
+}
@@ -0,0 +1,247 @@
+use std::{iter::FromIterator, ops::Index};
+
+use gc::{Finalize, Gc, Trace};
+
+use crate::Variable;
+
+const BITS:u32 = 5;
+const WIDTH:usize = 1 << BITS;
+const MASK:usize = WIDTH - 1;
+
+#[derive(Debug,Finalize,Trace)]
+enum Node {
+    Branch(Vec<Gc<Node>>),
+    Leaf(Vec<Variable>)
+}
+
+/*
+  32-way trie with the last (up to) 32 items kept in an owned tail.
+  clone is O(1) and shares every node, the &mut methods copy only the path they touch,
+  so clone + push is the persistent conj and push on a value in place is the transient one.
+*/
+#[derive(Debug,Clone,Finalize,Trace)]
+pub struct PersistentVector {
+    len:usize,
+    shift:u32,
+    root:Gc<Node>,
+    tail:Vec<Variable>
+}
+
+impl Default for PersistentVector {
+    fn default() -> Self {
+        PersistentVector::new()
+    }
+}
+
+impl PersistentVector {
+    pub fn new() -> Self {
+        PersistentVector { len:0, shift:BITS, root:Gc::new(Node::Branch(vec![])), tail:vec![] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn tail_offset(&self) -> usize {
+        if self.len < WIDTH { 0 } else { ((self.len - 1) >> BITS) << BITS }
+    }
+
+    //the 32 item chunk holding idx
+    fn leaf_for(&self,idx:usize) -> &[Variable] {
+        if idx >= self.tail_offset() {
+            return &self.tail;
+        }
+        let mut node:&Node = &self.root;
+        let mut level = self.shift;
+        loop {
+            match node {
+                Node::Branch(children) => {
+                    node = &children[(idx >> level) & MASK];
+                    level = level.saturating_sub(BITS);
+                },
+                Node::Leaf(items) => return items
+            }
+        }
+    }
+
+    pub fn get(&self,idx:usize) -> Option<&Variable> {
+        if idx >= self.len { return None; }
+        Some(&self.leaf_for(idx)[idx & MASK])
+    }
+
+    pub fn first(&self) -> Option<&Variable> {
+        self.get(0)
+    }
+
+    pub fn last(&self) -> Option<&Variable> {
+        if self.len == 0 { None } else { self.get(self.len - 1) }
+    }
+
+    pub fn push(&mut self,var:Variable) {
+        if self.len - self.tail_offset() < WIDTH {
+            self.tail.push(var);
+            self.len += 1;
+            return;
+        }
+        let tail_node = Gc::new(Node::Leaf(std::mem::replace(&mut self.tail, vec![var])));
+        if (self.len >> BITS) > (1 << self.shift) {
+            let path = new_path(self.shift, tail_node);
+            self.root = Gc::new(Node::Branch(vec![self.root.clone(),path]));
+            self.shift += BITS;
+        } else {
+            self.root = Gc::new(self.push_tail(self.shift, &self.root, tail_node));
+        }
+        self.len += 1;
+    }
+
+    fn push_tail(&self,level:u32,parent:&Node,tail_node:Gc<Node>) -> Node {
+        let mut children = match parent {
+            Node::Branch(children) => children.clone(),
+            Node::Leaf(_) => unreachable!()
+        };
+        let sub_idx = ((self.len - 1) >> level) & MASK;
+        let insert = if level == BITS {
+            tail_node
+        } else if sub_idx < children.len() {
+            Gc::new(self.push_tail(level - BITS, &children[sub_idx], tail_node))
+        } else {
+            new_path(level - BITS, tail_node)
+        };
+        if sub_idx < children.len() { children[sub_idx] = insert; } else { children.push(insert); }
+        Node::Branch(children)
+    }
+
+    //replaces the item at idx, panics when out of bounds like Vec
+    pub fn set(&mut self,idx:usize,var:Variable) {
+        assert!(idx < self.len, "index {} out of bounds for length {}", idx, self.len);
+        if idx >= self.tail_offset() {
+            self.tail[idx & MASK] = var;
+            return;
+        }
+        self.root = Gc::new(set_in(&self.root, self.shift, idx, var));
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { vec:self, idx:0, chunk:&[] }
+    }
+
+    pub fn to_vec(&self) -> Vec<Variable> {
+        self.iter().cloned().collect()
+    }
+}
+
+fn new_path(level:u32,node:Gc<Node>) -> Gc<Node> {
+    if level == 0 { node } else { Gc::new(Node::Branch(vec![new_path(level - BITS, node)])) }
+}
+
+fn set_in(node:&Node,level:u32,idx:usize,var:Variable) -> Node {
+    match node {
+        Node::Leaf(items) => {
+            let mut items = items.clone();
+            items[idx & MASK] = var;
+            Node::Leaf(items)
+        },
+        Node::Branch(children) => {
+            let mut children = children.clone();
+            let sub_idx = (idx >> level) & MASK;
+            children[sub_idx] = Gc::new(set_in(&children[sub_idx], level.saturating_sub(BITS), idx, var));
+            Node::Branch(children)
+        }
+    }
+}
+
+pub struct Iter<'a> {
+    vec:&'a PersistentVector,
+    idx:usize,
+    chunk:&'a [Variable]
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Variable;
+
+    fn next(&mut self) -> Option<&'a Variable> {
+        if self.idx >= self.vec.len { return None; }
+        if self.idx & MASK == 0 {
+            self.chunk = self.vec.leaf_for(self.idx);
+        }
+        let var = &self.chunk[self.idx & MASK];
+        self.idx += 1;
+        Some(var)
+    }
+
+    fn size_hint(&self) -> (usize,Option<usize>) {
+        let remain = self.vec.len - self.idx;
+        (remain,Some(remain))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+impl<'a> IntoIterator for &'a PersistentVector {
+    type Item = &'a Variable;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl Index<usize> for PersistentVector {
+    type Output = Variable;
+
+    fn index(&self,idx:usize) -> &Variable {
+        match self.get(idx) {
+            Some(var) => var,
+            None => panic!("index {} out of bounds for length {}", idx, self.len)
+        }
+    }
+}
+
+impl Extend<Variable> for PersistentVector {
+    fn extend<I:IntoIterator<Item = Variable>>(&mut self,iter:I) {
+        for var in iter {
+            self.push(var);
+        }
+    }
+}
+
+impl FromIterator<Variable> for PersistentVector {
+    fn from_iter<I:IntoIterator<Item = Variable>>(iter:I) -> Self {
+        let mut vec = PersistentVector::new();
+        vec.extend(iter);
+        vec
+    }
+}
+
+impl From<Vec<Variable>> for PersistentVector {
+    fn from(lst:Vec<Variable>) -> Self {
+        lst.into_iter().collect()
+    }
+}
+
+impl PartialEq for PersistentVector {
+    fn eq(&self,other:&Self) -> bool {
+        self.len == other.len && self.iter().zip(other.iter()).all(|(a,b)| a == b)
+    }
+}
+
+#[test]
+fn test_persistent_vector() {
+    let mut vec:PersistentVector = (0..2000).map(Variable::Int).collect();
+    let old = vec.clone();
+    for idx in 0..2000 {
+        vec.set(idx, Variable::Int(idx as i64 * 2));
+    }
+    vec.push(Variable::Nil);
+    assert_eq!(vec.len(), 2001);
+    assert_eq!(old.len(), 2000);
+    assert!(old.iter().enumerate().all(|(idx,v)| *v == Variable::Int(idx as i64)));
+    assert!(vec.iter().take(2000).enumerate().all(|(idx,v)| *v == Variable::Int(idx as i64 * 2)));
+    assert_eq!(vec[2000], Variable::Nil);
+    assert_eq!(old.last(), Some(&Variable::Int(1999)));
+    assert!(old.get(2000).is_none());
+}
@@ -0,0 +1,112 @@
+use lite_clojure_parser::{ast::MacroExpander, core_macros::is_core_macro, cexpr::{CExpr, CExprKind, Number}, errors::ASTError, span::Span};
+use lite_clojure_parser::value::{Keyword, Symbol as ASTSymbol};
+use crate::{Variable, GcRefCell, PersistentHashMap, variable::{ExecScope, Symbol}};
+
+//quoted forms evaluate to data: lists stay lists, symbols stay symbols
+pub fn cexpr_to_var(cexpr:&CExpr) -> Variable {
+    match &cexpr.kind {
+        CExprKind::Nil => Variable::Nil,
+        CExprKind::Boolean(b) => Variable::Bool(*b),
+        CExprKind::Number(_,Number::Int(i)) => Variable::Int(*i),
+        CExprKind::Number(_,Number::Float(f)) => Variable::Float(*f),
+        CExprKind::String(s) => Variable::String(GcRefCell::new(s.clone())),
+        CExprKind::Keyword(k) => Variable::Keyword(GcRefCell::new(k.sym.name.clone())),
+        CExprKind::Char(chr) => Variable::Char(*chr),
+        CExprKind::Symbol(sym) => Variable::Symbol(Symbol::val(sym.to_string(), 0)),
+        CExprKind::List(lst) => Variable::List(GcRefCell::new(cexprs_to_vars(lst).into())),
+        CExprKind::Vector(lst) => Variable::Array(GcRefCell::new(cexprs_to_vars(lst).into())),
+        CExprKind::Set(lst) => Variable::Set(GcRefCell::new(cexprs_to_vars(lst).into_iter().collect())),
+        CExprKind::Map(lst) => {
+            let mut kvs = cexprs_to_vars(lst).into_iter();
+            let mut hash_map = PersistentHashMap::new();
+            while let (Some(k),Some(v)) = (kvs.next(),kvs.next()) {
+                hash_map.insert(k, v);
+            }
+            Variable::Map(GcRefCell::new(hash_map))
+        },
+        CExprKind::Quote(e) => wrap_form("quote", e),
+        CExprKind::SyntaxQuote(e) => wrap_form("clojure.core/syntax-quote", e),
+        CExprKind::UnQuote(e) => wrap_form("clojure.core/unquote", e),
+        CExprKind::UnQuoteS(e) => wrap_form("clojure.core/unquote-splicing", e),
+        CExprKind::Dref(e) => wrap_form("clojure.core/deref", e),
+        CExprKind::QuoteVar(sym) => {
+            let var_sym = Variable::Symbol(Symbol::val(String::from("var"), 0));
+            let sym = Variable::Symbol(Symbol::val(sym.to_string(), 0));
+            Variable::List(GcRefCell::new(vec![var_sym,sym].into()))
+        },
+        CExprKind::Comment(_) | CExprKind::Meta(_) => Variable::Nil
+    }
+}
+
+fn cexprs_to_vars(lst:&[CExpr]) -> Vec<Variable> {
+    lst.iter().filter(|e| !e.is_comment()).map(cexpr_to_var).collect()
+}
+
+fn wrap_form(name:&str,cexpr:&CExpr) -> Variable {
+    let head = Variable::Symbol(Symbol::val(name.to_string(), 0));
+    Variable::List(GcRefCell::new(vec![head,cexpr_to_var(cexpr)].into()))
+}
+
+//macro results are data again and go back through analysis
+pub fn var_to_cexpr(var:&Variable,span:&Span) -> Option<CExpr> {
+    let kind = match var {
+        Variable::Nil => CExprKind::Nil,
+        Variable::Bool(b) => CExprKind::Boolean(*b),
+        Variable::Int(i) => CExprKind::Number(i.to_string(),Number::Int(*i)),
+        Variable::Float(f) => CExprKind::Number(f.to_string(),Number::Float(*f)),
+        Variable::String(s) => CExprKind::String(s.borrow().clone()),
+        Variable::Char(chr) => CExprKind::Char(*chr),
+        Variable::Keyword(k) => {
+            let name = k.borrow().clone();
+            let mut keyword = Keyword::intern(ASTSymbol::intern(None, name));
+            keyword.is_local = keyword.sym.name.starts_with("::");
+            CExprKind::Keyword(keyword)
+        },
+        Variable::Symbol(sym) => CExprKind::Symbol(ASTSymbol::intern_name(&sym.var_name)),
+        Variable::Var(name) => CExprKind::QuoteVar(ASTSymbol::intern_name(name)),
+        Variable::List(lst) => CExprKind::List(vars_to_cexprs(&lst.borrow().to_vec(),span)?),
+        Variable::Array(arr) => CExprKind::Vector(vars_to_cexprs(&arr.borrow().to_vec(),span)?),
+        Variable::Set(set) => {
+            let items:Vec<Variable> = set.borrow().iter().cloned().collect();
+            CExprKind::Set(vars_to_cexprs(&items,span)?)
+        },
+        Variable::Map(hash_map) => {
+            let mut lst:Vec<CExpr> = vec![];
+            for (k,v) in hash_map.borrow().iter() {
+                lst.push(var_to_cexpr(k, span)?);
+                lst.push(var_to_cexpr(v, span)?);
+            }
+            CExprKind::Map(lst)
+        },
+        Variable::LazySeq(lazy) => match lazy.realized_items() {
+            (items,false) => CExprKind::List(vars_to_cexprs(&items,span)?),
+            (_,true) => return None
+        },
+        Variable::Function(_) | Variable::ExInfo(_) | Variable::UserData(_) => return None
+    };
+    Some(CExpr::new(kind,span.clone()))
+}
+
+fn vars_to_cexprs(lst:&[Variable],span:&Span) -> Option<Vec<CExpr>> {
+    lst.iter().map(|v| var_to_cexpr(v, span)).collect()
+}
+
+impl<'a> MacroExpander for ExecScope<'a> {
+    fn expand_macro(&mut self,sym:&ASTSymbol,args:&[CExpr],span:&Span) -> Option<Result<CExpr,ASTError>> {
+        let macro_fn = self.context.find_macro(sym.sym_ns().map(|s| s.as_str()), sym.sym_name(), self.modules)?;
+        let args:Vec<Variable> = cexprs_to_vars(args);
+        let ret = match self.context.invoke_func2(&macro_fn, args, self.modules) {
+            Ok(ret) => ret,
+            Err(err) => return Some(Err(ASTError::ErrMacro(format!("{} {:?}",sym,err))))
+        };
+        Some(var_to_cexpr(&ret, span).ok_or(ASTError::ErrMacro(format!("{} returned {}",sym,ret.show_str()))))
+    }
+
+    //names defined in the current file shadow the prelude
+    fn is_core_symbol(&self,name:&str) -> bool {
+        if self.context.find_local_symbol(name).is_some() || self.context.find_local_macro(name).is_some() {
+            return false;
+        }
+        is_core_macro(name) || self.modules.prelude.find_local_symbol(name).is_some() || self.modules.prelude.find_local_macro(name).is_some()
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{EvalError, GcRefCell, PersistentHashMap, Variable, buildin_fn::{self, check_args, type_error}, lazy_seq::{self, LazySeq, is_lazy, next_chunk, walk}, variable::ExecScope};
+
+//items of a seqable value, lazy seqs are realized completely
+pub(crate) fn seq_items(scope:&mut ExecScope,fn_name:&str,var:&Variable) -> Result<Vec<Variable>,EvalError> {
+    if !is_lazy(var) {
+        return coll_items(fn_name, var);
+    }
+    let mut items = vec![];
+    walk(scope, fn_name, var, |_,item| {
+        items.push(item);
+        Ok(true)
+    })?;
+    Ok(items)
+}
+
+//items of a collection, map entries become [k v] vectors and strings their chars
+pub(crate) fn coll_items(fn_name:&str,var:&Variable) -> Result<Vec<Variable>,EvalError> {
+    match var {
+        Variable::Array(arr) | Variable::List(arr) => Ok(arr.borrow().to_vec()),
+        Variable::Set(set) => Ok(set.borrow().iter().cloned().collect()),
+        Variable::Map(map) => Ok(map.borrow().iter().map(|(k,v)| map_entry(k, v)).collect()),
+        Variable::String(s) => Ok(s.borrow().chars().map(Variable::Char).collect()),
+        Variable::Nil => Ok(vec![]),
+        other => Err(type_error(fn_name, other))
+    }
+}
+
+fn map_entry(k:&Variable,v:&Variable) -> Variable {
+    Variable::Array(GcRefCell::new(vec![k.clone(),v.clone()].into()))
+}
+
+fn new_list(items:Vec<Variable>) -> Variable {
+    Variable::List(GcRefCell::new(items.into()))
+}
+
+pub(crate) fn call(scope:&mut ExecScope,f:&Variable,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    scope.context.invoke_func2(f, args, scope.modules)
+}
+
+pub(crate) fn count_arg(fn_name:&str,var:&Variable) -> Result<usize,EvalError> {
+    let n = var.cast_int().ok_or_else(|| type_error(fn_name, var))?;
+    Ok(n.max(0) as usize)
+}
+
+pub fn first(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("first", &args, 1, Some(1))?;
+    match &args[0] {
+        Variable::Array(arr) | Variable::List(arr) => Ok(arr.borrow().first().cloned().unwrap_or(Variable::Nil)),
+        other => Ok(next_chunk(scope, "first", other)?.map(|(items,_)| items[0].clone()).unwrap_or(Variable::Nil))
+    }
+}
+
+pub fn rest(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("rest", &args, 1, Some(1))?;
+    if is_lazy(&args[0]) {
+        return Ok(match next_chunk(scope, "rest", &args[0])? {
+            Some((mut items,rest)) => LazySeq::realized(items.split_off(1), rest),
+            None => LazySeq::realized(vec![], Variable::Nil)
+        });
+    }
+    Ok(new_list(coll_items("rest", &args[0])?.into_iter().skip(1).collect()))
+}
+
+//consing onto a lazy seq keeps it lazy
+pub fn cons(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("cons", &args, 2, Some(2))?;
+    if is_lazy(&args[1]) {
+        return Ok(LazySeq::realized(vec![args[0].clone()], args[1].clone()));
+    }
+    let mut items = vec![args[0].clone()];
+    items.extend(coll_items("cons", &args[1])?);
+    Ok(new_list(items))
+}
+
+pub fn count(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("count", &args, 1, Some(1))?;
+    let len = match &args[0] {
+        Variable::LazySeq(_) => seq_items(scope, "count", &args[0])?.len(),
+        Variable::Array(arr) | Variable::List(arr) => arr.borrow().len(),
+        Variable::Map(map) => map.borrow().len(),
+        Variable::Set(set) => set.borrow().len(),
+        Variable::String(s) => s.borrow().chars().count(),
+        Variable::Nil => 0,
+        other => return Err(type_error("count", other))
+    };
+    Ok(Variable::Int(len as i64))
+}
+
+pub fn seq(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("seq", &args, 1, Some(1))?;
+    if is_lazy(&args[0]) {
+        let is_empty = next_chunk(scope, "seq", &args[0])?.is_none();
+        return Ok(if is_empty { Variable::Nil } else { args[0].clone() });
+    }
+    let items = coll_items("seq", &args[0])?;
+    Ok(if items.is_empty() { Variable::Nil } else { new_list(items) })
+}
+
+//(map f c1 c2 ..) stops at the shortest collection, lazy when any of them is
+pub fn map(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("map", &args, 2, None)?;
+    if args[1..].iter().any(is_lazy) {
+        return Ok(lazy_seq::lazy_map(args[0].clone(), args[1..].to_vec()));
+    }
+    let colls = args[1..].iter().map(|c| coll_items("map", c)).collect::<Result<Vec<_>,_>>()?;
+    let len = colls.iter().map(|c| c.len()).min().unwrap_or(0);
+    let mut ret = Vec::with_capacity(len);
+    for idx in 0..len {
+        let call_args = colls.iter().map(|c| c[idx].clone()).collect();
+        ret.push(call(scope, &args[0], call_args)?);
+    }
+    Ok(new_list(ret))
+}
+
+fn filter_by(scope:&mut ExecScope,fn_name:&str,args:&[Variable],keep:bool) -> Result<Variable,EvalError> {
+    check_args(fn_name, args, 2, Some(2))?;
+    if is_lazy(&args[1]) {
+        return Ok(lazy_seq::lazy_filter(args[0].clone(), args[1].clone(), keep));
+    }
+    let mut ret = vec![];
+    for item in coll_items(fn_name, &args[1])? {
+        if call(scope, &args[0], vec![item.clone()])?.is_truthy() == keep {
+            ret.push(item);
+        }
+    }
+    Ok(new_list(ret))
+}
+
+pub fn filter(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    filter_by(scope, "filter", &args, true)
+}
+
+pub fn remove(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    filter_by(scope, "remove", &args, false)
+}
+
+//(reduce f coll) starts from the first item, (f) for an empty coll
+pub fn reduce(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("reduce", &args, 2, Some(3))?;
+    let mut items = seq_items(scope, "reduce", args.last().unwrap())?.into_iter();
+    let mut acc = if args.len() == 3 {
+        args[1].clone()
+    } else {
+        match items.next() {
+            Some(item) => item,
+            None => return call(scope, &args[0], vec![])
+        }
+    };
+    for item in items {
+        acc = call(scope, &args[0], vec![acc,item])?;
+    }
+    Ok(acc)
+}
+
+//(range) is infinite, every form is lazy so large ranges are only realized as far as they are walked
+pub fn range(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("range", &args, 0, Some(3))?;
+    let mut nums:Vec<i64> = vec![];
+    for arg in args.iter() {
+        nums.push(arg.cast_int().ok_or_else(|| type_error("range", arg))?);
+    }
+    Ok(match nums.as_slice() {
+        [] => lazy_seq::lazy_range(0,None,1),
+        [end] => lazy_seq::lazy_range(0,Some(*end),1),
+        [start,end] => lazy_seq::lazy_range(*start,Some(*end),1),
+        [start,end,step] => lazy_seq::lazy_range(*start,Some(*end),*step),
+        _ => unreachable!()
+    })
+}
+
+pub fn take(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("take", &args, 2, Some(2))?;
+    let n = count_arg("take", &args[0])?;
+    let mut ret = vec![];
+    if n > 0 {
+        walk(scope, "take", &args[1], |_,item| {
+            ret.push(item);
+            Ok(ret.len() < n)
+        })?;
+    }
+    Ok(new_list(ret))
+}
+
+pub fn drop(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("drop", &args, 2, Some(2))?;
+    let n = count_arg("drop", &args[0])?;
+    if is_lazy(&args[1]) {
+        return lazy_seq::drop_lazy(scope, n, &args[1]);
+    }
+    Ok(new_list(coll_items("drop", &args[1])?.into_iter().skip(n).collect()))
+}
+
+pub fn into(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("into", &args, 2, Some(2))?;
+    let mut conj_args = vec![args[0].clone()];
+    conj_args.extend(seq_items(scope, "into", &args[1])?);
+    buildin_fn::conj(scope, conj_args)
+}
+
+pub fn some(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("some", &args, 2, Some(2))?;
+    let mut ret = Variable::Nil;
+    walk(scope, "some", &args[1], |scope,item| {
+        ret = call(scope, &args[0], vec![item])?;
+        Ok(!ret.is_truthy())
+    })?;
+    Ok(if ret.is_truthy() { ret } else { Variable::Nil })
+}
+
+pub fn every(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("every?", &args, 2, Some(2))?;
+    let mut ret = true;
+    walk(scope, "every?", &args[1], |scope,item| {
+        ret = call(scope, &args[0], vec![item])?.is_truthy();
+        Ok(ret)
+    })?;
+    Ok(Variable::Bool(ret))
+}
+
+fn map_arg(fn_name:&str,var:&Variable) -> Result<PersistentHashMap,EvalError> {
+    match var {
+        Variable::Map(map) => Ok(map.borrow().clone()),
+        Variable::Nil => Ok(PersistentHashMap::new()),
+        other => Err(type_error(fn_name, other))
+    }
+}
+
+pub fn keys(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("keys", &args, 1, Some(1))?;
+    let map = map_arg("keys", &args[0])?;
+    Ok(if map.is_empty() { Variable::Nil } else { new_list(map.keys().cloned().collect()) })
+}
+
+pub fn vals(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("vals", &args, 1, Some(1))?;
+    let map = map_arg("vals", &args[0])?;
+    Ok(if map.is_empty() { Variable::Nil } else { new_list(map.values().cloned().collect()) })
+}
+
+//later maps win, nil when every argument is nil
+pub fn merge(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    let mut ret:Option<PersistentHashMap> = None;
+    for arg in args.iter() {
+        if let Variable::Nil = arg { continue; }
+        let map = map_arg("merge", arg)?;
+        match &mut ret {
+            Some(ret) => ret.extend(map.iter().map(|(k,v)| (k.clone(),v.clone()))),
+            None => ret = Some(map)
+        }
+    }
+    Ok(ret.map(|map| Variable::Map(GcRefCell::new(map))).unwrap_or(Variable::Nil))
+}
+
+pub fn select_keys(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("select-keys", &args, 2, Some(2))?;
+    let map = map_arg("select-keys", &args[0])?;
+    let mut ret = PersistentHashMap::new();
+    for key in seq_items(scope, "select-keys", &args[1])? {
+        if let Some(v) = map.get(&key) {
+            let v = v.clone();
+            ret.insert(key, v);
+        }
+    }
+    Ok(Variable::Map(GcRefCell::new(ret)))
+}
+
+pub fn frequencies(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("frequencies", &args, 1, Some(1))?;
+    let mut ret = PersistentHashMap::new();
+    for item in seq_items(scope, "frequencies", &args[0])? {
+        let n = ret.get(&item).and_then(|v| v.cast_int()).unwrap_or(0);
+        ret.insert(item, Variable::Int(n + 1));
+    }
+    Ok(Variable::Map(GcRefCell::new(ret)))
+}
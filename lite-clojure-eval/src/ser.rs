@@ -0,0 +1,240 @@
+use std::fmt::Display;
+
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple, SerializeTupleStruct, SerializeTupleVariant};
+
+use crate::{EvalError, GcRefCell, PersistentHashMap, Variable, convert::derive_support::keyword, de::{key_segment, ENUM_TAG, ENUM_VALUE}};
+
+/*
+  structs become maps with keyword keys, sequences and tuples vectors
+  unit variants keywords, the others maps tagged {:type :Variant ...} like the derive writes them
+*/
+pub fn to_variable<T:Serialize + ?Sized>(value:&T) -> Result<Variable,EvalError> {
+    value.serialize(Serializer)
+}
+
+impl ser::Error for EvalError {
+    fn custom<T:Display>(msg:T) -> Self {
+        EvalError::NativeError(msg.to_string())
+    }
+}
+
+pub struct Serializer;
+
+fn new_vec(lst:Vec<Variable>) -> Variable {
+    Variable::Array(GcRefCell::new(lst.into()))
+}
+
+fn new_map(map:PersistentHashMap) -> Variable {
+    Variable::Map(GcRefCell::new(map))
+}
+
+fn tagged(variant:&str,mut map:PersistentHashMap) -> Variable {
+    map.insert(keyword(ENUM_TAG),keyword(variant));
+    new_map(map)
+}
+
+fn tagged_value(variant:&str,value:Variable) -> Variable {
+    let mut map = PersistentHashMap::new();
+    map.insert(keyword(ENUM_VALUE),value);
+    tagged(variant,map)
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Variable;
+    type Error = EvalError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self,v:bool) -> Result<Variable,EvalError> { Ok(Variable::Bool(v)) }
+    fn serialize_i8(self,v:i8) -> Result<Variable,EvalError> { Ok(Variable::Int(v as i64)) }
+    fn serialize_i16(self,v:i16) -> Result<Variable,EvalError> { Ok(Variable::Int(v as i64)) }
+    fn serialize_i32(self,v:i32) -> Result<Variable,EvalError> { Ok(Variable::Int(v as i64)) }
+    fn serialize_i64(self,v:i64) -> Result<Variable,EvalError> { Ok(Variable::Int(v)) }
+    fn serialize_u8(self,v:u8) -> Result<Variable,EvalError> { Ok(Variable::Int(v as i64)) }
+    fn serialize_u16(self,v:u16) -> Result<Variable,EvalError> { Ok(Variable::Int(v as i64)) }
+    fn serialize_u32(self,v:u32) -> Result<Variable,EvalError> { Ok(Variable::Int(v as i64)) }
+
+    fn serialize_u64(self,v:u64) -> Result<Variable,EvalError> {
+        if v > i64::MAX as u64 {
+            return Err(EvalError::NativeError(format!("{} out of int range",v)));
+        }
+        Ok(Variable::Int(v as i64))
+    }
+
+    fn serialize_f32(self,v:f32) -> Result<Variable,EvalError> { Ok(Variable::Float(v as f64)) }
+    fn serialize_f64(self,v:f64) -> Result<Variable,EvalError> { Ok(Variable::Float(v)) }
+    fn serialize_char(self,v:char) -> Result<Variable,EvalError> { Ok(Variable::Char(v)) }
+    fn serialize_str(self,v:&str) -> Result<Variable,EvalError> { Ok(Variable::from(v)) }
+
+    fn serialize_bytes(self,v:&[u8]) -> Result<Variable,EvalError> {
+        Ok(new_vec(v.iter().map(|b| Variable::Int(*b as i64)).collect()))
+    }
+
+    fn serialize_none(self) -> Result<Variable,EvalError> { Ok(Variable::Nil) }
+
+    fn serialize_some<T:Serialize + ?Sized>(self,value:&T) -> Result<Variable,EvalError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Variable,EvalError> { Ok(Variable::Nil) }
+    fn serialize_unit_struct(self,_:&'static str) -> Result<Variable,EvalError> { Ok(Variable::Nil) }
+
+    fn serialize_unit_variant(self,_:&'static str,_:u32,variant:&'static str) -> Result<Variable,EvalError> {
+        Ok(keyword(variant))
+    }
+
+    fn serialize_newtype_struct<T:Serialize + ?Sized>(self,_:&'static str,value:&T) -> Result<Variable,EvalError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T:Serialize + ?Sized>(self,_:&'static str,_:u32,variant:&'static str,value:&T) -> Result<Variable,EvalError> {
+        let value = value.serialize(self).map_err(|err| err.at_path(ENUM_VALUE))?;
+        Ok(tagged_value(variant,value))
+    }
+
+    fn serialize_seq(self,len:Option<usize>) -> Result<SeqSerializer,EvalError> {
+        Ok(SeqSerializer { variant:None, lst:Vec::with_capacity(len.unwrap_or(0)) })
+    }
+
+    fn serialize_tuple(self,len:usize) -> Result<SeqSerializer,EvalError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self,_:&'static str,len:usize) -> Result<SeqSerializer,EvalError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self,_:&'static str,_:u32,variant:&'static str,len:usize) -> Result<SeqSerializer,EvalError> {
+        Ok(SeqSerializer { variant:Some(variant), lst:Vec::with_capacity(len) })
+    }
+
+    fn serialize_map(self,_:Option<usize>) -> Result<MapSerializer,EvalError> {
+        Ok(MapSerializer { variant:None, map:PersistentHashMap::new(), key:None })
+    }
+
+    fn serialize_struct(self,_:&'static str,_:usize) -> Result<MapSerializer,EvalError> {
+        self.serialize_map(None)
+    }
+
+    fn serialize_struct_variant(self,_:&'static str,_:u32,variant:&'static str,_:usize) -> Result<MapSerializer,EvalError> {
+        Ok(MapSerializer { variant:Some(variant), map:PersistentHashMap::new(), key:None })
+    }
+}
+
+pub struct SeqSerializer {
+    variant:Option<&'static str>,
+    lst:Vec<Variable>
+}
+
+impl SeqSerializer {
+    fn push<T:Serialize + ?Sized>(&mut self,value:&T) -> Result<(),EvalError> {
+        let idx = self.lst.len();
+        let var = value.serialize(Serializer).map_err(|err| {
+            let err = err.at_path(&format!("[{}]",idx));
+            if self.variant.is_some() { err.at_path(ENUM_VALUE) } else { err }
+        })?;
+        self.lst.push(var);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Variable,EvalError> {
+        let lst = new_vec(self.lst);
+        Ok(match self.variant {
+            Some(variant) => tagged_value(variant,lst),
+            None => lst
+        })
+    }
+}
+
+impl SerializeSeq for SeqSerializer {
+    type Ok = Variable;
+    type Error = EvalError;
+    fn serialize_element<T:Serialize + ?Sized>(&mut self,value:&T) -> Result<(),EvalError> { self.push(value) }
+    fn end(self) -> Result<Variable,EvalError> { self.finish() }
+}
+
+impl SerializeTuple for SeqSerializer {
+    type Ok = Variable;
+    type Error = EvalError;
+    fn serialize_element<T:Serialize + ?Sized>(&mut self,value:&T) -> Result<(),EvalError> { self.push(value) }
+    fn end(self) -> Result<Variable,EvalError> { self.finish() }
+}
+
+impl SerializeTupleStruct for SeqSerializer {
+    type Ok = Variable;
+    type Error = EvalError;
+    fn serialize_field<T:Serialize + ?Sized>(&mut self,value:&T) -> Result<(),EvalError> { self.push(value) }
+    fn end(self) -> Result<Variable,EvalError> { self.finish() }
+}
+
+impl SerializeTupleVariant for SeqSerializer {
+    type Ok = Variable;
+    type Error = EvalError;
+    fn serialize_field<T:Serialize + ?Sized>(&mut self,value:&T) -> Result<(),EvalError> { self.push(value) }
+    fn end(self) -> Result<Variable,EvalError> { self.finish() }
+}
+
+pub struct MapSerializer {
+    variant:Option<&'static str>,
+    map:PersistentHashMap,
+    key:Option<Variable>
+}
+
+impl MapSerializer {
+    fn insert<T:Serialize + ?Sized>(&mut self,key:Variable,value:&T) -> Result<(),EvalError> {
+        let var = value.serialize(Serializer).map_err(|err| err.at_path(&key_segment(&key)))?;
+        self.map.insert(key,var);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Variable,EvalError> {
+        Ok(match self.variant {
+            Some(variant) => tagged(variant,self.map),
+            None => new_map(self.map)
+        })
+    }
+}
+
+impl SerializeMap for MapSerializer {
+    type Ok = Variable;
+    type Error = EvalError;
+
+    fn serialize_key<T:Serialize + ?Sized>(&mut self,key:&T) -> Result<(),EvalError> {
+        self.key = Some(key.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T:Serialize + ?Sized>(&mut self,value:&T) -> Result<(),EvalError> {
+        let key = self.key.take().ok_or_else(|| EvalError::NativeError("map value before key".to_string()))?;
+        self.insert(key,value)
+    }
+
+    fn end(self) -> Result<Variable,EvalError> { self.finish() }
+}
+
+impl SerializeStruct for MapSerializer {
+    type Ok = Variable;
+    type Error = EvalError;
+
+    fn serialize_field<T:Serialize + ?Sized>(&mut self,name:&'static str,value:&T) -> Result<(),EvalError> {
+        self.insert(keyword(name),value)
+    }
+
+    fn end(self) -> Result<Variable,EvalError> { self.finish() }
+}
+
+impl SerializeStructVariant for MapSerializer {
+    type Ok = Variable;
+    type Error = EvalError;
+
+    fn serialize_field<T:Serialize + ?Sized>(&mut self,name:&'static str,value:&T) -> Result<(),EvalError> {
+        self.insert(keyword(name),value)
+    }
+
+    fn end(self) -> Result<Variable,EvalError> { self.finish() }
+}
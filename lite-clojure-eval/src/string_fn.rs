@@ -0,0 +1,294 @@
+use crate::{EvalError, GcRefCell, Variable, buildin_fn::{check_args, type_error}, lazy_seq::realize_all, seq_fn::seq_items, variable::ExecScope};
+
+//text of a value without the quotes show_str puts around strings and chars, nil is empty
+pub(crate) fn str_of(var:&Variable) -> String {
+    match var {
+        Variable::String(s) => s.borrow().clone(),
+        Variable::Char(chr) => chr.to_string(),
+        Variable::Nil => String::new(),
+        other => other.show_str()
+    }
+}
+
+fn string_arg(fn_name:&str,var:&Variable) -> Result<String,EvalError> {
+    match var {
+        Variable::String(s) => Ok(s.borrow().clone()),
+        other => Err(type_error(fn_name, other))
+    }
+}
+
+//strings and chars both work as the text to look for
+fn pattern_arg(fn_name:&str,var:&Variable) -> Result<String,EvalError> {
+    match var {
+        Variable::String(s) => Ok(s.borrow().clone()),
+        Variable::Char(chr) => Ok(chr.to_string()),
+        other => Err(type_error(fn_name, other))
+    }
+}
+
+fn index_arg(fn_name:&str,var:&Variable) -> Result<i64,EvalError> {
+    var.cast_int().ok_or_else(|| type_error(fn_name, var))
+}
+
+fn new_string(s:String) -> Variable {
+    Variable::String(GcRefCell::new(s))
+}
+
+//byte offset of the char index, the string length for the index just past the end
+fn byte_offset(s:&str,char_idx:usize) -> Option<usize> {
+    s.char_indices().map(|(idx,_)| idx).chain(std::iter::once(s.len())).nth(char_idx)
+}
+
+pub fn str(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    let mut ret = String::new();
+    for arg in args.iter() {
+        realize_all(scope, arg)?;
+        ret.push_str(&str_of(arg));
+    }
+    Ok(new_string(ret))
+}
+
+//(subs s start end) with char indexes, end defaults to the length
+pub fn subs(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("subs", &args, 2, Some(3))?;
+    let s = string_arg("subs", &args[0])?;
+    let len = s.chars().count();
+    let start = index_arg("subs", &args[1])?;
+    let end = match args.get(2) {
+        Some(end) => index_arg("subs", end)?,
+        None => len as i64
+    };
+    if start < 0 || start as usize > len {
+        return Err(EvalError::IndexOutOfBounds(start,len));
+    }
+    if end < start || end as usize > len {
+        return Err(EvalError::IndexOutOfBounds(end,len));
+    }
+    Ok(new_string(s.chars().skip(start as usize).take((end - start) as usize).collect()))
+}
+
+//(split s sep) drops trailing empty strings like clojure, (split s sep limit) keeps at most limit parts
+pub fn split(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("split", &args, 2, Some(3))?;
+    let s = string_arg("split", &args[0])?;
+    let sep = pattern_arg("split", &args[1])?;
+    let limit = match args.get(2) {
+        Some(limit) => Some(index_arg("split", limit)?),
+        None => None
+    };
+    let mut parts:Vec<String> = match (sep.is_empty(),limit) {
+        (true,_) => s.chars().map(|chr| chr.to_string()).collect(),
+        (false,Some(limit)) if limit > 0 => s.splitn(limit as usize, sep.as_str()).map(String::from).collect(),
+        (false,_) => s.split(sep.as_str()).map(String::from).collect()
+    };
+    if limit.is_none() {
+        while parts.len() > 1 && parts.last().map(|p| p.is_empty()).unwrap_or(false) {
+            parts.pop();
+        }
+    }
+    Ok(Variable::from(parts.into_iter().map(new_string).collect::<Vec<_>>()))
+}
+
+//(join coll) or (join sep coll)
+pub fn join(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("join", &args, 1, Some(2))?;
+    let sep = if args.len() == 2 { str_of(&args[0]) } else { String::new() };
+    let items = seq_items(scope, "join", args.last().unwrap())?;
+    for item in items.iter() {
+        realize_all(scope, item)?;
+    }
+    let strs:Vec<String> = items.iter().map(str_of).collect();
+    Ok(new_string(strs.join(&sep)))
+}
+
+fn map_string(fn_name:&str,args:&[Variable],f:impl Fn(&str) -> String) -> Result<Variable,EvalError> {
+    check_args(fn_name, args, 1, Some(1))?;
+    Ok(new_string(f(&string_arg(fn_name, &args[0])?)))
+}
+
+pub fn trim(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    map_string("trim", &args, |s| s.trim().to_string())
+}
+
+pub fn upper_case(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    map_string("upper-case", &args, str::to_uppercase)
+}
+
+pub fn lower_case(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    map_string("lower-case", &args, str::to_lowercase)
+}
+
+//(replace s match replacement) replaces every occurrence, match is a string or a char
+pub fn replace(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("replace", &args, 3, Some(3))?;
+    let s = string_arg("replace", &args[0])?;
+    let from = pattern_arg("replace", &args[1])?;
+    let to = pattern_arg("replace", &args[2])?;
+    Ok(new_string(s.replace(from.as_str(), &to)))
+}
+
+fn test_string(fn_name:&str,args:&[Variable],f:impl Fn(&str,&str) -> bool) -> Result<Variable,EvalError> {
+    check_args(fn_name, args, 2, Some(2))?;
+    let s = string_arg(fn_name, &args[0])?;
+    let sub = pattern_arg(fn_name, &args[1])?;
+    Ok(Variable::Bool(f(&s, &sub)))
+}
+
+pub fn starts_with(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    test_string("starts-with?", &args, |s,sub| s.starts_with(sub))
+}
+
+pub fn ends_with(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    test_string("ends-with?", &args, |s,sub| s.ends_with(sub))
+}
+
+pub fn includes(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    test_string("includes?", &args, |s,sub| s.contains(sub))
+}
+
+//(index-of s value from) is the char index of value at or after from, nil when missing
+pub fn index_of(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("index-of", &args, 2, Some(3))?;
+    let s = string_arg("index-of", &args[0])?;
+    let sub = pattern_arg("index-of", &args[1])?;
+    let from = match args.get(2) {
+        Some(from) => index_arg("index-of", from)?.max(0) as usize,
+        None => 0
+    };
+    let start = match byte_offset(&s, from) {
+        Some(start) => start,
+        None => return Ok(Variable::Nil)
+    };
+    Ok(match s[start..].find(sub.as_str()) {
+        Some(pos) => Variable::Int(s[..start + pos].chars().count() as i64),
+        None => Variable::Nil
+    })
+}
+
+//nil and whitespace only strings are blank
+pub fn is_blank(_:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("blank?", &args, 1, Some(1))?;
+    match &args[0] {
+        Variable::Nil => Ok(Variable::Bool(true)),
+        other => Ok(Variable::Bool(string_arg("blank?", other)?.trim().is_empty()))
+    }
+}
+
+//one %[flags][width][.precision]conversion of a format string
+struct FormatSpec {
+    left:bool,
+    zero:bool,
+    plus:bool,
+    width:usize,
+    precision:Option<usize>,
+    conv:char
+}
+
+//width and precision are capped so a format string can't ask for unbounded padding
+const MAX_FORMAT_COUNT:usize = 10000;
+
+fn parse_count(chars:&mut std::iter::Peekable<std::str::Chars>,what:&str) -> Result<usize,EvalError> {
+    let mut count:usize = 0;
+    while let Some(digit) = chars.peek().and_then(|chr| chr.to_digit(10)) {
+        count = count.checked_mul(10).and_then(|n| n.checked_add(digit as usize)).filter(|n| *n <= MAX_FORMAT_COUNT)
+                     .ok_or_else(|| EvalError::NativeError(format!("format: {} larger than {}",what,MAX_FORMAT_COUNT)))?;
+        chars.next();
+    }
+    Ok(count)
+}
+
+fn parse_spec(chars:&mut std::iter::Peekable<std::str::Chars>) -> Result<FormatSpec,EvalError> {
+    let mut spec = FormatSpec { left:false, zero:false, plus:false, width:0, precision:None, conv:'%' };
+    while let Some(flag) = chars.peek() {
+        match flag {
+            '-' => spec.left = true,
+            '0' => spec.zero = true,
+            '+' => spec.plus = true,
+            _ => break
+        }
+        chars.next();
+    }
+    spec.width = parse_count(chars, "width")?;
+    if chars.peek() == Some(&'.') {
+        chars.next();
+        spec.precision = Some(parse_count(chars, "precision")?);
+    }
+    spec.conv = chars.next().ok_or_else(|| EvalError::NativeError(String::from("format: incomplete format specifier at end")))?;
+    if !"dfxXs%n".contains(spec.conv) {
+        return Err(EvalError::NativeError(format!("format: unknown conversion %{}",spec.conv)));
+    }
+    Ok(spec)
+}
+
+impl FormatSpec {
+    fn render(&self,var:&Variable) -> Result<String,EvalError> {
+        let mismatch = |expected:&str| EvalError::TypeMismatch(expected.to_string(),var.type_name().to_string());
+        let (negative,digits) = match self.conv {
+            'd' => {
+                let n = var.cast_int().ok_or_else(|| mismatch("int"))?;
+                (n < 0,n.unsigned_abs().to_string())
+            },
+            'f' => {
+                let n = var.cast_float().ok_or_else(|| mismatch("number"))?;
+                (n < 0f64,format!("{:.*}",self.precision.unwrap_or(6),n.abs()))
+            },
+            'x' => (false,format!("{:x}",var.cast_int().ok_or_else(|| mismatch("int"))?)),
+            'X' => (false,format!("{:X}",var.cast_int().ok_or_else(|| mismatch("int"))?)),
+            's' => {
+                let s = str_of(var);
+                let s = match self.precision {
+                    Some(precision) => s.chars().take(precision).collect(),
+                    None => s
+                };
+                return Ok(self.pad(String::new(), s, false));
+            },
+            _ => unreachable!()
+        };
+        let sign = if negative { "-" } else if self.plus { "+" } else { "" };
+        Ok(self.pad(sign.to_string(), digits, self.zero))
+    }
+
+    //zeros go between the sign and the digits, spaces outside both
+    fn pad(&self,sign:String,body:String,zero:bool) -> String {
+        let len = sign.chars().count() + body.chars().count();
+        if len >= self.width {
+            return sign + &body;
+        }
+        let fill = self.width - len;
+        if self.left {
+            format!("{}{}{}",sign,body," ".repeat(fill))
+        } else if zero {
+            format!("{}{}{}",sign,"0".repeat(fill),body)
+        } else {
+            format!("{}{}{}"," ".repeat(fill),sign,body)
+        }
+    }
+}
+
+//(format fmt args ..) supports %d %s %f %x %X %% %n with -, 0 and + flags, width and precision
+pub fn format(scope:&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError> {
+    check_args("format", &args, 1, None)?;
+    let fmt = string_arg("format", &args[0])?;
+    let mut ret = String::new();
+    let mut arg_idx = 1;
+    let mut chars = fmt.chars().peekable();
+    while let Some(chr) = chars.next() {
+        if chr != '%' {
+            ret.push(chr);
+            continue;
+        }
+        let spec = parse_spec(&mut chars)?;
+        match spec.conv {
+            '%' => ret.push('%'),
+            'n' => ret.push('\n'),
+            conv => {
+                let arg = args.get(arg_idx).ok_or_else(|| EvalError::NativeError(format!("format: missing argument for %{}",conv)))?;
+                realize_all(scope, arg)?;
+                let text = spec.render(arg).map_err(|err| EvalError::ArgError(String::from("format"),arg_idx,Box::new(err)))?;
+                ret.push_str(&text);
+                arg_idx += 1;
+            }
+        }
+    }
+    Ok(new_string(ret))
+}
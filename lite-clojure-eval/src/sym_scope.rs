@@ -90,6 +90,14 @@ impl SymbolScope {
     pub fn pop_let(&mut self) {
         self.lets.pop();
     }
+
+    pub fn let_len(&self) -> usize {
+        self.lets.len()
+    }
+
+    pub fn truncate_lets(&mut self,len:usize) {
+        self.lets.truncate(len);
+    }
 }
 
 #[derive(Default,Debug)]
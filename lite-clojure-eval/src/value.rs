@@ -1,38 +1,50 @@
-use std::collections::HashMap;
+use std::convert::TryFrom;
+
 use serde_json::{Map, Number, Value};
-use crate::Variable;
+use crate::{EvalError, PersistentHashMap, Variable};
 
+//lazy seqs must be realized first, see EvalRT::realize
+//map keys must be scalars, keyword keys drop the ':'
+impl TryFrom<Variable> for Value {
+    type Error = EvalError;
 
-impl Into<Value> for  Variable {
-    fn into(self) -> Value {
-        match self {
+    fn try_from(var:Variable) -> Result<Value,EvalError> {
+        Ok(match var {
             Variable::Int(i) => Value::Number(i.into()),
-            Variable::Float(f) => Value::Number(Number::from_f64(f).unwrap()),
+            Variable::Float(f) => Value::Number(Number::from_f64(f).ok_or_else(|| EvalError::TypeMismatch("finite float".to_string(),f.to_string()))?),
             Variable::Bool(b) => Value::Bool(b),
             Variable::String(ref s) => Value::String(s.borrow().clone()),
             Variable::Keyword(ref s) => Value::String(s.borrow().clone()),
-            Variable::Array(ref arr) => {
-                Value::Array(arr.borrow().iter().map(|v| v.clone().into()).collect())
+            Variable::Char(chr) => Value::String(chr.to_string()),
+            Variable::Array(ref arr) | Variable::List(ref arr) => {
+                Value::Array(arr.borrow().iter().map(|v| Value::try_from(v.clone())).collect::<Result<_,_>>()?)
+            },
+            Variable::LazySeq(ref lazy) => match lazy.realized_items() {
+                (items,false) => Value::Array(items.into_iter().map(Value::try_from).collect::<Result<_,_>>()?),
+                (_,true) => return Err(EvalError::Unrealized)
+            },
+            Variable::Set(ref set) => {
+                Value::Array(set.borrow().iter().map(|v| Value::try_from(v.clone())).collect::<Result<_,_>>()?)
             },
             Variable::Map(ref map) => {
-                let map_ref:&HashMap<Variable,Variable> = &map.borrow();
+                let map_ref:&PersistentHashMap = &map.borrow();
                 let mut value_map = Map::new();
                 for (k,v) in map_ref {
                     let k_str = match k {
                        Variable::String(s) => s.borrow().clone(),
-                       Variable::Keyword(s) => s.borrow().clone(),
+                       Variable::Keyword(s) => s.borrow().trim_start_matches(':').to_string(),
                        Variable::Int(s) => s.to_string(),
                        Variable::Float(f) => f.to_string(),
                        Variable::Bool(b) => b.to_string(),
-                       _ => panic!("err key type"),
+                       other => return Err(EvalError::TypeMismatch("scalar map key".to_string(),other.type_name().to_string())),
                     };
-                    let val:Value = v.clone().into();
+                    let val = Value::try_from(v.clone())?;
                     value_map.insert(k_str, val);
                 }
                 Value::Object(value_map)
             }
             
             _ => Value::Null
-        }
+        })
     }
 }
\ No newline at end of file
@@ -1,8 +1,8 @@
-use std::{borrow::Borrow, collections::{HashMap}, fmt::{Debug, Formatter}, rc::Rc, usize};
+use std::{any::{Any, TypeId}, borrow::Borrow, cell::{Ref, RefCell, RefMut}, collections::{HashMap, HashSet, hash_map::DefaultHasher}, fmt::{Debug, Formatter}, hash::{Hash, Hasher}, rc::Rc, usize};
 use gc::{Gc,GcCell,Finalize,Trace,GcCellRef,GcCellRefMut };
-use lite_clojure_parser::expr::Expr;
+use lite_clojure_parser::expr::FnArity;
 
-use crate::{exec_context::ExecContext, module::EvalModules};
+use crate::{exec_context::ExecContext, module::EvalModules, EvalError, LazySeq, PersistentHashMap, PersistentVector};
 
 #[derive(Debug,Clone,Finalize,Trace)]
 pub struct  GcRefCell<T:Trace + Finalize + 'static>(Gc<GcCell<T>>);
@@ -33,12 +33,16 @@ pub enum Variable {
     String(GcRefCell<String>),
     Keyword(GcRefCell<String>),
     Function(Gc<Function>),
-    Array(GcRefCell<Vec<Variable>>),
-    Map(GcRefCell<HashMap<Variable,Variable>>),
+    Array(GcRefCell<PersistentVector>),
+    List(GcRefCell<PersistentVector>),
+    Map(GcRefCell<PersistentHashMap>),
+    Set(GcRefCell<HashSet<Variable>>),
     Var(String),
     Char(char),
+    ExInfo(Gc<ExInfo>),
+    LazySeq(Gc<LazySeq>),
     
-    UserData(#[unsafe_ignore_trace] *mut u8),
+    UserData(#[unsafe_ignore_trace] Rc<UserData>),
     Nil,
 }
 
@@ -80,7 +84,7 @@ impl From<&[Variable]> for Variable {
 
 impl From<Vec<Variable>> for Variable {
     fn from(src: Vec<Variable>) -> Variable {
-        Variable::Array(GcRefCell::new(src))
+        Variable::Array(GcRefCell::new(src.into()))
     }
 }
 
@@ -96,7 +100,13 @@ impl From<&[(Variable, Variable)]> for Variable {
 
 impl From<HashMap<Variable, Variable>> for Variable {
     fn from(src: HashMap<Variable, Variable>) -> Variable {
-        Variable::Map(GcRefCell::new(src))
+        Variable::Map(GcRefCell::new(src.into()))
+    }
+}
+
+impl From<HashSet<Variable>> for Variable {
+    fn from(src: HashSet<Variable>) -> Variable {
+        Variable::Set(GcRefCell::new(src))
     }
 }
 
@@ -117,17 +127,30 @@ impl PartialEq for Variable {
                 let str2:&String = &v1.borrow();
                 str1 == str2
             },
-            (Variable::Array(arr),Variable::Array(other_arr)) => {
-                let arr_ref:&Vec<Variable> = &arr.borrow();
-                let other_ref:&Vec<Variable> = &other_arr.borrow();
-                if arr_ref.len() != other_ref.len() {return  false;  }
-                for idx in 0..arr_ref.len() {
-                    if arr_ref[idx] != arr_ref[idx] {
-                        return false;
-                    }
-                }
-                true
+            (Variable::Symbol(v0),Variable::Symbol(v1)) => v0.var_name == v1.var_name,
+            (Variable::Array(arr),Variable::Array(other_arr)) |
+            (Variable::Array(arr),Variable::List(other_arr)) |
+            (Variable::List(arr),Variable::Array(other_arr)) |
+            (Variable::List(arr),Variable::List(other_arr)) => {
+                let arr_ref:&PersistentVector = &arr.borrow();
+                let other_ref:&PersistentVector = &other_arr.borrow();
+                arr_ref == other_ref
             },
+            (Variable::Map(map),Variable::Map(other_map)) => {
+                let map_ref:&PersistentHashMap = &map.borrow();
+                let other_ref:&PersistentHashMap = &other_map.borrow();
+                map_ref == other_ref
+            },
+            (Variable::Set(set),Variable::Set(other_set)) => {
+                let set_ref:&HashSet<Variable> = &set.borrow();
+                let other_ref:&HashSet<Variable> = &other_set.borrow();
+                set_ref == other_ref
+            },
+            (Variable::ExInfo(e0),Variable::ExInfo(e1)) => Gc::ptr_eq(e0, e1),
+            (Variable::LazySeq(l0),Variable::LazySeq(l1)) if Gc::ptr_eq(l0, l1) => true,
+            (Variable::LazySeq(lazy),other) | (other,Variable::LazySeq(lazy)) => lazy.eq_realized(other),
+            (Variable::UserData(u0),Variable::UserData(u1)) => Rc::ptr_eq(u0, u1),
+            (Variable::Nil,Variable::Nil) => true,
 
             _ => false
         }
@@ -142,15 +165,47 @@ impl std::hash::Hash for Variable {
             Variable::Int(v) => v.hash(state),
             Variable::Bool(v) => v.hash(state),
             Variable::Char(v) => v.hash(state),
-            Variable::Nil => Variable::Nil.hash(state),
+            Variable::Nil => 0u8.hash(state),
             Variable::String(s) => s.borrow().hash(state),
             Variable::Keyword(s) => s.borrow().hash(state),
             Variable::Symbol(s) => s.borrow().var_name.hash(state),
+            //0.0 and -0.0 are equal
+            Variable::Float(v) => (if *v == 0f64 { 0f64 } else { *v }).to_bits().hash(state),
+            //vectors, lists and realized lazy seqs with the same items are equal
+            Variable::Array(arr) | Variable::List(arr) => hash_items(arr.borrow().iter(), state),
+            Variable::LazySeq(lazy) => hash_items(lazy.realized_items().0.iter(), state),
+            Variable::Map(map) => hash_unordered(map.borrow().iter(), state),
+            Variable::Set(set) => hash_unordered(set.borrow().iter(), state),
+            Variable::ExInfo(ex) => (&**ex as *const ExInfo).hash(state),
+            Variable::UserData(data) => Rc::as_ptr(data).hash(state),
             _ => 0.hash(state)
         }
     }
 }
 
+fn hash_items<'a,H:Hasher>(items:impl Iterator<Item = &'a Variable>,state:&mut H) {
+    let mut len = 0usize;
+    for item in items {
+        item.hash(state);
+        len += 1;
+    }
+    len.hash(state);
+}
+
+//maps and sets iterate in an order that depends on how they were built, so entry hashes are summed
+fn hash_unordered<T:Hash,H:Hasher>(items:impl Iterator<Item = T>,state:&mut H) {
+    let mut len = 0usize;
+    let mut sum = 0u64;
+    for item in items {
+        let mut hasher = DefaultHasher::new();
+        item.hash(&mut hasher);
+        sum = sum.wrapping_add(hasher.finish());
+        len += 1;
+    }
+    len.hash(state);
+    sum.hash(state);
+}
+
 impl Variable {
     pub fn show_str(&self) -> String {
         match self {
@@ -166,7 +221,7 @@ impl Variable {
             Variable::Var(s) =>format!("#'{}",s),
             Variable::Map(maps) => {
                 let mut kv_string = String::from("{");
-                let map:&HashMap<Variable,Variable> =  &maps.borrow();
+                let map:&PersistentHashMap = &maps.borrow();
                 for (k,v) in map {
                     kv_string.push_str(k.show_str().as_str());
                     kv_string.push(' ');
@@ -188,7 +243,47 @@ impl Variable {
                 };
                 format!("[{}]",lst_string)
             },
-            Variable::UserData(_) =>  String::from("userdata"),
+            Variable::List(lst) => {
+                let items:Vec<String> = lst.borrow().iter().map(|v| v.show_str()).collect();
+                format!("({})",items.join(" "))
+            },
+            Variable::Set(set) => {
+                let items:Vec<String> = set.borrow().iter().map(|v| v.show_str()).collect();
+                format!("#{{{}}}",items.join(" "))
+            },
+            Variable::ExInfo(ex) => format!("#error {{:message \"{}\" :data {}}}",ex.message,ex.data.show_str()),
+            Variable::LazySeq(lazy) => {
+                let (items,is_pending) = lazy.realized_items();
+                let mut items:Vec<String> = items.iter().map(|v| v.show_str()).collect();
+                if is_pending { items.push(String::from("...")); }
+                format!("({})",items.join(" "))
+            },
+            Variable::UserData(data) => match data.type_name() {
+                Some(name) => format!("#<{}>",name),
+                None => String::from("userdata")
+            },
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Variable::Int(_) => "int",
+            Variable::Float(_) => "float",
+            Variable::Bool(_) => "bool",
+            Variable::Symbol(_) => "symbol",
+            Variable::String(_) => "string",
+            Variable::Keyword(_) => "keyword",
+            Variable::Function(_) => "function",
+            Variable::Array(_) => "vector",
+            Variable::List(_) => "list",
+            Variable::Map(_) => "map",
+            Variable::Set(_) => "set",
+            Variable::Var(_) => "var",
+            Variable::Char(_) => "char",
+            Variable::ExInfo(_) => "ex-info",
+            Variable::LazySeq(_) => "lazy-seq",
+            Variable::UserData(_) => "userdata",
+            Variable::Nil => "nil"
         }
     }
 
@@ -221,6 +316,10 @@ impl Variable {
         }
     }
 
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self,Variable::Nil | Variable::Bool(false))
+    }
+
     pub fn cast_bool(&self) -> Option<bool> {
         match self {
             Variable::Bool(n) => Some(*n),
@@ -228,20 +327,34 @@ impl Variable {
         }
     }
 
-    pub fn cast_vec(&self) -> Option<GcRefCell<Vec<Variable>>> {
+    pub fn cast_vec(&self) -> Option<GcRefCell<PersistentVector>> {
         match self {
             Variable::Array(arr) => Some(arr.clone()),
             _ => None
         }
     }
 
-    pub fn cast_map(&self) -> Option<GcRefCell<HashMap<Variable,Variable>>> {
+    pub fn cast_list(&self) -> Option<GcRefCell<PersistentVector>> {
+        match self {
+            Variable::List(lst) => Some(lst.clone()),
+            _ => None
+        }
+    }
+
+    pub fn cast_map(&self) -> Option<GcRefCell<PersistentHashMap>> {
         match self {
             Variable::Map(m) => Some(m.clone()),
             _ => None
         }
     }
 
+    pub fn cast_set(&self) -> Option<GcRefCell<HashSet<Variable>>> {
+        match self {
+            Variable::Set(s) => Some(s.clone()),
+            _ => None
+        }
+    }
+
     pub fn cast_var(&self) -> Option<String> {
         match self {
             Variable::Var(s) => Some(s.clone()),
@@ -249,9 +362,16 @@ impl Variable {
         }
     }
 
-    pub fn cast_userdata(&self) -> Option<*mut u8> {
+    pub fn cast_userdata(&self) -> Option<Rc<UserData>> {
         match self {
-            Variable::UserData(s) => Some(*s),
+            Variable::UserData(s) => Some(s.clone()),
+            _ => None
+        }
+    }
+
+    pub fn cast_exinfo(&self) -> Option<Gc<ExInfo>> {
+        match self {
+            Variable::ExInfo(ex) => Some(ex.clone()),
             _ => None
         }
     }
@@ -265,6 +385,13 @@ impl Variable {
 }
 
 
+#[derive(Debug,Finalize,Trace)]
+pub struct ExInfo {
+    pub message:String,
+    pub data:Variable,
+    pub cause:Variable
+}
+
 #[derive(Debug,Clone,Finalize,Trace)]
 pub struct Symbol {
     pub var_name:String,
@@ -296,17 +423,20 @@ pub struct ExecScope<'a> {
     pub modules:&'a mut EvalModules
 }
 
+pub type NativeClosureFn = dyn Fn(&mut ExecScope,Vec<Variable>) -> Result<Variable,EvalError>;
+
 #[derive(Finalize,Trace)]
 pub enum Function {
-    NativeFn(#[unsafe_ignore_trace] fn(&mut ExecScope,args:Vec<Variable>) -> Variable),
+    NativeFn(#[unsafe_ignore_trace] fn(&mut ExecScope,args:Vec<Variable>) -> Result<Variable,EvalError>),
+    NativeClosure(#[unsafe_ignore_trace] Rc<NativeClosureFn>),
     ClosureFn(ClosureData)
 }
 
 #[derive(Debug,Finalize,Trace)]
 pub struct ClosureData {
-    pub args:Vec<Symbol>,
+    pub name:Option<String>,
     #[unsafe_ignore_trace]
-    pub body:Vec<Expr>,
+    pub arities:Vec<FnArity>,
     pub cap_vars:Option<HashMap<String,Symbol>>
 }
 
@@ -317,10 +447,77 @@ impl Debug for Function {
 }
 
 impl<'a> ExecScope<'a> {
-    pub fn find_userdata<T>(&self,name:&str) -> Option<&mut T> {
-        let user_var = self.context.find_symbol(None, name, &self.modules)?;
-        let ptr = user_var.cast_userdata()?;
-        let value_ptr = unsafe { &mut *(ptr as *mut T) };
-        Some(value_ptr)
+    pub fn find_userdata(&self,name:&str) -> Option<Rc<UserData>> {
+        let user_var = self.context.find_symbol(None, name, self.modules)?;
+        user_var.cast_userdata()
+    }
+}
+
+type UserDataFinalizer = dyn Fn(&mut dyn Any);
+
+//host value handed to scripts, only reachable again through a checked downcast
+pub struct UserData {
+    type_name:Option<String>,
+    type_id:TypeId,
+    value:RefCell<Box<dyn Any>>,
+    finalizer:Option<Box<UserDataFinalizer>>
+}
+
+impl UserData {
+    pub fn new<T:Any>(value:T) -> UserData {
+        UserData { type_name:None, type_id:TypeId::of::<T>(), value:RefCell::new(Box::new(value)), finalizer:None }
+    }
+
+    pub fn with_type_name(mut self,name:&str) -> UserData {
+        self.type_name = Some(name.to_string());
+        self
+    }
+
+    //runs when the last script or host reference is dropped
+    pub fn with_finalizer<T:Any>(mut self,f:impl Fn(&mut T) + 'static) -> UserData {
+        self.finalizer = Some(Box::new(move |value:&mut dyn Any| {
+            if let Some(value) = value.downcast_mut::<T>() {
+                f(value);
+            }
+        }));
+        self
+    }
+
+    pub fn type_name(&self) -> Option<&str> {
+        self.type_name.as_deref()
+    }
+
+    pub fn is<T:Any>(&self) -> bool {
+        self.type_id == TypeId::of::<T>()
+    }
+
+    //None when the type differs or the value is already borrowed mutably
+    pub fn downcast_ref<T:Any>(&self) -> Option<Ref<'_,T>> {
+        Ref::filter_map(self.value.try_borrow().ok()?, |v| v.downcast_ref::<T>()).ok()
+    }
+
+    //None when the type differs or the value is already borrowed
+    pub fn downcast_mut<T:Any>(&self) -> Option<RefMut<'_,T>> {
+        RefMut::filter_map(self.value.try_borrow_mut().ok()?, |v| v.downcast_mut::<T>()).ok()
+    }
+}
+
+impl Drop for UserData {
+    fn drop(&mut self) {
+        if let Some(finalizer) = self.finalizer.take() {
+            finalizer(self.value.get_mut().as_mut());
+        }
+    }
+}
+
+impl Debug for UserData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f,"UserData({})",self.type_name().unwrap_or("?"))
+    }
+}
+
+impl From<UserData> for Variable {
+    fn from(src: UserData) -> Variable {
+        Variable::UserData(Rc::new(src))
     }
 }
\ No newline at end of file
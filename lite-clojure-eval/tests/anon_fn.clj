@@ -0,0 +1,11 @@
+(def add #(+ % %2))
+(def call-with #(%1 10))
+(def rest-args #(concat [%] %&))
+
+[
+ (add 1 2)
+ (call-with #(* % 3))
+ (rest-args 1 2 3)
+ (rest-args 1)
+ ((fn [a & more] more) 1)
+]
@@ -0,0 +1,8 @@
+[
+ \A
+ (= \u0041 \o101)
+ (int \a)
+ (char 955)
+ "caf\u00e9"
+ (char (+ (int \a) 1))
+]
@@ -0,0 +1,19 @@
+(defn classify [n]
+  (cond
+    (< n 0) :neg
+    (= n 0) :zero
+    :else :pos))
+
+(defn kind [x]
+  (case x
+    1 :one
+    (2 3) :few
+    foo :sym
+    "s" :str
+    :other))
+
+(defn pick [n]
+  (condp = n
+    1 "a"
+    2 "b"
+    "z"))
@@ -0,0 +1,19 @@
+(def pos [1 2 3 4])
+(def cfg {:hp 80 "name" "slime" :pos [3 4]})
+
+(defn describe [{:keys [hp mp] :or {mp 10} :strs [name] [x y] :pos}]
+  [name hp mp x y])
+
+(defn sum-pairs [acc [a b] & [c]]
+  (+ acc a b (if (nil? c) 0 c)))
+
+[
+ (let [[a b & more :as all] pos] [a b more all])
+ (describe cfg)
+ (sum-pairs 1 [2 3])
+ (sum-pairs 1 [2 3] 4)
+ (loop [[x & xs] pos acc 0]
+   (if (nil? x) acc (recur xs (+ acc x))))
+ (let [[a [b c]] [1 nil]] [a b c])
+ (#(let [{k :k} %] k) {:k 5})
+]
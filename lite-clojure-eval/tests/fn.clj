@@ -0,0 +1,23 @@
+(defn area
+  "area of a rect or a square"
+  ([w] (area w w))
+  ([w h] (* w h)))
+
+(defn sum [& nums]
+  (loop [lst nums acc 0 idx 0]
+    (if (nil? (get (vec lst) idx))
+      acc
+      (recur lst (+ acc (nth lst idx)) (+ idx 1)))))
+
+(def fact (fn self [n] (if (<= n 1) 1 (* n (self (- n 1))))))
+
+(def head-tail (fn ([] nil) ([a & more] [a more])))
+
+[
+ (area 3)
+ (area 2 5)
+ (sum 1 2 3 4)
+ (fact 5)
+ (head-tail 1 2 3)
+ (head-tail)
+]
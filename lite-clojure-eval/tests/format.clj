@@ -0,0 +1 @@
+(defn err [f] (try (f) (catch Exception e (ex-message e))))
@@ -0,0 +1,6 @@
+(defn nat [n] (lazy-seq (cons n (nat (inc n)))))
+(def seen (transient []))
+(def squares (map (fn [x] (conj! seen x) (* x x)) (range)))
+(def doubled (transient []))
+(def powers (iterate (fn [x] (conj! doubled x) (* x 2)) 1))
+(defn nested [n] (lazy-seq (if (> n 0) (nested (dec n)) [n])))
@@ -1,6 +1,3 @@
-(defn inc [n] (+ n 1))
-(defn dec [n] (- n 1))
-
 (defn test-loop [num]
   (println num)
   (if (> num 0)
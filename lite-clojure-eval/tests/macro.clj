@@ -0,0 +1,16 @@
+(defmacro unless [test then else]
+  `(if ~test ~else ~then))
+
+(defmacro swap-args [form]
+  (list (nth form 0) (nth form 2) (nth form 1)))
+
+(defmacro def-pair [name a b]
+  `(def ~name [~a ~b]))
+
+(def-pair pair 1 2)
+
+[
+ (unless (< 1 2) :then :else)
+ (swap-args (- 1 10))
+ pair
+]
@@ -0,0 +1,15 @@
+(defn div [a b] (/ a b))
+
+(defn err-type [f]
+  (try (f) (catch Exception e (get (ex-data e) :type))))
+
+[
+ (err-type (fn [] (div 1 0)))
+ (err-type (fn [] (nth [1 2] 5)))
+ (err-type (fn [] (+ 1 "a")))
+ (err-type (fn [] (-)))
+ (try (< 1 :a) (catch ArgTypeError e (ex-message e)))
+ (- 5)
+ (< 1 2 3)
+ (/ 1.0 0)
+]
@@ -0,0 +1,7 @@
+(def min-int (- -9223372036854775807 1))
+(defn err [f] (try (f) (catch ArithmeticException e (ex-message e))))
+
+(set-rand-seed! 7)
+(def rolls [(rand-int 6) (rand-int 6) (rand-int 6) (rand)])
+(set-rand-seed! 7)
+(def replay [(rand-int 6) (rand-int 6) (rand-int 6) (rand)])
@@ -0,0 +1,16 @@
+(def base {:hp 10 :pos [0 0] :tags #{:a}})
+(def moved (assoc-in base [:pos 1] 5))
+(def healed (update base :hp + 5))
+(def items [1 2 3])
+(def more (conj items 4))
+(def t (transient items))
+(conj! t 9)
+(def snapshot (persistent! t))
+(conj! t 10)
+(def m (transient base))
+(assoc! m :hp 0)
+(def plain [1 2])
+(def plain-ret (conj! plain 3))
+(def private (transient plain))
+(conj! private 4)
+(def big (loop [v [] i 0] (if (< i 100) (recur (conj v i) (+ i 1)) v)))
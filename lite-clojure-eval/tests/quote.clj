@@ -0,0 +1,9 @@
+(def b 2)
+(def c [3 4])
+
+[
+ '(a b c)
+ `(+ ~b ~@c)
+ `[x ~b]
+ (let [l `(a# a#)] (= (nth l 0) (nth l 1)))
+]
@@ -0,0 +1,2 @@
+(def users [{:name "ann" :age 31} {:name "bob" :age 17} {:name "cid" :age 45}])
+(defn adult? [u] (>= (get u :age) 18))
@@ -0,0 +1,14 @@
+(def tags #{:fire :water})
+(def x 1)
+
+[
+ (contains? tags :fire)
+ (contains? (disj tags :fire) :fire)
+ (conj tags :earth)
+ (union tags #{:air})
+ (intersection tags #{:water :air})
+ (difference tags #{:water})
+ (tags :water)
+ (= #{x 2} `#{~x 2})
+ (= {:a 1} {:a 1})
+]
@@ -0,0 +1 @@
+(require "clojure.string")
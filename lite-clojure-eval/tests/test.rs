@@ -1,7 +1,16 @@
-use lite_clojure_eval::{EvalRT, Variable};
+use std::convert::TryFrom;
+
+use lite_clojure_eval::{EvalError, EvalRT, GcRefCell, Variable, from_variable};
+
+//evaluates one case after the fixture file, a failing case shows as [:error message]
+fn show(rt:&mut EvalRT,code:&str) -> String {
+    let code = format!("(try {} (catch Exception e [:error (ex-message e)]))",code);
+    rt.eval_string(String::from("test"), &code).unwrap().show_str()
+}
+
 #[test]
 fn test_loop() {
-    env_logger::try_init().unwrap();
+    let _ = env_logger::try_init();
     let mut rt = EvalRT::new();
     rt.init();
     rt.eval_file("tests/loop.clj");  
@@ -45,19 +54,845 @@ fn test_invoke2() {
 
 #[test]
 fn test_require() {
-    env_logger::try_init().unwrap();
+    let _ = env_logger::try_init();
     let mut rt = EvalRT::new();
     rt.add_search_path("tests/");
     rt.init();
     rt.eval_file("tests/main.clj");
+    //a qualified name only looks in its own module
+    assert_eq!(show(&mut rt, "(string.tools/log-str \"a\")"), "nil");
+    assert_eq!(show(&mut rt, "(string.tools/map inc [1])"), r#"[:error "not found symbol:string.tools/map"]"#);
+    assert_eq!(show(&mut rt, "(clojure.string/map inc [1])"), r#"[:error "not found symbol:clojure.string/map"]"#);
 }
 
 
 #[test]
 fn test_record() {
-    env_logger::try_init().unwrap();
+    let _ = env_logger::try_init();
     let mut rt = EvalRT::new();
     rt.add_search_path("tests/");
     rt.init();
     rt.eval_file("tests/record.clj");
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_quote() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    let var = rt.eval_file("tests/quote.clj").unwrap();
+    let arr = var.cast_vec().unwrap();
+    let arr_ref = arr.borrow();
+    assert_eq!(arr_ref[0].show_str(), "(a b c)");
+    assert_eq!(arr_ref[1].show_str(), "(clojure.core/+ 2 3 4)");
+    assert_eq!(arr_ref[2].show_str(), "[tests.quote/x 2]");
+    assert!(arr_ref[3] == Variable::Bool(true));
+}
+
+#[test]
+fn test_macro() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    let var = rt.eval_file("tests/macro.clj").unwrap();
+    let arr = var.cast_vec().unwrap();
+    let arr_ref = arr.borrow();
+    assert_eq!(arr_ref[0].show_str(), ":else");
+    assert!(arr_ref[1] == Variable::Int(9));
+    assert_eq!(arr_ref[2].show_str(), "[1 2]");
+}
+
+#[test]
+fn test_macro_registry() {
+    use lite_clojure_eval::parser::cexpr::CExpr;
+    let mut rt = EvalRT::new();
+    rt.init();
+    rt.macros().add_macro("twice", Box::new(|args,span| {
+        let add = CExpr::symbol(None, "+", span);
+        Ok(CExpr::list(vec![add,args[0].clone(),args[0].clone()], span))
+    }));
+    let ret = rt.eval_string(String::default(), "(twice 21)").unwrap();
+    assert!(ret == Variable::Int(42));
+    //expanders only see the forms, not the comments between them
+    let ret = rt.eval_string(String::default(), "(twice ;; the answer\n 21)").unwrap();
+    assert!(ret == Variable::Int(42));
+
+    let mut other_rt = EvalRT::new();
+    other_rt.init();
+    other_rt.eval_string(String::default(), "(defn twice [a] a)");
+    let ret = other_rt.eval_string(String::default(), "(twice 21)").unwrap();
+    assert!(ret == Variable::Int(21));
+}
+
+#[test]
+fn test_anon_fn() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    let var = rt.eval_file("tests/anon_fn.clj").unwrap();
+    let arr = var.cast_vec().unwrap();
+    let arr_ref = arr.borrow();
+    assert!(arr_ref[0] == Variable::Int(3));
+    assert!(arr_ref[1] == Variable::Int(30));
+    assert_eq!(arr_ref[2].show_str(), "[1 2 3]");
+    assert_eq!(arr_ref[3].show_str(), "[1]");
+    assert!(arr_ref[4] == Variable::Nil);
+}
+
+#[test]
+fn test_set() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    let var = rt.eval_file("tests/set.clj").unwrap();
+    let arr = var.cast_vec().unwrap();
+    let arr_ref = arr.borrow();
+    let set_of = |names:&[&str]| -> Variable {
+        let set:std::collections::HashSet<Variable> = names.iter().map(|s| Variable::Keyword(GcRefCell::new(s.to_string()))).collect();
+        set.into()
+    };
+    assert!(arr_ref[0] == Variable::Bool(true));
+    assert!(arr_ref[1] == Variable::Bool(false));
+    assert!(arr_ref[2] == set_of(&[":fire",":water",":earth"]));
+    assert!(arr_ref[3] == set_of(&[":fire",":water",":air"]));
+    assert!(arr_ref[4] == set_of(&[":water"]));
+    assert_eq!(arr_ref[5].show_str(), "#{:fire}");
+    assert_eq!(arr_ref[6].show_str(), ":water");
+    assert!(arr_ref[7] == Variable::Bool(true));
+    assert!(arr_ref[8] == Variable::Bool(true));
+    let json = serde_json::Value::try_from(arr_ref[5].clone()).unwrap();
+    assert_eq!(json.to_string(), "[\":fire\"]");
+    //a literal that repeats an element is a read error, not a smaller set
+    assert!(rt.eval_string(String::from("dup"), "#{1 1}").is_none());
+}
+
+#[test]
+fn test_char() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    let var = rt.eval_file("tests/char.clj").unwrap();
+    let arr = var.cast_vec().unwrap();
+    let arr_ref = arr.borrow();
+    assert!(arr_ref[0] == Variable::Char('A'));
+    assert!(arr_ref[1] == Variable::Bool(true));
+    assert!(arr_ref[2] == Variable::Int(97));
+    assert!(arr_ref[3] == Variable::Char('λ'));
+    assert!(arr_ref[4] == Variable::from("café"));
+    assert!(arr_ref[5] == Variable::Char('b'));
+}
+
+#[test]
+fn test_fn_arity() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    let var = rt.eval_file("tests/fn.clj").unwrap();
+    let arr = var.cast_vec().unwrap();
+    let arr_ref = arr.borrow();
+    assert!(arr_ref[0] == Variable::Int(9));
+    assert!(arr_ref[1] == Variable::Int(10));
+    assert!(arr_ref[2] == Variable::Int(10));
+    assert!(arr_ref[3] == Variable::Int(120));
+    assert_eq!(arr_ref[4].show_str(), "[1 (2 3)]");
+    assert!(arr_ref[5] == Variable::Nil);
+    assert!(rt.invoke_func("area", vec![]).is_err());
+}
+
+#[test]
+fn test_destructure() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    let var = rt.eval_file("tests/destructure.clj").unwrap();
+    let arr = var.cast_vec().unwrap();
+    let arr_ref = arr.borrow();
+    assert_eq!(arr_ref[0].show_str(), "[1 2 (3 4) [1 2 3 4]]");
+    assert_eq!(arr_ref[1].show_str(), "[\"slime\" 80 10 3 4]");
+    assert!(arr_ref[2] == Variable::Int(6));
+    assert!(arr_ref[3] == Variable::Int(10));
+    assert!(arr_ref[4] == Variable::Int(10));
+    assert_eq!(arr_ref[5].show_str(), "[1 nil nil]");
+    assert!(arr_ref[6] == Variable::Int(5));
+}
+
+#[test]
+fn test_core_macros() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    rt.eval_file("tests/core_macros.clj");
+    assert_eq!(show(&mut rt, "[(classify -1) (classify 0) (classify 5)]"), "[:neg :zero :pos]");
+    assert_eq!(show(&mut rt, "[(kind 1) (kind 3) (kind 'foo) (kind \"s\") (kind 9)]"), "[:one :few :sym :str :other]");
+    assert_eq!(show(&mut rt, "[(pick 1) (pick 2) (pick 3)]"), r#"["a" "b" "z"]"#);
+    assert_eq!(show(&mut rt, "(when (= 1 1) 1 2)"), "2");
+    assert_eq!(show(&mut rt, "(when (= 1 2) 1)"), "nil");
+    assert_eq!(show(&mut rt, "(when-not (= 1 2) 3)"), "3");
+    assert_eq!(show(&mut rt, "(if-not (= 1 2) :a :b)"), ":a");
+    assert_eq!(show(&mut rt, "(if-let [x (= 1 1)] [x] :no)"), "[true]");
+    assert_eq!(show(&mut rt, "(when-let [x (= 1 2)] :yes)"), "nil");
+    assert_eq!(show(&mut rt, "(-> 10 (- 3) (* 2))"), "14");
+    assert_eq!(show(&mut rt, "(->> 10 (- 3) (* 2))"), "-14");
+    assert_eq!(show(&mut rt, "(as-> 1 v (+ v 1) [v v])"), "[2 2]");
+    assert_eq!(show(&mut rt, "(some-> {:a {:b 5}} (get :a) (get :b) (+ 1))"), "6");
+    assert_eq!(show(&mut rt, "(some-> {:a nil} (get :a) (get :b))"), "nil");
+    assert_eq!(show(&mut rt, "(doto [1 2] (nth 0))"), "[1 2]");
+    assert_eq!(show(&mut rt, "(case 5 1 :a)"), r#"[:error "No matching clause: 5"]"#);
+
+    let mut rt = EvalRT::new();
+    rt.init();
+    rt.eval_string(String::from("test"), "(defmacro when [c x] 42)").unwrap();
+    assert_eq!(show(&mut rt, "(when true 1)"), "42");
+    assert_eq!(show(&mut rt, "(clojure.core/when true 1)"), "1");
+    assert_eq!(show(&mut rt, "(when-not false 1)"), "1");
+}
+
+#[test]
+fn test_truthy() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    let var = rt.eval_file("tests/truthy.clj").unwrap();
+    let arr = var.cast_vec().unwrap();
+    let arr_ref = arr.borrow();
+    let show:Vec<String> = arr_ref.iter().map(|v| v.show_str()).collect();
+    assert_eq!(show[0..6].join(" "), "false false true true true true");
+    assert!(arr_ref[6] == Variable::Bool(true));
+    assert!(arr_ref[7] == Variable::Int(3));
+    assert!(arr_ref[8] == Variable::Nil);
+    assert!(arr_ref[9] == Variable::Bool(false));
+    assert!(arr_ref[10] == Variable::Nil);
+    assert!(arr_ref[11] == Variable::Bool(false));
+    assert!(arr_ref[12] == Variable::Int(2));
+    assert!(arr_ref[13] == Variable::Bool(true));
+    assert!(arr_ref[14] == Variable::Bool(false));
+    assert_eq!(show[15], ":map");
+    assert!(arr_ref[16] == Variable::Int(2));
+    assert_eq!(show[17], ":none");
+    assert!(arr_ref[18] == Variable::Int(2));
+}
+
+#[test]
+fn test_try() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    let var = rt.eval_file("tests/try.clj").unwrap();
+    let arr = var.cast_vec().unwrap();
+    let arr_ref = arr.borrow();
+    let show:Vec<String> = arr_ref.iter().map(|v| v.show_str()).collect();
+    assert!(arr_ref[0] == Variable::Int(5));
+    assert_eq!(show[1], "[\"divide by zero\" {:a 1 }]");
+    assert!(arr_ref[2] == Variable::Int(0));
+    assert!(arr_ref[3] == Variable::Int(11));
+    assert_eq!(show[4], ":NotFoundSymbol");
+    assert_eq!(show[5], "[:other 42]");
+    assert_eq!(show[6], "[1 \"inner\" [:finally :inner]]");
+    assert_eq!(show[7], "#error {:message \"root\" :data {}}");
+    assert_eq!(show[8], "[1 nil]");
+    assert_eq!(show[9], "[1 nil]");
+    assert!(arr_ref[10] == Variable::Nil);
+}
+
+#[test]
+fn test_native_error() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    let var = rt.eval_file("tests/native_error.clj").unwrap();
+    let arr = var.cast_vec().unwrap();
+    let arr_ref = arr.borrow();
+    let show:Vec<String> = arr_ref.iter().map(|v| v.show_str()).collect();
+    assert_eq!(show[0..4].join(" "), ":DivideByZero :IndexOutOfBounds :ArgTypeError :ArityError");
+    assert_eq!(show[4], "\"< does not accept keyword\"");
+    assert!(arr_ref[5] == Variable::Int(-5));
+    assert!(arr_ref[6] == Variable::Bool(true));
+    assert_eq!(show[7], "inf");
+
+    let err = rt.invoke_func("div", vec![Variable::Int(1),Variable::Int(0)]).unwrap_err();
+    assert!(matches!(err, EvalError::DivideByZero));
+    let err = rt.invoke_func("div", vec![Variable::Int(1)]).unwrap_err();
+    assert!(matches!(err, EvalError::FunctionArgCountError));
+    assert!(rt.invoke_func("div", vec![Variable::Int(6),Variable::Int(3)]).unwrap() == Variable::Int(2));
+
+    //a failed last form gives None instead of the value left by an earlier call
+    assert_eq!(rt.eval_string(String::from("test"), "(/ 1.0 0)").unwrap().show_str(), "inf");
+    assert!(rt.eval_string(String::from("test"), "(div 1 0)").is_none());
+    assert!(rt.eval_string(String::from("test"), "(def x 7) (div 1 0)").is_none());
+    assert!(rt.eval_string(String::from("test"), "(def y (+ x 1))").unwrap() == Variable::Int(8));
+    assert_eq!(rt.eval_string(String::from("test"), "[x y]").unwrap().show_str(), "[7 8]");
+}
+
+#[test]
+fn test_native_closure() {
+    use std::{cell::Cell, rc::Rc};
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    let counter = Rc::new(Cell::new(0i64));
+    let state = counter.clone();
+    rt.global_context().push_native_closure("tick!", move |_,args| {
+        let step = args.first().and_then(|v| v.cast_int()).unwrap_or(1);
+        state.set(state.get() + step);
+        Ok(Variable::Int(state.get()))
+    });
+    let var = rt.eval_string("test".to_string(), "(tick!) (tick! 10) [(tick!) (apply tick! [5])]").unwrap();
+    assert_eq!(var.show_str(), "[12 17]");
+    assert_eq!(counter.get(), 17);
+}
+
+#[test]
+fn test_userdata() {
+    use std::{cell::Cell, rc::Rc};
+    use lite_clojure_eval::UserData;
+    struct Entity { id:u32, hp:i64 }
+
+    let _ = env_logger::try_init();
+    let dropped = Rc::new(Cell::new(false));
+    {
+        let mut rt = EvalRT::new();
+        rt.init();
+        let flag = dropped.clone();
+        let player = UserData::new(Entity { id: 7, hp: 100 }).with_type_name("Entity")
+                                  .with_finalizer(move |e:&mut Entity| flag.set(e.id == 7));
+        rt.global_context().push_var("player", player);
+        rt.global_context().push_native_closure("damage!", |_,args| {
+            let data = args[0].cast_userdata().ok_or(EvalError::TypeCastError)?;
+            let mut entity = data.downcast_mut::<Entity>().ok_or(EvalError::TypeCastError)?;
+            entity.hp -= args[1].cast_int().unwrap_or(0);
+            Ok(Variable::Int(entity.hp))
+        });
+        let var = rt.eval_string("test".to_string(), "(damage! player 30)").unwrap();
+        assert!(var == Variable::Int(70));
+        let player_var = rt.global_context().find_local_symbol("player").unwrap();
+        assert_eq!(player_var.show_str(), "#<Entity>");
+        let data = player_var.cast_userdata().unwrap();
+        assert!(data.is::<Entity>());
+        assert!(data.downcast_ref::<String>().is_none());
+        assert_eq!(data.downcast_ref::<Entity>().unwrap().hp, 70);
+        //a second borrow while one is held gives None instead of panicking
+        let held = data.downcast_mut::<Entity>().unwrap();
+        assert!(data.is::<Entity>());
+        assert!(data.downcast_ref::<Entity>().is_none());
+        assert!(data.downcast_mut::<Entity>().is_none());
+        drop(held);
+        assert_eq!(rt.eval_string("test".to_string(), "(try (damage! player 5) (catch Exception e :busy))").unwrap().show_str(), "65");
+        let held = data.downcast_ref::<Entity>().unwrap();
+        assert!(data.downcast_ref::<Entity>().is_some());
+        assert_eq!(rt.eval_string("test".to_string(), "(try (damage! player 5) (catch Exception e :busy))").unwrap().show_str(), ":busy");
+        drop(held);
+    }
+    assert!(dropped.get());
+}
+
+#[test]
+fn test_register_fn() {
+    use std::collections::HashMap;
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    rt.register_fn("mix", |a:i64,b:f64,s:String| -> anyhow::Result<String> { Ok(format!("{}{}{}",s,a,b)) });
+    rt.register_fn("safe-div", |a:i64,b:i64| -> anyhow::Result<f64> {
+        if b == 0 { anyhow::bail!("divide by zero") }
+        Ok(a as f64 / b as f64)
+    });
+    rt.register_fn("sum-all", |lst:Vec<i64>,extra:Option<i64>| -> Result<i64,EvalError> { Ok(lst.iter().sum::<i64>() + extra.unwrap_or(0)) });
+    rt.register_fn("count-keys", |m:HashMap<String,Variable>| -> Result<Vec<String>,EvalError> {
+        let mut keys:Vec<String> = m.into_iter().map(|(k,_)| k).collect();
+        keys.sort();
+        Ok(keys)
+    });
+    assert_eq!(show(&mut rt, r#"(mix 1 2.5 "x")"#), r#""x12.5""#);
+    assert_eq!(show(&mut rt, "(safe-div 3 2)"), "1.5");
+    assert_eq!(show(&mut rt, "(sum-all [1 2 3] nil)"), "6");
+    assert_eq!(show(&mut rt, "(sum-all '(1 2) 10)"), "13");
+    assert_eq!(show(&mut rt, r#"(count-keys {"b" 1 "a" 2})"#), r#"["a" "b"]"#);
+    assert_eq!(show(&mut rt, "(mix 1 2)"), r#"[:error "wrong number of args (2) passed to mix"]"#);
+    assert_eq!(show(&mut rt, "(mix 1 2.0 :k)"), r#"[:error "mix arg 2: expected string, found keyword"]"#);
+    assert_eq!(show(&mut rt, r#"(sum-all [1 "2"] nil)"#), r#"[:error "sum-all arg 0: [1]: expected int, found string"]"#);
+    assert_eq!(show(&mut rt, "(safe-div 1 0)"), r#"[:error "native func safe-div error:divide by zero"]"#);
+
+    //trailing Option args may be left out, earlier ones may not
+    rt.register_fn("pad", |a:Option<i64>,b:i64,c:Option<String>,d:Option<bool>| -> Result<String,EvalError> { Ok(format!("{:?} {} {:?} {:?}",a,b,c,d)) });
+    assert_eq!(show(&mut rt, "(sum-all [1 2])"), "3");
+    assert_eq!(show(&mut rt, "(sum-all)"), r#"[:error "wrong number of args (0) passed to sum-all"]"#);
+    assert_eq!(show(&mut rt, "(sum-all [1] 2 3)"), r#"[:error "wrong number of args (3) passed to sum-all"]"#);
+    assert_eq!(show(&mut rt, "(pad nil 1)"), r#""None 1 None None""#);
+    assert_eq!(show(&mut rt, r#"(pad 0 1 "c")"#), r#""Some(0) 1 Some("c") None""#);
+    assert_eq!(show(&mut rt, "(pad 1)"), r#"[:error "wrong number of args (1) passed to pad"]"#);
+}
+
+#[test]
+fn test_derive() {
+    use lite_clojure_eval::{FromVariable, IntoVariable};
+    #[derive(Debug, PartialEq, FromVariable, IntoVariable)]
+    struct Item {
+        name:String,
+        #[variable(rename = "max-hp")]
+        hp:i64,
+        #[variable(default)]
+        count:i64,
+        tags:Option<Vec<String>>
+    }
+    #[derive(Debug, PartialEq, FromVariable, IntoVariable)]
+    struct Pos(i64,i64);
+    #[derive(Debug, PartialEq, FromVariable, IntoVariable)]
+    #[variable(tag = "kind")]
+    enum Shape {
+        Empty,
+        #[variable(rename = "circle")]
+        Circle { r:f64 },
+        Rect(Pos,Pos)
+    }
+    #[derive(Debug, PartialEq, FromVariable, IntoVariable)]
+    struct Bag<T> {
+        items:Vec<T>,
+        #[variable(default = "default_owner")]
+        owner:String,
+        shape:Shape
+    }
+    fn default_owner() -> String { "nobody".to_string() }
+
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    rt.register_fn("heal", |mut item:Item| -> Result<Item,EvalError> { item.hp += 10; Ok(item) });
+    rt.register_fn("try-bag", |var:Variable| -> Result<String,EvalError> {
+        Ok(match Bag::<Item>::from_variable(&var) {
+            Ok(bag) => format!("{}:{}", bag.owner, bag.items.len()),
+            Err(err) => err.to_string()
+        })
+    });
+    let var = rt.eval_string("test".to_string(), r#"
+      [(heal {:name "sword" :max-hp 5})
+       (try-bag {:items [{:name "a" :max-hp 1}] :shape :Empty})
+       (try-bag {:items [{:name "a" :max-hp 1} {:name "b" :max-hp "x"}] :shape :Empty})
+       (try-bag {:items [{:max-hp 1}] :shape :Empty})
+       (try-bag {:items [] :shape {:kind :circle :r "big"}})
+       (try-bag {:items [] :shape :Hexagon})
+       (try-bag {:items [] :shape {:kind :Rect :value [[0 0] [1]]}})]"#).unwrap();
+    let arr = var.cast_vec().unwrap();
+    let show:Vec<String> = arr.borrow().iter().map(|v| v.show_str()).collect();
+    let healed = Item::from_variable(&arr.borrow()[0]).unwrap();
+    assert_eq!(healed, Item { name:"sword".to_string(), hp:15, count:0, tags:None });
+    assert_eq!(show[1], "\"nobody:1\"");
+    assert_eq!(show[2], "\"items[1].max-hp: expected int, found string\"");
+    assert_eq!(show[3], "\"items[0].name: missing field\"");
+    assert_eq!(show[4], "\"shape.r: expected float, found string\"");
+    assert_eq!(show[5], "\"shape: unknown variant Hexagon\"");
+    assert_eq!(show[6], "\"shape.value[1]: expected vector of 2, found vector\"");
+
+    let bag = Bag { items:vec![Pos(1,2)], owner:"me".to_string(), shape:Shape::Rect(Pos(0,0),Pos(3,4)) };
+    let var = bag.into_variable();
+    assert_eq!(Bag::<Pos>::from_variable(&var).unwrap().shape, Shape::Rect(Pos(0,0),Pos(3,4)));
+    let circle = Shape::Circle { r:1.5 }.into_variable();
+    assert_eq!(Shape::from_variable(&circle).unwrap(), Shape::Circle { r:1.5 });
+    assert!(circle.show_str().contains(":kind :circle"));
+    assert_eq!(Shape::Empty.into_variable().show_str(), ":Empty");
+}
+
+//derived code must not pick up local items named like prelude ones
+mod derive_hygiene {
+    #![allow(dead_code, unused_macros)]
+    use lite_clojure_eval::{FromVariable, IntoVariable};
+    type Result<T> = T;
+    type Option<T> = T;
+    type Vec = ();
+    struct Ok;
+    struct Err;
+    struct Some;
+    struct None;
+    macro_rules! vec { () => { () }; }
+
+    #[derive(Debug, PartialEq, FromVariable, IntoVariable)]
+    struct Point { x:i64, y: ::std::option::Option<i64> }
+    #[derive(Debug, PartialEq, FromVariable, IntoVariable)]
+    struct Pair(i64,i64);
+    #[derive(Debug, PartialEq, FromVariable, IntoVariable)]
+    enum Step { Stop, Move(Pair), Jump { to:Point } }
+
+    #[test]
+    fn test_derive_hygiene() {
+        let step = Step::Jump { to:Point { x:1, y: ::std::option::Option::None } };
+        assert_eq!(Step::from_variable(&step.into_variable()).unwrap(), Step::Jump { to:Point { x:1, y: ::std::option::Option::None } });
+        let step = Step::Move(Pair(1,2));
+        assert_eq!(Step::from_variable(&step.into_variable()).unwrap(), Step::Move(Pair(1,2)));
+    }
+}
+
+#[test]
+fn test_serde_de() {
+    use std::collections::{BTreeMap, HashMap};
+    use lite_clojure_eval::from_variable;
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    let mut eval = |code:&str| rt.eval_string("test".to_string(), code).unwrap();
+
+    let cfg:HashMap<String,(String,Vec<f64>,Option<bool>)> = from_variable(eval(r#"{:stone ["rock" [1 2.5] nil] "wood" [:tree #{3} true]}"#)).unwrap();
+    assert_eq!(cfg["stone"], ("rock".to_string(),vec![1.0,2.5],None));
+    assert_eq!(cfg["wood"], ("tree".to_string(),vec![3.0],Some(true)));
+
+    let json:serde_json::Value = from_variable(eval(r#"{:name "a" :tags [:x 'y \z] :size {:w 1 :h 2.5}}"#)).unwrap();
+    assert_eq!(json, serde_json::json!({"name":"a","tags":["x","y","z"],"size":{"w":1,"h":2.5}}));
+
+    let res:Vec<Result<i64,String>> = from_variable(eval(r#"[{:type :Ok :value 1} {:type :Err :value "bad"}]"#)).unwrap();
+    assert_eq!(res, vec![Ok(1),Err("bad".to_string())]);
+
+    let err = from_variable::<BTreeMap<String,Vec<i64>>>(eval(r#"{:a [1 2] :b [3 "4"]}"#)).unwrap_err();
+    assert_eq!(err.to_string(), "b[1]: expected i64, found string");
+    assert_eq!(err.name(), "TypeMismatch");
+    let err = from_variable::<Vec<Result<i64,String>>>(eval(r#"[{:type :Ok :value 1} {:type :Ok :value "x"}]"#)).unwrap_err();
+    assert_eq!(err.to_string(), "[1].value: expected i64, found string");
+    let err = from_variable::<Result<i64,String>>(eval(r#"{:type :Maybe :value 1}"#)).unwrap_err();
+    assert_eq!(err.to_string(), "unknown variant Maybe");
+    let err = from_variable::<Result<i64,String>>(eval(r#"{:Ok 1}"#)).unwrap_err();
+    assert_eq!(err.to_string(), "type: missing field");
+    let err = from_variable::<i64>(eval("(fn [] 1)")).unwrap_err();
+    assert_eq!(err.to_string(), "expected data, found function");
+
+    //the plain json bridge errors instead of panicking
+    let json = serde_json::Value::try_from(eval(r#"{:a 1 "b" [2]}"#)).unwrap();
+    assert_eq!(json, serde_json::json!({"a":1,"b":[2]}));
+    let err = serde_json::Value::try_from(eval("{[1 2] :pair}")).unwrap_err();
+    assert_eq!(err.to_string(), "expected scalar map key, found vector");
+    let err = serde_json::Value::try_from(Variable::Float(f64::NAN)).unwrap_err();
+    assert_eq!(err.to_string(), "expected finite float, found NaN");
+}
+
+#[test]
+fn test_serde_ser() {
+    use std::collections::HashMap;
+    use serde::ser::{Serialize, SerializeStruct, Serializer};
+    use lite_clojure_eval::{from_variable, to_variable};
+    enum Mode { Fast, Slow(i64) }
+    impl Serialize for Mode {
+        fn serialize<S:Serializer>(&self,s:S) -> Result<S::Ok,S::Error> {
+            match self {
+                Mode::Fast => s.serialize_unit_variant("Mode",0,"Fast"),
+                Mode::Slow(n) => s.serialize_newtype_variant("Mode",1,"Slow",n)
+            }
+        }
+    }
+    struct Unit { name:String, hp:u32, pos:(f32,f32), modes:Vec<Mode> }
+    impl Serialize for Unit {
+        fn serialize<S:Serializer>(&self,s:S) -> Result<S::Ok,S::Error> {
+            let mut st = s.serialize_struct("Unit",4)?;
+            st.serialize_field("name",&self.name)?;
+            st.serialize_field("hp",&self.hp)?;
+            st.serialize_field("pos",&self.pos)?;
+            st.serialize_field("modes",&self.modes)?;
+            st.end()
+        }
+    }
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    rt.eval_string("test".to_string(), r#"
+      (defn describe [u] [(get u :name) (+ (get u :hp) 1) (nth (get u :pos) 1) (nth (get u :modes) 0) (get (nth (get u :modes) 1) :type) (get (nth (get u :modes) 1) :value)])
+      (defn lookup [m k] (get m k))"#);
+    let unit = Unit { name:"archer".to_string(), hp:10, pos:(1.0,2.5), modes:vec![Mode::Fast,Mode::Slow(3)] };
+    let var = rt.invoke_func("describe", vec![to_variable(&unit).unwrap()]).unwrap();
+    assert_eq!(var.show_str(), "[\"archer\" 11 2.5 :Fast :Slow 3]");
+
+    let mut table:HashMap<String,Option<Vec<i64>>> = HashMap::new();
+    table.insert("a".to_string(), Some(vec![1,2]));
+    table.insert("b".to_string(), None);
+    let var = to_variable(&table).unwrap();
+    assert_eq!(rt.invoke_func("lookup", vec![var.clone(),Variable::from("a")]).unwrap().show_str(), "[1 2]");
+    assert_eq!(from_variable::<HashMap<String,Option<Vec<i64>>>>(var).unwrap(), table);
+
+    let json = serde_json::json!({"k":[true,null,1.5,"s"]});
+    let var = to_variable(&json).unwrap();
+    assert_eq!(var.show_str(), "{\"k\" [true nil 1.5 \"s\"] }");
+    assert_eq!(from_variable::<serde_json::Value>(var).unwrap(), json);
+
+    let err = to_variable(&vec![vec![1u64],vec![2,u64::MAX]]).unwrap_err();
+    assert_eq!(err.to_string(), format!("[1][1]: {} out of int range",u64::MAX));
+}
+
+#[test]
+fn test_serde_derive_enum() {
+    use serde::{Deserialize, Serialize};
+    use lite_clojure_eval::{from_variable, to_variable, FromVariable, IntoVariable};
+    //serde and the derive read each other's enums
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromVariable, IntoVariable)]
+    enum Shape { Empty, Circle { r:f64 }, Rect(i64,i64), Named(String) }
+
+    let shapes = vec![Shape::Empty, Shape::Circle { r:1.5 }, Shape::Rect(2,3), Shape::Named("a".to_string())];
+    for shape in shapes {
+        let derived = shape.clone().into_variable();
+        let serialized = to_variable(&shape).unwrap();
+        assert!(derived == serialized, "{:?}", shape);
+        assert_eq!(from_variable::<Shape>(derived).unwrap(), shape);
+        assert_eq!(Shape::from_variable(&serialized).unwrap(), shape);
+    }
+}
+
+#[test]
+fn test_persistent() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    rt.eval_file("tests/persistent.clj");
+    assert_eq!(show(&mut rt, "(= base {:hp 10 :pos [0 0] :tags #{:a}})"), "true");
+    assert_eq!(show(&mut rt, "(nth (get moved :pos) 1)"), "5");
+    assert_eq!(show(&mut rt, "(get healed :hp)"), "15");
+    assert_eq!(show(&mut rt, "[items more snapshot t]"), "[[1 2 3] [1 2 3 4] [1 2 3 9] [1 2 3 9 10]]");
+    assert_eq!(show(&mut rt, "(= (dissoc {:a 1 :b 2} :a :c) {:b 2})"), "true");
+    assert_eq!(show(&mut rt, "(assoc [1 2] 2 3 0 :x)"), "[:x 2 3]");
+    assert_eq!(show(&mut rt, "(= (update-in {:a {:b 1}} [:a :b] + 10 1) {:a {:b 12}})"), "true");
+    assert_eq!(show(&mut rt, "(= (assoc-in nil [:x :y] 1) {:x {:y 1}})"), "true");
+    assert_eq!(show(&mut rt, "[(get m :hp) (get base :hp)]"), "[0 10]");
+    assert_eq!(show(&mut rt, "(try (assoc [1] 5 0) (catch IndexOutOfBounds e :oob))"), ":oob");
+    assert_eq!(show(&mut rt, "[(nth big 99) (nth (assoc big 50 :x) 50) (nth big 50)]"), "[99 :x 50]");
+    assert_eq!(show(&mut rt, "(update {:n nil} :n (fn [x] (if x x :default)))"), "{:n :default }");
+    //the ! fns change any collection in place and return it, transient copies first
+    assert_eq!(show(&mut rt, "[plain plain-ret private]"), "[[1 2 3] [1 2 3] [1 2 3 4]]");
+    assert_eq!(show(&mut rt, "(assoc! {:a 1} :a 2)"), "{:a 2 }");
+    assert_eq!(show(&mut rt, "(dissoc! {:a 1 :b 2} :a)"), "{:b 2 }");
+    assert_eq!(show(&mut rt, "(conj! [1] :x)"), "[1 :x]");
+    assert_eq!(show(&mut rt, "(conj! 1 2)"), r#"[:error "conj! does not accept int"]"#);
+    //collections hash by their contents
+    assert_eq!(show(&mut rt, "(get {[1 2] :v} (list 1 2))"), ":v");
+    assert_eq!(show(&mut rt, "(contains? #{{:a 1 :b 2}} {:b 2 :a 1})"), "true");
+    assert_eq!(show(&mut rt, "(count (set (map (fn [i] [i]) (range 100))))"), "100");
+    assert_eq!(show(&mut rt, "(get {#{1 2} :s} #{2 1})"), ":s");
+}
+
+#[test]
+fn test_seq() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    rt.eval_file("tests/seq.clj");
+    assert_eq!(show(&mut rt, "(first [1 2])"), "1");
+    assert_eq!(show(&mut rt, "(first nil)"), "nil");
+    assert_eq!(show(&mut rt, r#"(first "ab")"#), "'a'");
+    assert_eq!(show(&mut rt, "(rest [1 2 3])"), "(2 3)");
+    assert_eq!(show(&mut rt, "(rest nil)"), "()");
+    assert_eq!(show(&mut rt, "(cons 0 [1 2])"), "(0 1 2)");
+    assert_eq!(show(&mut rt, "(count [1 2])"), "2");
+    assert_eq!(show(&mut rt, "(count {:a 1})"), "1");
+    assert_eq!(show(&mut rt, r#"(count "héllo")"#), "5");
+    assert_eq!(show(&mut rt, "(count nil)"), "0");
+    assert_eq!(show(&mut rt, "(seq [])"), "nil");
+    assert_eq!(show(&mut rt, r#"(seq "ab")"#), "('a' 'b')");
+    assert_eq!(show(&mut rt, "(map inc [1 2 3])"), "(2 3 4)");
+    assert_eq!(show(&mut rt, "(map + [1 2 3] [10 20])"), "(11 22)");
+    assert_eq!(show(&mut rt, "(map (fn [u] (get u :name)) (filter adult? users))"), r#"("ann" "cid")"#);
+    assert_eq!(show(&mut rt, "(map (fn [u] (get u :name)) (remove adult? users))"), r#"("bob")"#);
+    assert_eq!(show(&mut rt, "(reduce + [1 2 3 4])"), "10");
+    assert_eq!(show(&mut rt, "(reduce + 10 [1 2])"), "13");
+    assert_eq!(show(&mut rt, "(reduce + [])"), "0");
+    assert_eq!(show(&mut rt, "(reduce (fn [acc kv] (+ acc (nth kv 1))) 0 {:a 1 :b 2})"), "3");
+    assert_eq!(show(&mut rt, "(doall (range 4))"), "(0 1 2 3)");
+    assert_eq!(show(&mut rt, "(doall (range 2 5))"), "(2 3 4)");
+    assert_eq!(show(&mut rt, "(doall (range 10 0 -3))"), "(10 7 4 1)");
+    assert_eq!(show(&mut rt, "(doall (range 0))"), "()");
+    //a step past the i64 limits ends the range
+    assert_eq!(show(&mut rt, "(doall (range 9223372036854775800 9223372036854775807 5))"), "(9223372036854775800 9223372036854775805)");
+    assert_eq!(show(&mut rt, "(doall (range -9223372036854775800 -9223372036854775807 -5))"), "(-9223372036854775800 -9223372036854775805)");
+    assert_eq!(show(&mut rt, "(take 2 [1 2 3])"), "(1 2)");
+    assert_eq!(show(&mut rt, "(drop 2 [1 2 3])"), "(3)");
+    assert_eq!(show(&mut rt, "(take 5 (range 3))"), "(0 1 2)");
+    //bounded ranges are lazy too, only what is walked gets realized
+    assert_eq!(show(&mut rt, "(take 5 (range 1000000000000))"), "(0 1 2 3 4)");
+    assert_eq!(show(&mut rt, "(range 3)"), "(...)");
+    assert_eq!(show(&mut rt, "(count (range 0 100 3))"), "34");
+    assert_eq!(show(&mut rt, "(nth (range 1000000000000) 5)"), "5");
+    assert_eq!(show(&mut rt, "[(vec (range 3)) (apply + (range 4)) (conj (range 2) 9) (concat (range 2) [5])]"), "[[0 1 2] 6 (9 0 1) [0 1 5]]");
+    assert_eq!(show(&mut rt, "(contains? (set (range 3)) 2)"), "true");
+    assert_eq!(show(&mut rt, "(into [] (list 1 2))"), "[1 2]");
+    assert_eq!(show(&mut rt, "(= (into #{} [1 1 2]) #{1 2})"), "true");
+    assert_eq!(show(&mut rt, "(= (into {} [[:a 1] [:b 2]]) {:a 1 :b 2})"), "true");
+    assert_eq!(show(&mut rt, "(some (fn [x] (if (> x 2) (* x 10) nil)) [1 2 3 4])"), "30");
+    assert_eq!(show(&mut rt, "(some adult? [])"), "nil");
+    assert_eq!(show(&mut rt, "(every? adult? users)"), "false");
+    assert_eq!(show(&mut rt, "(every? adult? [])"), "true");
+    assert_eq!(show(&mut rt, "(= (set (keys {:a 1 :b 2})) #{:a :b})"), "true");
+    assert_eq!(show(&mut rt, "(reduce + (vals {:a 1 :b 2}))"), "3");
+    assert_eq!(show(&mut rt, "(keys {})"), "nil");
+    assert_eq!(show(&mut rt, "(= (merge {:a 1 :b 2} nil {:b 3}) {:a 1 :b 3})"), "true");
+    assert_eq!(show(&mut rt, "(merge nil nil)"), "nil");
+    assert_eq!(show(&mut rt, "(= (select-keys {:a 1 :b 2 :c 3} [:a :c :d]) {:a 1 :c 3})"), "true");
+    assert_eq!(show(&mut rt, r#"(= (frequencies "abca") {\a 2 \b 1 \c 1})"#), "true");
+    assert_eq!(show(&mut rt, "(inc 1.5)"), "2.5");
+    assert_eq!(show(&mut rt, "(dec 0)"), "-1");
+    assert_eq!(show(&mut rt, "(try (map 1 [1]) (catch Exception e :not-fn))"), ":not-fn");
+}
+
+#[test]
+fn test_lazy() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    rt.eval_file("tests/lazy.clj");
+    assert_eq!(show(&mut rt, "(take 5 (range))"), "(0 1 2 3 4)");
+    assert_eq!(show(&mut rt, "(take 3 (nat 10))"), "(10 11 12)");
+    assert_eq!(show(&mut rt, "(take 4 (iterate (fn [x] (* x 2)) 1))"), "(1 2 4 8)");
+    assert_eq!(show(&mut rt, "(take 3 (repeat :a))"), "(:a :a :a)");
+    assert_eq!(show(&mut rt, r#"(doall (repeat 2 "x"))"#), r#"("x" "x")"#);
+    assert_eq!(show(&mut rt, "(take 5 (cycle [1 2]))"), "(1 2 1 2 1)");
+    assert_eq!(show(&mut rt, "(cycle [])"), "()");
+    //map realizes a chunk of 32 at a time
+    assert_eq!(show(&mut rt, "[(take 3 squares) (count seen)]"), "[(0 1 4) 32]");
+    //iterate realizes one item at a time
+    assert_eq!(show(&mut rt, "[(take 3 powers) (count doubled)]"), "[(1 2 4) 2]");
+    assert_eq!(show(&mut rt, "(take 3 (filter (fn [x] (> x 40)) (range)))"), "(41 42 43)");
+    assert_eq!(show(&mut rt, "(take 2 (drop 100 (range)))"), "(100 101)");
+    assert_eq!(show(&mut rt, "(seq (drop 3 (repeat 3 1)))"), "nil");
+    assert_eq!(show(&mut rt, "(first (range))"), "0");
+    assert_eq!(show(&mut rt, "(first (rest (nat 0)))"), "1");
+    assert_eq!(show(&mut rt, "(first (remove (fn [x] (< x 5)) (nat 0)))"), "5");
+    assert_eq!(show(&mut rt, "(take 4 (map + [1 2 3] (range)))"), "(1 3 5)");
+    assert_eq!(show(&mut rt, "(count (repeat 40 1))"), "40");
+    assert_eq!(show(&mut rt, "(reduce + (take 10 (range)))"), "45");
+    assert_eq!(show(&mut rt, "(some (fn [x] (if (> x 3) x nil)) (range))"), "4");
+    assert_eq!(show(&mut rt, "(every? (fn [x] (< x 3)) (range))"), "false");
+    assert_eq!(show(&mut rt, "(= (take 3 (range)) (list 0 1 2))"), "true");
+    assert_eq!(show(&mut rt, "(= (map inc (repeat 2 1)) [2 2])"), "true");
+    //= stops at the first difference, even against an infinite seq
+    assert_eq!(show(&mut rt, "(= [1 2] (range))"), "false");
+    assert_eq!(show(&mut rt, "(= (range) [0 1])"), "false");
+    assert_eq!(show(&mut rt, "(= [(map inc [1])] [(list 2)])"), "true");
+    assert_eq!(show(&mut rt, "(= {:a (map inc [1])} {:a [2]})"), "true");
+    assert_eq!(show(&mut rt, "(range)"), "(...)");
+    //deep self recursive seqs are walked without growing the stack
+    assert_eq!(show(&mut rt, "(reduce + (take 100000 (nat 0)))"), "4999950000");
+    assert_eq!(show(&mut rt, "(first (nested 100000))"), "0");
+
+    let lazy = rt.eval_string(String::from("lazy"), "(map inc (repeat 3 1))").unwrap();
+    assert!(matches!(serde_json::Value::try_from(lazy.clone()),Err(EvalError::Unrealized)));
+    assert!(matches!(from_variable::<Vec<i64>>(lazy.clone()),Err(EvalError::Unrealized)));
+    rt.realize(&lazy).unwrap();
+    let json = serde_json::Value::try_from(lazy).unwrap();
+    assert_eq!(json.to_string(), "[2,2,2]");
+}
+
+#[test]
+fn test_clojure_string() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    rt.eval_file("tests/strings.clj");
+    assert_eq!(show(&mut rt, "(clojure.string/str)"), r#""""#);
+    assert_eq!(show(&mut rt, r#"(clojure.string/str "a" 1 \b nil :k [1 "x"])"#), r#""a1b:k[1 "x"]""#);
+    assert_eq!(show(&mut rt, "(clojure.string/str (take 2 (range)))"), r#""(0 1)""#);
+    assert_eq!(show(&mut rt, r#"(clojure.string/subs "héllo" 1 3)"#), r#""él""#);
+    assert_eq!(show(&mut rt, r#"(clojure.string/subs "日本語" 1)"#), r#""本語""#);
+    assert_eq!(show(&mut rt, r#"(try (clojure.string/subs "ab" 1 5) (catch Exception e :out))"#), ":out");
+    assert_eq!(show(&mut rt, r#"(clojure.string/split "a,b,,c,," ",")"#), r#"["a" "b" "" "c"]"#);
+    assert_eq!(show(&mut rt, r#"(clojure.string/split "a b c" " " 2)"#), r#"["a" "b c"]"#);
+    assert_eq!(show(&mut rt, r#"(clojure.string/split "ab" "")"#), r#"["a" "b"]"#);
+    assert_eq!(show(&mut rt, "(clojure.string/join [1 2 3])"), r#""123""#);
+    assert_eq!(show(&mut rt, r#"(clojure.string/join ", " (list "a" \b :c))"#), r#""a, b, :c""#);
+    assert_eq!(show(&mut rt, r#"(clojure.string/join "-" (repeat 2 "x"))"#), r#""x-x""#);
+    assert_eq!(show(&mut rt, r#"(clojure.string/trim "  hi \n")"#), r#""hi""#);
+    assert_eq!(show(&mut rt, r#"(clojure.string/upper-case "straße")"#), r#""STRASSE""#);
+    assert_eq!(show(&mut rt, r#"(clojure.string/lower-case "ÀB")"#), r#""àb""#);
+    assert_eq!(show(&mut rt, r#"(clojure.string/replace "a.b.c" "." "/")"#), r#""a/b/c""#);
+    assert_eq!(show(&mut rt, r#"(clojure.string/replace "aXa" \a \o)"#), r#""oXo""#);
+    assert_eq!(show(&mut rt, r#"(clojure.string/starts-with? "héllo" "hé")"#), "true");
+    assert_eq!(show(&mut rt, r#"(clojure.string/ends-with? "héllo" \o)"#), "true");
+    assert_eq!(show(&mut rt, r#"(clojure.string/includes? "héllo" "ll")"#), "true");
+    assert_eq!(show(&mut rt, r#"(clojure.string/index-of "日本語本" "本")"#), "1");
+    assert_eq!(show(&mut rt, r#"(clojure.string/index-of "日本語本" \本 2)"#), "3");
+    assert_eq!(show(&mut rt, r#"(clojure.string/index-of "abc" "z")"#), "nil");
+    assert_eq!(show(&mut rt, r#"(clojure.string/index-of "abc" "a" 9)"#), "nil");
+    assert_eq!(show(&mut rt, "(clojure.string/blank? nil)"), "true");
+    assert_eq!(show(&mut rt, r#"(clojure.string/blank? " \t")"#), "true");
+    assert_eq!(show(&mut rt, r#"(clojure.string/blank? " a ")"#), "false");
+    assert_eq!(show(&mut rt, "(try (clojure.string/upper-case 1) (catch Exception e :type))"), ":type");
+}
+
+#[test]
+fn test_format() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    rt.eval_file("tests/format.clj");
+    assert_eq!(show(&mut rt, r#"(format "%d items" 3)"#), r#""3 items""#);
+    assert_eq!(show(&mut rt, r#"(format "%5d|%-5d|%05d" 42 42 -42)"#), r#""   42|42   |-0042""#);
+    assert_eq!(show(&mut rt, r#"(format "%+d" 7)"#), r#""+7""#);
+    assert_eq!(show(&mut rt, r#"(format "%.2f" 3.14159)"#), r#""3.14""#);
+    assert_eq!(show(&mut rt, r#"(format "%8.3f|" 2)"#), r#""   2.000|""#);
+    assert_eq!(show(&mut rt, r#"(format "%f" 1.5)"#), r#""1.500000""#);
+    assert_eq!(show(&mut rt, r#"(format "%07.1f" -2.25)"#), r#""-0002.2""#);
+    assert_eq!(show(&mut rt, r#"(format "%x %X %04x" 255 255 10)"#), r#""ff FF 000a""#);
+    assert_eq!(show(&mut rt, r#"(format "%s and %s" "a" :b)"#), r#""a and :b""#);
+    assert_eq!(show(&mut rt, r#"(format "%-6s|%6s|" "ab" nil)"#), r#""ab    |      |""#);
+    assert_eq!(show(&mut rt, r#"(format "%.2s" "héllo")"#), r#""hé""#);
+    assert_eq!(show(&mut rt, r#"(format "100%%")"#), r#""100%""#);
+    assert_eq!(show(&mut rt, r#"(format "%s" [1 "x"])"#), r#""[1 "x"]""#);
+    assert_eq!(show(&mut rt, r#"(format "%s" (take 2 (range)))"#), r#""(0 1)""#);
+    assert_eq!(show(&mut rt, "(str)"), r#""""#);
+    assert_eq!(show(&mut rt, r#"(str "a" 1 \b nil :k)"#), r#""a1b:k""#);
+    assert_eq!(show(&mut rt, r#"(str "assets/" "hero" "_" 2 ".png")"#), r#""assets/hero_2.png""#);
+    assert_eq!(show(&mut rt, r#"(err (fn [] (format "%d" 1.5)))"#), r#""format arg 1: expected int, found float""#);
+    assert_eq!(show(&mut rt, r#"(err (fn [] (format "%d %s" 1)))"#), r#""format: missing argument for %s""#);
+    assert_eq!(show(&mut rt, r#"(err (fn [] (format "%q" 1)))"#), r#""format: unknown conversion %q""#);
+    assert_eq!(show(&mut rt, r#"(err (fn [] (format "%.2f" "x")))"#), r#""format arg 1: expected number, found string""#);
+    assert_eq!(show(&mut rt, r#"(err (fn [] (format "%99999999999999999999999d" 1)))"#), r#""format: width larger than 10000""#);
+    assert_eq!(show(&mut rt, r#"(err (fn [] (format "%.100000f" 1.5)))"#), r#""format: precision larger than 10000""#);
+    assert_eq!(show(&mut rt, r#"(count (format "%10000d" 1))"#), "10000");
+}
+
+#[test]
+fn test_math() {
+    let _ = env_logger::try_init();
+    let mut rt = EvalRT::new();
+    rt.init();
+    rt.eval_file("tests/numbers.clj");
+    assert_eq!(show(&mut rt, "(quot 7 2)"), "3");
+    assert_eq!(show(&mut rt, "(quot -7 2)"), "-3");
+    assert_eq!(show(&mut rt, "(rem -7 2)"), "-1");
+    assert_eq!(show(&mut rt, "(mod -7 2)"), "1");
+    assert_eq!(show(&mut rt, "(mod 7 -2)"), "-1");
+    assert_eq!(show(&mut rt, "(mod 5.5 2)"), "1.5");
+    assert_eq!(show(&mut rt, "(quot 7.0 2)"), "3");
+    assert_eq!(show(&mut rt, "(abs -3)"), "3");
+    assert_eq!(show(&mut rt, "(abs -2.5)"), "2.5");
+    assert_eq!(show(&mut rt, "(min 3 1.5 2)"), "1.5");
+    assert_eq!(show(&mut rt, "(max 1 4 2)"), "4");
+    assert_eq!(show(&mut rt, "(inc 1)"), "2");
+    assert_eq!(show(&mut rt, "(dec 1.5)"), "0.5");
+    assert_eq!(show(&mut rt, "(bit-and 12 10)"), "8");
+    assert_eq!(show(&mut rt, "(bit-or 12 10)"), "14");
+    assert_eq!(show(&mut rt, "(bit-xor 12 10)"), "6");
+    assert_eq!(show(&mut rt, "(bit-not 0)"), "-1");
+    assert_eq!(show(&mut rt, "(bit-shift-left 1 4)"), "16");
+    assert_eq!(show(&mut rt, "(bit-shift-right -16 2)"), "-4");
+    assert_eq!(show(&mut rt, "(clojure.math/sqrt 16)"), "4");
+    assert_eq!(show(&mut rt, "(clojure.math/pow 2 10)"), "1024");
+    assert_eq!(show(&mut rt, "(clojure.math/floor -1.5)"), "-2");
+    assert_eq!(show(&mut rt, "(clojure.math/ceil 1.2)"), "2");
+    assert_eq!(show(&mut rt, "(clojure.math/round 2.5)"), "3");
+    assert_eq!(show(&mut rt, "(clojure.math/round -2.5)"), "-2");
+    assert_eq!(show(&mut rt, "(clojure.math/sin 0)"), "0");
+    assert_eq!(show(&mut rt, "(clojure.math/atan2 0 1)"), "0");
+    assert_eq!(show(&mut rt, "(clojure.math/log 1)"), "0");
+    assert_eq!(show(&mut rt, "(> clojure.math/PI 3.14)"), "true");
+    assert_eq!(show(&mut rt, "(clojure.math/inc 1)"), r#"[:error "not found symbol:clojure.math/inc"]"#);
+    assert_eq!(show(&mut rt, "(err (fn [] (/ 1 0)))"), r#""divide by zero""#);
+    assert_eq!(show(&mut rt, "(err (fn [] (+ 9223372036854775807 1)))"), r#""integer overflow in +""#);
+    assert_eq!(show(&mut rt, "(err (fn [] (* 4611686018427387904 2)))"), r#""integer overflow in *""#);
+    assert_eq!(show(&mut rt, "(err (fn [] (inc 9223372036854775807)))"), r#""integer overflow in inc""#);
+    assert_eq!(show(&mut rt, "(err (fn [] (mod 1 0)))"), r#""divide by zero""#);
+    assert_eq!(show(&mut rt, "(err (fn [] (quot min-int -1)))"), r#""integer overflow in quot""#);
+    assert_eq!(show(&mut rt, "(err (fn [] (abs min-int)))"), r#""integer overflow in abs""#);
+    assert_eq!(show(&mut rt, "(/ 1.0 0)"), "inf");
+    //float quot, rem and mod divide by zero like / does
+    assert_eq!(show(&mut rt, "[(quot 5.5 0) (quot -5.5 0.0) (rem 5.5 0) (mod 5.5 0.0) (mod 5 0.0)]"), "[inf -inf NaN NaN NaN]");
+    assert_eq!(show(&mut rt, "(= rolls replay)"), "true");
+    assert_eq!(show(&mut rt, "(every? (fn [n] (and (>= n 0) (< n 6))) (take 3 rolls))"), "true");
+    assert_eq!(show(&mut rt, "(< (nth rolls 3) 1)"), "true");
+    assert_eq!(show(&mut rt, "(try (/ 1 0) (catch DivideByZero e :caught))"), ":caught");
+
+    let rand_seq = |rt:&mut EvalRT| show(rt, "[(rand-int 100) (rand-int 100) (rand-int 100)]");
+    rt.set_rand_seed(42);
+    let first = rand_seq(&mut rt);
+    rt.set_rand_seed(42);
+    assert_eq!(rand_seq(&mut rt), first);
+}
+
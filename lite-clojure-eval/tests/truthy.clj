@@ -0,0 +1,23 @@
+(defn truthy? [x] (if x true false))
+
+[
+ (truthy? nil)
+ (truthy? false)
+ (truthy? 0)
+ (truthy? "")
+ (truthy? {})
+ (truthy? [])
+ (and)
+ (and 1 2 3)
+ (and 1 nil 3)
+ (and 1 false (nth nil 5))
+ (or)
+ (or nil false)
+ (or nil 2 (nth nil 5))
+ (not nil)
+ (not 0)
+ (when {:a 1} :map)
+ (if-let [x (get {:a 1} :a)] (+ x 1) :none)
+ (if-let [x (get {:a 1} :b)] (+ x 1) :none)
+ (cond nil 1 0 2)
+]
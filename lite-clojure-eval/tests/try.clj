@@ -0,0 +1,34 @@
+(def log [])
+
+(defn safe-div [a b]
+  (try
+    (if (= b 0)
+      (throw (ex-info "divide by zero" {:a a}))
+      (/ a b))
+    (catch ExceptionInfo e
+      [(ex-message e) (ex-data e)])))
+
+(defn deep [n]
+  (if (= n 0)
+    (throw (ex-info "bottom" {:n n}))
+    (let [x n] (deep (- n 1)))))
+
+(def finally-ret (try 1 (finally (conj! log :finally))))
+
+(def inner-ret
+  (try (try (throw (ex-info "inner" {})) (finally (conj! log :inner)))
+       (catch Exception e (ex-message e))))
+
+[
+ (safe-div 10 2)
+ (safe-div 1 0)
+ (try (deep 5) (catch Exception e (get (ex-data e) :n)))
+ (let [a 1] (try (let [b 2] (deep 3)) (catch Exception e (+ a 10))))
+ (try (undefined-fn 1) (catch NotFoundSymbol e (get (ex-data e) :type)))
+ (try (throw 42) (catch ExceptionInfo e :info) (catch :default e [:other e]))
+ [finally-ret inner-ret log]
+ (ex-cause (ex-info "outer" {} (ex-info "root" {})))
+ [1 (try (finally 2))]
+ [1 (try)]
+ (try (throw (ex-info "x" {})) (catch Exception e))
+]
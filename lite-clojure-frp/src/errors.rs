@@ -1,4 +1,3 @@
-use lite_clojure_eval::EvalError;
 use thiserror::{Error};
 #[derive(Error,Debug)]
 pub enum FRPError {
@@ -10,6 +9,10 @@ pub enum FRPError {
     EventNotFound,
     #[error("dynamic not found")]
     DynamicNotFound,
-    #[error("eval error:{0:?}")]
-    EvalError(EvalError)
+    #[error("wrong number of args")]
+    ArgCountError,
+    #[error("frp system is already borrowed")]
+    SystemBorrowed,
+    #[error("eval error:{0}")]
+    EvalError(String)
 }
\ No newline at end of file
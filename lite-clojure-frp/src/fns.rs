@@ -1,70 +1,74 @@
-use lite_clojure_eval::{EvalRT, ExecScope, Variable, run_native_fn};
+use std::{cell::RefCell, rc::{Rc, Weak}};
+use lite_clojure_eval::{EvalError, EvalRT, Variable};
 use anyhow::{Result,anyhow};
 use crate::FRPSystem;
 use crate::errors::{FRPError};
 
 
-pub fn add_frp_fns(vm:&mut EvalRT) {
-    vm.global_context().push_native_fn("$>", event_map);
-    vm.global_context().push_native_fn("<$>", event_map);
-    vm.global_context().push_native_fn("holdDyn", hold_dynamic);
-    vm.global_context().push_native_fn("foldDyn", fold_dynamic);
-    vm.global_context().push_native_fn("updated", updated_dynamic);
+//the closures only keep a weak handle, the system holds script values the gc can't see
+pub fn add_frp_fns(vm:&mut EvalRT,system:&Rc<RefCell<FRPSystem>>) {
+    push_frp_fn(vm, "$>", system, event_map);
+    push_frp_fn(vm, "<$>", system, event_map);
+    push_frp_fn(vm, "holdDyn", system, hold_dynamic);
+    push_frp_fn(vm, "foldDyn", system, fold_dynamic);
+    push_frp_fn(vm, "updated", system, updated_dynamic);
 }
 
-fn get_frp_system<'a>(scope:&'a mut ExecScope) -> Result<&'a mut FRPSystem> {
-   scope.find_userdata("*FRPSYSTEM*").ok_or(anyhow!(FRPError::NotFoundSystem))
+fn push_frp_fn(vm:&mut EvalRT,name:&'static str,system:&Rc<RefCell<FRPSystem>>,f:fn(&mut FRPSystem,Vec<Variable>) -> Result<Variable>) {
+    let system:Weak<RefCell<FRPSystem>> = Rc::downgrade(system);
+    vm.global_context().push_native_closure(name, move |_,args| {
+        let system = system.upgrade().ok_or_else(|| EvalError::NativeError(format!("native func {} error:{}",name,FRPError::NotFoundSystem)))?;
+        let ret = match system.try_borrow_mut() {
+            Ok(mut frp_system) => f(&mut frp_system,args),
+            Err(_) => Err(anyhow!(FRPError::SystemBorrowed))
+        };
+        ret.map_err(|err| EvalError::NativeError(format!("native func {} error:{}",name,err)))
+    });
 }
 
+fn take_arg(args:&mut Vec<Variable>) -> Result<Variable> {
+    if args.is_empty() {
+        return Err(anyhow!(FRPError::ArgCountError));
+    }
+    Ok(args.remove(0))
+}
 
-fn event_map(s:&mut ExecScope,a:Vec<Variable>) -> Variable {
-    run_native_fn("<$>", s, a, |scope,mut args| {
-        let frp_system = get_frp_system(scope)?;
-        let event_var = args.remove(0);
-        let next_var = args.remove(0);
-        let event_id = event_var.cast_int().ok_or(anyhow!(FRPError::TypeCastError) )?;
-        let next_event_id = frp_system.new_next_event(event_id as u32, next_var)
-                                          .ok_or(anyhow!(FRPError::EventNotFound) )?;
-        Ok(Variable::Int(next_event_id as i64))
-    })
+fn event_map(frp_system:&mut FRPSystem,mut args:Vec<Variable>) -> Result<Variable> {
+    let event_var = take_arg(&mut args)?;
+    let next_var = take_arg(&mut args)?;
+    let event_id = event_var.cast_int().ok_or(anyhow!(FRPError::TypeCastError) )?;
+    let next_event_id = frp_system.new_next_event(event_id as u32, next_var)
+                                      .ok_or(anyhow!(FRPError::EventNotFound) )?;
+    Ok(Variable::Int(next_event_id as i64))
 }
 
-fn hold_dynamic(s:&mut ExecScope,a:Vec<Variable>) -> Variable {
-    run_native_fn("holdDyn",s,a,|scope,mut args| {
-        let frp_system = get_frp_system(scope)?;
-        let default_value = args.remove(0);
-        let event_id = args.remove(0).cast_int().ok_or(anyhow!(FRPError::TypeCastError))?;
-        let dynamic_id = frp_system.new_dynamic(default_value, event_id as u32,None)
-                                       .ok_or(anyhow!(FRPError::EventNotFound))?;
-        Ok(Variable::Int(dynamic_id as i64))
-    })
+fn hold_dynamic(frp_system:&mut FRPSystem,mut args:Vec<Variable>) -> Result<Variable> {
+    let default_value = take_arg(&mut args)?;
+    let event_id = take_arg(&mut args)?.cast_int().ok_or(anyhow!(FRPError::TypeCastError))?;
+    let dynamic_id = frp_system.new_dynamic(default_value, event_id as u32,None)
+                                   .ok_or(anyhow!(FRPError::EventNotFound))?;
+    Ok(Variable::Int(dynamic_id as i64))
 }
 
-fn fold_dynamic(s:&mut ExecScope,a:Vec<Variable>) -> Variable {
-    run_native_fn("foldDyn", s, a, |scope,mut args| {
-        let frp_system = get_frp_system(scope)?;
-        let default_value = args.remove(0);
-        let event_id = args.remove(0).cast_int().ok_or(anyhow!(FRPError::TypeCastError))?;
-        let fn_var = args.remove(0);
-        let dynamic_id = frp_system.new_dynamic(default_value, event_id as u32, Some(fn_var))
-                                        .ok_or(anyhow!(FRPError::EventNotFound))?;
-        Ok(Variable::Int(dynamic_id as i64)) 
-    })
+fn fold_dynamic(frp_system:&mut FRPSystem,mut args:Vec<Variable>) -> Result<Variable> {
+    let default_value = take_arg(&mut args)?;
+    let event_id = take_arg(&mut args)?.cast_int().ok_or(anyhow!(FRPError::TypeCastError))?;
+    let fn_var = take_arg(&mut args)?;
+    let dynamic_id = frp_system.new_dynamic(default_value, event_id as u32, Some(fn_var))
+                                    .ok_or(anyhow!(FRPError::EventNotFound))?;
+    Ok(Variable::Int(dynamic_id as i64)) 
 }
 
-fn updated_dynamic(s:&mut ExecScope,a:Vec<Variable>) -> Variable {
-    run_native_fn("updated", s, a, |scope,mut args| {
-        let frp_system = get_frp_system(scope)?;
-        let dynamic_id = args.remove(0).cast_int().ok_or(anyhow!(FRPError::TypeCastError))? as u32;
-        let dynamic_updated = frp_system.dynamics.get(&dynamic_id)
-                                                            .ok_or(anyhow!(FRPError::DynamicNotFound))?.updated;
-        if let Some(updated_id) = dynamic_updated {
-            return Ok(Variable::Int(updated_id as i64));
-        } else {
-            let updated_id = frp_system.new_event(None);
-            let mut dynamic = frp_system.dynamics.get_mut(&dynamic_id).ok_or(anyhow!(FRPError::DynamicNotFound))?;
-            dynamic.updated = Some(updated_id);
-            return Ok(Variable::Int(updated_id as i64))
-        }
-    })
+fn updated_dynamic(frp_system:&mut FRPSystem,mut args:Vec<Variable>) -> Result<Variable> {
+    let dynamic_id = take_arg(&mut args)?.cast_int().ok_or(anyhow!(FRPError::TypeCastError))? as u32;
+    let dynamic_updated = frp_system.dynamics.get(&dynamic_id)
+                                                        .ok_or(anyhow!(FRPError::DynamicNotFound))?.updated;
+    if let Some(updated_id) = dynamic_updated {
+        return Ok(Variable::Int(updated_id as i64));
+    } else {
+        let updated_id = frp_system.new_event(None);
+        let mut dynamic = frp_system.dynamics.get_mut(&dynamic_id).ok_or(anyhow!(FRPError::DynamicNotFound))?;
+        dynamic.updated = Some(updated_id);
+        return Ok(Variable::Int(updated_id as i64))
+    }
 }
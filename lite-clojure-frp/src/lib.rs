@@ -79,7 +79,7 @@ impl FRPSystem {
         for (id,var) in dyn_calls.drain(..) {
            let dynamic = self.dynamics.get_mut(&id).ok_or(anyhow!(FRPError::DynamicNotFound))?;
            if let Some(fold_fn) = dynamic.fold_fn.as_ref() {
-             dynamic.value = vm.invoke_func2(fold_fn, vec![var.clone(),dynamic.value.clone()]).map_err(FRPError::EvalError)?
+             dynamic.value = vm.invoke_func2(fold_fn, vec![var.clone(),dynamic.value.clone()]).map_err(|err| FRPError::EvalError(err.to_string()))?
            } else {
              dynamic.value = var;
            }
@@ -98,7 +98,7 @@ impl FRPSystem {
         let event_var = if let Some(event_var) = cur_event.f.as_ref() {
             match event_var {
                 Variable::Function(_) => {
-                   vm.invoke_func2(&event_var, vec![var.clone()]).map_err(FRPError::EvalError)?
+                   vm.invoke_func2(&event_var, vec![var.clone()]).map_err(|err| FRPError::EvalError(err.to_string()))?
                 },
                 other => { other.clone() }
             }
@@ -132,20 +132,21 @@ fn testt() {
     env_logger::init();
     use fns::add_frp_fns;
 
+    use std::{cell::RefCell, rc::Rc};
+
     let mut vm = EvalRT::new();
     vm.init();
-    add_frp_fns(&mut vm);
-    let mut system = FRPSystem::default();
-    let e0 = system.new_event(None);
+    let system = Rc::new(RefCell::new(FRPSystem::default()));
+    add_frp_fns(&mut vm,&system);
+    let e0 = system.borrow_mut().new_event(None);
     vm.global_context().push_var("eRoot", Variable::Int(e0 as i64));
 
-    let frp_ptr = &mut system as *mut FRPSystem as *mut u8;
-    vm.global_context().push_var("*FRPSYSTEM*", Variable::UserData(frp_ptr));
-
     vm.eval_file("tests/1.clj").unwrap();
 
-    system.fire(e0, &Variable::Nil, &mut vm).unwrap();
-    system.fire(e0, &Variable::Nil, &mut vm).unwrap();
-    system.fire(e0, &Variable::Nil, &mut vm).unwrap();
-    system.fire(e0, &Variable::Nil, &mut vm).unwrap();
+    system.borrow_mut().fire(e0, &Variable::Nil, &mut vm).unwrap();
+    system.borrow_mut().fire(e0, &Variable::Nil, &mut vm).unwrap();
+    system.borrow_mut().fire(e0, &Variable::Nil, &mut vm).unwrap();
+    system.borrow_mut().fire(e0, &Variable::Nil, &mut vm).unwrap();
+    let dynamics = &system.borrow().dynamics;
+    assert!(dynamics.values().any(|d| d.get_value() == &Variable::Int(4)));
 }
\ No newline at end of file
@@ -1,16 +1,39 @@
-use crate::{errors::CSTError, GLOBAL_MACRO_HOOKS};
+use std::collections::HashSet;
 
-use super::{cexpr::{CExpr}, errors::{ASTError}, expr::Expr, meta::MetaTable, value::Symbol};
+use crate::errors::CSTError;
+
+use super::{cexpr::{CExpr, CExprKind}, errors::{ASTError}, expr::{Catch, Expr, ExprKind, FnArity}, macros::MacroRegistry, meta::MetaTable, span::Span, syntax_quote::{gensym, SyntaxQuote}, value::{Symbol, CORE_NS}};
+use super::destructure::destructure;
+use super::core_macros::{expand_core_macro, is_core_macro};
 use super::cst::ParseCST;
 pub struct ASTModule {
     pub file_name:String,
     pub exprs:Vec<Expr>
 }
 
+pub trait MacroExpander {
+    fn expand_macro(&mut self,sym:&Symbol,args:&[CExpr],span:&Span) -> Option<Result<CExpr,ASTError>>;
+
+    //whether an unqualified name resolves to clojure.core, syntax quote qualifies it as clojure.core/name
+    fn is_core_symbol(&self,name:&str) -> bool {
+        is_core_macro(name)
+    }
+}
+
+struct NoMacro;
+
+impl MacroExpander for NoMacro {
+    fn expand_macro(&mut self,_:&Symbol,_:&[CExpr],_:&Span) -> Option<Result<CExpr,ASTError>> {
+        None
+    }
+}
+
 pub struct TranslateToAST {
     file_name:String,
+    ns:String,
     cexprs:Vec<CExpr>,
     meta_table:MetaTable<CExpr>,
+    macros:MacroRegistry,
 
     exprs:Vec<Expr>,
 }
@@ -18,7 +41,12 @@ pub struct TranslateToAST {
 
 impl TranslateToAST {
     pub fn new(file_name:String,cexprs:Vec<CExpr>,meta_table:MetaTable<CExpr>) -> Self {
-       TranslateToAST {file_name,cexprs,meta_table,exprs:vec![]}
+       TranslateToAST::with_macros(file_name, cexprs, meta_table, MacroRegistry::default())
+    }
+
+    pub fn with_macros(file_name:String,cexprs:Vec<CExpr>,meta_table:MetaTable<CExpr>,macros:MacroRegistry) -> Self {
+       let ns = ns_from_file_name(&file_name);
+       TranslateToAST {file_name,ns,cexprs,meta_table,macros,exprs:vec![]}
     }
     
 
@@ -35,55 +63,98 @@ impl TranslateToAST {
         }
     }
 
-    fn translate_cexpr(&mut self,mut cexpr:CExpr) {
-       
-        self.hand_macro_expr(&mut cexpr);
-
-        let macro_list = GLOBAL_MACRO_HOOKS.lock().unwrap();
-        for macro_fn in macro_list.iter() {
-            macro_fn(&mut cexpr);
-        }
-        if let Some(az) = self.analyze(cexpr) {
+    fn translate_cexpr(&mut self,cexpr:CExpr) {
+        if let Some(az) = self.translate_form(cexpr,&mut NoMacro) {
             match az {
                Ok(v) => {
                    self.exprs.push(v)
                },
-               Err(err) => { dbg!(err); }
+               Err(err) => { log::error!("analyze error:{}",err); }
             }
         }
     }
 
-    fn hand_macro_list(&mut self,lst:&mut Vec<CExpr>) {
+    pub fn translate_form(&mut self,mut cexpr:CExpr,expander:&mut dyn MacroExpander) -> Option<Result<Expr,ASTError>> {
+        if let Err(err) = self.hand_macro_expr(&mut cexpr,expander) {
+            return Some(Err(err.at(&cexpr.span)));
+        }
+
+        self.macros.apply_hooks(&mut cexpr);
+        self.analyze(cexpr)
+    }
+
+    fn hand_macro_list(&mut self,lst:&mut Vec<CExpr>,expander:&mut dyn MacroExpander) -> Result<(),ASTError> {
         for e in lst {
-            self.hand_macro_expr(e);
+            self.hand_macro_expr(e,expander)?;
         }
+        Ok(())
     }
-    fn hand_macro_expr(&mut self,expr:&mut CExpr) {
-        match expr {
-            CExpr::List(lst) => {
-                match lst.first() {
-                    Some(CExpr::Symbol(sym)) => {
+
+    fn hand_macro_expr(&mut self,expr:&mut CExpr,expander:&mut dyn MacroExpander) -> Result<(),ASTError> {
+        let span = expr.span.clone();
+        match &mut expr.kind {
+            CExprKind::List(lst) => {
+                match lst.first().map(|e| &e.kind) {
+                    Some(CExprKind::Symbol(sym)) => {
                         match sym.name.as_str() {
-                            "defn" => self.ex_defn(lst),
-                            "defrecord" => self.ex_defrecord(lst).unwrap(),
-                            s => {
-                                if s.chars().next() == Some('.') {
-                                    self.ex_object_call(lst);
+                            "quote" => (),
+                            "defn" => {
+                                self.ex_defn(lst,&span).map_err(ASTError::CSTError)?;
+                                self.hand_macro_list(lst,expander)?;
+                            },
+                            "defrecord" => {
+                                self.ex_defrecord(lst,&span).map_err(ASTError::CSTError)?;
+                                self.hand_macro_list(lst,expander)?;
+                            },
+                            s if s.starts_with('.') => {
+                                self.ex_object_call(lst,&span).map_err(ASTError::CSTError)?;
+                                self.hand_macro_list(lst,expander)?;
+                            },
+                            _ => {
+                                let sym = self.local_sym(sym.clone());
+                                //user macros shadow core macros, clojure.core/name always means the core one
+                                let ns = sym.sym_ns().map(String::as_str);
+                                let is_core = is_core_macro(&sym.name) && matches!(ns,None | Some(CORE_NS));
+                                let expand = if is_core && ns.is_some() {
+                                    None
                                 } else {
-                                    self.hand_macro_list(lst);  
+                                    match self.macros.find_macro(&sym.to_string()) {
+                                        Some(macro_fn) => {
+                                            let args:Vec<CExpr> = lst[1..].iter().filter(|e| !e.is_comment()).cloned().collect();
+                                            Some(macro_fn(&args,&span))
+                                        },
+                                        None => expander.expand_macro(&sym,&lst[1..],&span)
+                                    }
+                                };
+                                let expand = match expand {
+                                    None if is_core => Some(expand_core_macro(&sym.name,lst.drain(1..).collect(),&span)),
+                                    expand => expand
+                                };
+                                if let Some(expand) = expand {
+                                    *expr = expand.map_err(|err| err.at(&span))?;
+                                    return self.hand_macro_expr(expr,expander);
                                 }
+                                self.hand_macro_list(lst,expander)?;
                             }
                         }
                     }
-                    _=> { self.hand_macro_list(lst);  }
+                    _=> { self.hand_macro_list(lst,expander)?; }
                 }
-            }
+            },
+            CExprKind::Vector(lst) | CExprKind::Map(lst) | CExprKind::Set(lst) => self.hand_macro_list(lst,expander)?,
+            CExprKind::SyntaxQuote(_) => {
+                if let CExprKind::SyntaxQuote(quoted) = std::mem::replace(&mut expr.kind,CExprKind::Nil) {
+                    let is_core = |name:&str| expander.is_core_symbol(name);
+                    *expr = SyntaxQuote::with_core(&self.ns, &is_core).expand(*quoted).map_err(|err| err.at(&span))?;
+                }
+                return self.hand_macro_expr(expr,expander);
+            },
             _ => ()
         }
-        
+        Ok(())
     }
 
-    fn ex_defrecord(&mut self,lst:&mut Vec<CExpr>) -> Result<(),CSTError> {
+    fn ex_defrecord(&mut self,lst:&mut Vec<CExpr>,span:&Span) -> Result<(),CSTError> {
          /*(defrecord RecordName []
              (fname [this param] 
             
@@ -112,50 +183,55 @@ impl TranslateToAST {
         )*/
         let mut new_lst:Vec<CExpr> = vec![];
         let def_sym = Symbol::intern(None,String::from("def"));
-        new_lst.push(CExpr::Symbol(def_sym));
+        new_lst.push(CExpr::new(CExprKind::Symbol(def_sym),span.clone()));
         
         lst.remove(0); //defrecord
         let mut record_name = lst.remove(0); //RecordName
-        if let CExpr::Symbol(ref mut sym) = record_name {
+        if let CExprKind::Symbol(ref mut sym) = record_name.kind {
             sym.name.push('.');
         }
         new_lst.push(record_name);
 
-        let mut fn_list:Vec<CExpr> = vec![CExpr::Symbol(Symbol::intern(None,String::from("fn")))];
+        let mut fn_list:Vec<CExpr> = vec![CExpr::new(CExprKind::Symbol(Symbol::intern(None,String::from("fn"))),span.clone())];
 
         let mut record_fns:Vec<CExpr> = vec![];
-        let mut args = lst.remove(0).take_list().ok_or(CSTError::ExMacroDefrecord)?;
+        let args_expr = lst.remove(0);
+        let args_span = args_expr.span.clone();
+        let mut args = args_expr.take_list().ok_or(CSTError::ExMacroDefrecord)?;
         for arg in args.iter() {
-            if let CExpr::Symbol(ref sym) = arg {
-                record_fns.push(CExpr::String(sym.name.clone()));
+            if let CExprKind::Symbol(ref sym) = arg.kind {
+                record_fns.push(CExpr::new(CExprKind::String(sym.name.clone()),arg.span.clone()));
                 record_fns.push(arg.clone());
             }
         }
-        fn_list.push(CExpr::Vector(args.clone()));
+        fn_list.push(CExpr::new(CExprKind::Vector(args.clone()),args_span));
         
         
 
        
         for f in lst.drain(..) {
+            let f_span = f.span.clone();
             let mut expr_list = f.take_list().ok_or(CSTError::ExMacroDefrecord)?;
-            let fn_name = expr_list.remove(0).cast_symbol().map_err(|_|CSTError::ExMacroDefrecord)?;
+            let fn_name_expr = expr_list.remove(0);
+            let name_span = fn_name_expr.span.clone();
+            let fn_name = fn_name_expr.cast_symbol().map_err(|_|CSTError::ExMacroDefrecord)?;
 
             let fn_sym = Symbol::intern(None,String::from("fn"));
-            expr_list.insert(0, CExpr::Symbol(fn_sym.clone()));
+            expr_list.insert(0, CExpr::new(CExprKind::Symbol(fn_sym.clone()),name_span.clone()));
 
             
-            record_fns.push(CExpr::String(fn_name.name) );
-            record_fns.push(CExpr::List(expr_list));
+            record_fns.push(CExpr::new(CExprKind::String(fn_name.name),name_span) );
+            record_fns.push(CExpr::new(CExprKind::List(expr_list),f_span));
         }
-        fn_list.push(CExpr::Map(record_fns));
-        new_lst.push(CExpr::List(fn_list));
+        fn_list.push(CExpr::new(CExprKind::Map(record_fns),span.clone()));
+        new_lst.push(CExpr::new(CExprKind::List(fn_list),span.clone()));
        
         *lst = new_lst;
         Ok(())
        
     }
 
-    fn ex_object_call(&self,lst:&mut Vec<CExpr>) -> Result<(),CSTError> {
+    fn ex_object_call(&self,lst:&mut Vec<CExpr>,span:&Span) -> Result<(),CSTError> {
          /*
         (.fname object 123)
         to
@@ -165,7 +241,7 @@ impl TranslateToAST {
         fn_name.remove(0);
         let this = lst[0].clone();
         let mut new_lst:Vec<CExpr> = vec![]; 
-        let fn_list = CExpr::List(vec![this, CExpr::String(fn_name)]);
+        let fn_list = CExpr::new(CExprKind::List(vec![this, CExpr::new(CExprKind::String(fn_name),span.clone())]),span.clone());
         new_lst.push(fn_list);
         new_lst.append(lst);
         *lst = new_lst;
@@ -173,58 +249,90 @@ impl TranslateToAST {
         Ok(())
     }
 
-    fn ex_defn(&mut self,lst:&mut Vec<CExpr>)  {
-        //(defn fn_name [args] (seq1 ) (seq 2)) -> (def fn_name (fn [args] (seq1) (seq 2)))
+    fn ex_defn(&mut self,lst:&mut Vec<CExpr>,span:&Span) -> Result<(),CSTError> {
+        //(defn fn_name "doc"? [args] (seq1 ) (seq 2)) -> (def fn_name "doc" (fn fn_name [args] (seq1) (seq 2)))
+        //(defn fn_name "doc"? ([a] ..) ([a b] ..)) -> (def fn_name "doc" (fn fn_name ([a] ..) ([a b] ..)))
+        lst.retain(|e| !e.is_comment());
+        if lst.len() < 3 {
+            return Err(CSTError::ExMacroDefn);
+        }
         lst.remove(0); //defn
         let name_expr = lst.remove(0);
-        let args_expr = lst.remove(0); //[args]
+        if name_expr.cast_sym().is_none() {
+            return Err(CSTError::ExMacroDefn);
+        }
         let mut new_lst:Vec<CExpr> = vec![];
         let def_sym = Symbol::intern(None,String::from("def"));
-        new_lst.push(CExpr::Symbol(def_sym));
-        new_lst.push(name_expr);
+        new_lst.push(CExpr::new(CExprKind::Symbol(def_sym),span.clone()));
+        new_lst.push(name_expr.clone());
+        if lst[0].is_string() {
+            new_lst.push(lst.remove(0));
+        }
+        if let Some(CExprKind::Map(_)) = lst.first().map(|e| &e.kind) {
+            lst.remove(0); //attr-map
+        }
+        if lst.is_empty() {
+            return Err(CSTError::ExMacroDefn);
+        }
 
         let fn_sym = Symbol::intern(None,String::from("fn"));
-        lst.insert(0, args_expr);
-        lst.insert(0, CExpr::Symbol(fn_sym));
+        lst.insert(0, name_expr);
+        lst.insert(0, CExpr::new(CExprKind::Symbol(fn_sym),span.clone()));
        
-        new_lst.push(CExpr::List(lst.clone()));
-       
-       
-        *lst = new_lst  
+        new_lst.push(CExpr::new(CExprKind::List(std::mem::take(lst)),span.clone()));
+        *lst = new_lst;
+        Ok(())
     }
 
-
-
-    
     fn analyze(&mut self,cexpr:CExpr) -> Option<Result<Expr,ASTError>> {
-       match cexpr {
-           CExpr::Nil => Some(Ok(Expr::Nil)),
-           CExpr::Boolean(b) => Some(Ok(Expr::Boolean(b))),
-           CExpr::Symbol(sym) => Some(self.analyze_sym(sym)),
-           CExpr::Number(_raw,num)  => Some(Ok(Expr::Number(num))),
-           CExpr::Keyword(key) => Some(Ok(Expr::Keyword(key))),
-           CExpr::String(str) => Some(Ok(Expr::String(str))),
-           CExpr::Map(lst) => Some(self.analyze_map(lst)),
-           CExpr::Vector(lst) => Some(self.analyze_vector(lst)),
-           CExpr::QuoteVar(s) => Some(Ok(Expr::QuoteVar(s))),
-           CExpr::Comment(_s) => None,
+       let span = cexpr.span.clone();
+       let kind = match cexpr.kind {
+           CExprKind::Nil => Ok(ExprKind::Nil),
+           CExprKind::Boolean(b) => Ok(ExprKind::Boolean(b)),
+           CExprKind::Symbol(sym) => self.analyze_sym(sym),
+           CExprKind::Number(_raw,num)  => Ok(ExprKind::Number(num)),
+           CExprKind::Keyword(key) => Ok(ExprKind::Keyword(key)),
+           CExprKind::String(str) => Ok(ExprKind::String(str)),
+           CExprKind::Char(chr) => Ok(ExprKind::Char(chr)),
+           CExprKind::Map(lst) => self.analyze_map(lst),
+           CExprKind::Vector(lst) => self.analyze_vector(lst),
+           CExprKind::Set(lst) => self.analyze_set(lst),
+           CExprKind::QuoteVar(s) => Ok(ExprKind::QuoteVar(s)),
+           CExprKind::Quote(quoted) => Ok(ExprKind::Quote(quoted)),
+           CExprKind::SyntaxQuote(quoted) => {
+               match SyntaxQuote::new(&self.ns).expand(*quoted) {
+                   Ok(expand) => return self.analyze(expand),
+                   Err(err) => Err(err)
+               }
+           },
+           CExprKind::UnQuote(_) => Err(ASTError::ErrUnQuote),
+           CExprKind::UnQuoteS(_) => Err(ASTError::ErrUnQuoteSplicing),
+           CExprKind::Comment(_s) => return None,
            _ => {
             if cexpr.is_iseq() {
-                Some(self.analyze_seq(cexpr))
+                self.analyze_seq(cexpr)
             } else {
                 dbg!(cexpr);
                 todo!()
             }
            }
-       }
+       };
+       Some(kind.map(|k| Expr::new(k,span.clone())).map_err(|err| err.at(&span)))
     }
 
-    fn analyze_sym(&mut self,sym:Symbol) -> Result<Expr,ASTError> {
-        //TODO
-        Ok(Expr::Symbol(sym))
+    fn analyze_sym(&mut self,sym:Symbol) -> Result<ExprKind,ASTError> {
+        Ok(ExprKind::Symbol(self.local_sym(sym)))
     }
 
-    fn analyze_seq(&mut self,cexpr:CExpr) -> Result<Expr,ASTError> {
+    //syntax-quote qualifies symbols with the current ns, which resolve as local ones
+    fn local_sym(&self,sym:Symbol) -> Symbol {
+        if sym.sym_ns() == Some(&self.ns) {
+            return Symbol::intern(None, sym.name);
+        }
+        sym
+    }
+
+    fn analyze_seq(&mut self,cexpr:CExpr) -> Result<ExprKind,ASTError> {
         let mop = cexpr.seq_first();
         if mop.is_none() {
             return Err(ASTError::ErrSeq);    
@@ -237,12 +345,17 @@ impl TranslateToAST {
                     "loop" => return self.parse_let_expr(cexpr,true),
                     "let" =>  return self.parse_let_expr(cexpr, false),
                     "if" => return self.parse_if_expr(cexpr),
-                    "case*" => return self.parse_case_expr(),
+                    "and" => return self.parse_and_or_expr(cexpr,true),
+                    "or" => return self.parse_and_or_expr(cexpr,false),
+                    "try" => return self.parse_try_expr(cexpr),
+                    "throw" => return self.parse_throw_expr(cexpr),
                     "recur" => return self.parse_recur_expr(cexpr),
+                    "quote" => return self.parse_quote_expr(cexpr),
+                    "defmacro" => return self.parse_defmacro_expr(cexpr),
                     "do" => {
                         let mut lst = cexpr.take_list_no_white().unwrap();
                         lst.remove(0);
-                        return self.parse_do_expr_(lst)
+                        return self.parse_do_expr_(lst).map(|e| e.kind)
                     },
                     _ => return self.parse_invoke(cexpr)
                 }
@@ -251,7 +364,33 @@ impl TranslateToAST {
         self.parse_invoke(cexpr)
     }
 
-    fn parse_recur_expr(&mut self,cexpr:CExpr)  -> Result<Expr,ASTError> {
+    fn parse_quote_expr(&mut self,cexpr:CExpr) -> Result<ExprKind,ASTError> {
+        //(quote form)
+        let mut lst = cexpr.take_list_no_white().unwrap();
+        if lst.len() != 2 {
+            return Err(ASTError::ErrQuote);
+        }
+        Ok(ExprKind::Quote(Box::new(lst.remove(1))))
+    }
+
+    fn parse_defmacro_expr(&mut self,cexpr:CExpr) -> Result<ExprKind,ASTError> {
+        //(defmacro name [args] body ...) or (defmacro name "docstring" [args] body ...)
+        let span = cexpr.span.clone();
+        let mut lst = cexpr.take_list_no_white().unwrap();
+        lst.remove(0);
+        if lst.len() > 1 && lst[1].is_string() {
+            lst.remove(1);
+        }
+        if lst.len() < 2 || !lst[1].is_vec() {
+            return Err(ASTError::ErrDefMacro);
+        }
+        let sym = lst.remove(0).cast_symbol().map_err(|_| ASTError::ErrDefMacro)?;
+        lst.insert(0, CExpr::symbol(None, "fn", &span));
+        let fn_expr = self.analyze(CExpr::list(lst, &span)).unwrap()?;
+        Ok(ExprKind::DefMacro(sym,Box::new(fn_expr)))
+    }
+
+    fn parse_recur_expr(&mut self,cexpr:CExpr)  -> Result<ExprKind,ASTError> {
         let mut lst = cexpr.take_list_no_white().unwrap();
         lst.remove(0);
         let mut arg_list :Vec<Expr> = vec![];
@@ -260,10 +399,10 @@ impl TranslateToAST {
                arg_list.push(v?);
            }
         }
-        Ok(Expr::Recur(arg_list))
+        Ok(ExprKind::Recur(arg_list))
     }
 
-    fn analyze_map(&mut self,lst:Vec<CExpr>) -> Result<Expr,ASTError> {
+    fn analyze_map(&mut self,lst:Vec<CExpr>) -> Result<ExprKind,ASTError> {
         let mut lst_expr:Vec<Expr> = vec![];
         for cexpr in lst {
           if let Some(v) = self.analyze(cexpr) {
@@ -271,10 +410,10 @@ impl TranslateToAST {
               lst_expr.push(a_expr);
           }
         }
-        Ok(Expr::Map(lst_expr))
+        Ok(ExprKind::Map(lst_expr))
     }
 
-    fn analyze_vector(&mut self,lst:Vec<CExpr>) -> Result<Expr,ASTError> {
+    fn analyze_vector(&mut self,lst:Vec<CExpr>) -> Result<ExprKind,ASTError> {
         let mut lst_expr:Vec<Expr> = vec![];
         for cexpr in lst {
           if let Some(v) = self.analyze(cexpr) {
@@ -282,52 +421,184 @@ impl TranslateToAST {
               lst_expr.push(a_expr);
           }
         }
-        Ok(Expr::Vector(lst_expr))
+        Ok(ExprKind::Vector(lst_expr))
     }
 
-    fn parse_case_expr(&mut self) -> Result<Expr,ASTError> {
-        todo!()
+    fn analyze_set(&mut self,lst:Vec<CExpr>) -> Result<ExprKind,ASTError> {
+        let mut lst_expr:Vec<Expr> = vec![];
+        for cexpr in lst {
+          if let Some(v) = self.analyze(cexpr) {
+              lst_expr.push(v?);
+          }
+        }
+        Ok(ExprKind::Set(lst_expr))
     }
 
-    fn parse_fn_expr(&mut self,cexpr:CExpr) -> Result<Expr,ASTError> {
-        //(fn [a b c] a) or (fn ([a] a)  ([a b] b))
+    fn parse_fn_expr(&mut self,cexpr:CExpr) -> Result<ExprKind,ASTError> {
+        //(fn name? [a b & more] a) or (fn name? ([a] a)  ([a b] b))
         let mut lst = cexpr.take_list_no_white().unwrap();
         lst.remove(0); //rm fn
-        let is_first_vec = lst.first().unwrap().is_vec();
-        if is_first_vec {
-             let head = self.analyze(lst.remove(0)).unwrap()?;
-             let mut flst = head.case_vector().unwrap();
-             let sym_lst:Vec<Symbol> = flst.drain(..).map(|f| f.case_sym().unwrap()).collect();
-             let mut form_lst:Vec<Expr> = vec![];
-             for item in lst {
-                 if let Some(az_expr) = self.analyze(item) {
-                    form_lst.push(az_expr?);
-                 }
-                
-             }
-             Ok(Expr::Fn(sym_lst,form_lst))
+        let name = lst.first().and_then(|e| e.cast_sym()).cloned();
+        if name.is_some() {
+            lst.remove(0);
+        }
+        if lst.is_empty() {
+            return Err(ASTError::ErrFn);
+        }
+        let mut arities:Vec<FnArity> = vec![];
+        if lst[0].is_vec() {
+            arities.push(self.parse_fn_arity(lst)?);
         } else {
-            eprintln!("fn expr error:{:?}",&lst);
+            for item in lst {
+                let arity_span = item.span.clone();
+                match item.kind {
+                    CExprKind::List(arity_lst) => {
+                        let arity_lst = arity_lst.into_iter().filter(|e| !e.is_comment()).collect();
+                        arities.push(self.parse_fn_arity(arity_lst).map_err(|err| err.at(&arity_span))?);
+                    },
+                    _ => return Err(ASTError::ErrFn.at(&arity_span))
+                }
+            }
+        }
+        let rest_count = arities.iter().filter(|a| a.rest.is_some()).count();
+        let fixed_counts:HashSet<usize> = arities.iter().filter(|a| a.rest.is_none()).map(|a| a.args.len()).collect();
+        if rest_count > 1 || fixed_counts.len() + rest_count != arities.len() {
+            return Err(ASTError::ErrFnArity);
+        }
+        let variadic_count = arities.iter().find(|a| a.rest.is_some()).map(|a| a.args.len());
+        if variadic_count.map(|n| fixed_counts.iter().any(|c| *c > n)).unwrap_or(false) {
+            return Err(ASTError::ErrFnFixedArity);
+        }
+        Ok(ExprKind::Fn(name,arities))
+    }
+
+    fn parse_fn_arity(&mut self,mut lst:Vec<CExpr>) -> Result<FnArity,ASTError> {
+        //([a b & more] body ...)
+        if lst.is_empty() || !lst[0].is_vec() {
             return Err(ASTError::ErrFn);
         }
+        let params_span = lst[0].span.clone();
+        let params = lst.remove(0).take_list_no_white().unwrap();
+        let mut args:Vec<Symbol> = vec![];
+        let mut rest:Option<Symbol> = None;
+        //([a {:keys [b]}] body) to ([a p__1] (let [{:keys [b]} p__1] body))
+        let mut param_patterns:Vec<CExpr> = vec![];
+        let mut param_iter = params.into_iter();
+        while let Some(param) = param_iter.next() {
+            let param_span = param.span.clone();
+            let is_rest = param.cast_sym().map(|s| s.name == "&").unwrap_or(false);
+            let param = if is_rest {
+                let rest_param = param_iter.next().ok_or(ASTError::BadBindingForm.at(&param_span))?;
+                if param_iter.next().is_some() {
+                    return Err(ASTError::BadBindingForm.at(&param_span));
+                }
+                rest_param
+            } else { param };
+            let sym = match param.cast_sym() {
+                Some(sym) => sym.clone(),
+                None => {
+                    let sym = gensym("p");
+                    param_patterns.push(param);
+                    param_patterns.push(CExpr::new(CExprKind::Symbol(sym.clone()),param_span));
+                    sym
+                }
+            };
+            if is_rest {
+                rest = Some(sym);
+            } else {
+                args.push(sym);
+            }
+        }
+        if !param_patterns.is_empty() {
+            lst = vec![wrap_let(param_patterns, lst, &params_span)];
+        }
+        let mut body:Vec<Expr> = vec![];
+        for item in lst {
+            if let Some(az_expr) = self.analyze(item) {
+                body.push(az_expr?);
+            }
+        }
+        Ok(FnArity { args, rest, body })
     }
 
-    fn parse_if_expr(&mut self,cexpr:CExpr) -> Result<Expr,ASTError> {
+    fn parse_if_expr(&mut self,cexpr:CExpr) -> Result<ExprKind,ASTError> {
         // (if test then) or (if test then else)
+        let if_span = cexpr.span.clone();
         let mut lst = cexpr.take_list_no_white().unwrap();
         if lst.len() > 4 || lst.len() < 3 {
             return Err(ASTError::ErrIf);
         }
         lst.remove(0);
-        let test_expr = self.analyze(lst.remove(0)).unwrap().unwrap();
-        let then_expr = self.analyze(lst.remove(0)).unwrap().unwrap();
+        let test_expr = self.analyze(lst.remove(0)).unwrap()?;
+        let then_expr = self.analyze(lst.remove(0)).unwrap()?;
         let else_expr = if lst.len() > 0 {
-            self.analyze(lst.remove(0)).unwrap().unwrap()
-        } else {Expr::Nil };
-        Ok(Expr::If(Box::new(test_expr),Box::new(then_expr),Box::new(else_expr)))
+            self.analyze(lst.remove(0)).unwrap()?
+        } else {Expr::new(ExprKind::Nil,if_span) };
+        Ok(ExprKind::If(Box::new(test_expr),Box::new(then_expr),Box::new(else_expr)))
+    }
+
+    fn parse_and_or_expr(&mut self,cexpr:CExpr,is_and:bool) -> Result<ExprKind,ASTError> {
+        // (and x y ...) or (or x y ...)
+        let mut lst = cexpr.take_list_no_white().unwrap();
+        lst.remove(0);
+        let mut exprs:Vec<Expr> = vec![];
+        for item in lst {
+            if let Some(v) = self.analyze(item) {
+                exprs.push(v?);
+            }
+        }
+        Ok(if is_and { ExprKind::And(exprs) } else { ExprKind::Or(exprs) })
+    }
+
+    fn parse_try_expr(&mut self,cexpr:CExpr) -> Result<ExprKind,ASTError> {
+        // (try body* (catch Class e body*)* (finally body*)?)
+        let mut lst = cexpr.take_list_no_white().unwrap();
+        lst.remove(0);
+        let mut body:Vec<CExpr> = vec![];
+        let mut catches:Vec<Catch> = vec![];
+        let mut finally:Option<Box<Expr>> = None;
+        for item in lst {
+            let head = item.seq_first().and_then(|e| e.cast_sym()).filter(|s| s.sym_ns().is_none()).map(|s| s.name.clone());
+            match head.as_deref() {
+                Some("catch") if finally.is_none() => {
+                    let mut clause = item.take_list_no_white().unwrap();
+                    if clause.len() < 3 {
+                        return Err(ASTError::ErrTry);
+                    }
+                    clause.remove(0);
+                    let class = clause.remove(0);
+                    let class = match &class.kind {
+                        CExprKind::Symbol(sym) => sym.to_string(),
+                        CExprKind::Keyword(k) => k.to_string(),
+                        _ => return Err(ASTError::ErrTry)
+                    };
+                    let bind = clause.remove(0).cast_sym().cloned().ok_or(ASTError::ErrTry)?;
+                    catches.push(Catch {class,bind,body:self.parse_do_expr_(clause)? });
+                },
+                Some("finally") if finally.is_none() => {
+                    let mut clause = item.take_list_no_white().unwrap();
+                    clause.remove(0);
+                    finally = Some(Box::new(self.parse_do_expr_(clause)?));
+                },
+                Some("catch") | Some("finally") => return Err(ASTError::ErrTry),
+                _ if !catches.is_empty() || finally.is_some() => return Err(ASTError::ErrTry),
+                _ => body.push(item)
+            }
+        }
+        Ok(ExprKind::Try(Box::new(self.parse_do_expr_(body)?),catches,finally))
+    }
+
+    fn parse_throw_expr(&mut self,cexpr:CExpr) -> Result<ExprKind,ASTError> {
+        // (throw expr)
+        let mut lst = cexpr.take_list_no_white().unwrap();
+        if lst.len() != 2 {
+            return Err(ASTError::ErrThrow);
+        }
+        let expr = self.analyze(lst.remove(1)).unwrap()?;
+        Ok(ExprKind::Throw(Box::new(expr)))
     }
 
-    fn parse_def_expr(&mut self,cexpr:CExpr) -> Result<Expr,ASTError> {
+    fn parse_def_expr(&mut self,cexpr:CExpr) -> Result<ExprKind,ASTError> {
         // (def x) or (def x initexpr) or (def x "docstring" initexpr)
         let mut lst = cexpr.take_list_no_white().unwrap();
         let mut doc_string:Option<String> = None;
@@ -344,10 +615,10 @@ impl TranslateToAST {
         if lst.len() > 0 {
            init_expr = Some(Box::new(self.analyze(lst.remove(0)).unwrap()?));
         }
-        Ok(Expr::Def(doc_string,sym,init_expr))
+        Ok(ExprKind::Def(doc_string,sym,init_expr))
     }
 
-    fn parse_let_expr(&mut self,cexpr:CExpr,is_loop:bool) -> Result<Expr,ASTError> {
+    fn parse_let_expr(&mut self,cexpr:CExpr,is_loop:bool) -> Result<ExprKind,ASTError> {
         //(let  [var1 val1 var2 val2 ... ] body ... )
         //(loop [var1 val1 var2 val2 ... ] body ... )
         let mut lst = cexpr.take_list_no_white().unwrap();
@@ -358,9 +629,28 @@ impl TranslateToAST {
         if !lst[0].is_vec() {
             return Err(ASTError::BadBindingForm);
         }
-        let mut bindings = lst.remove(0).take_list_no_white().unwrap();
-        if (bindings.len() % 2) != 0 {
-            return Err(ASTError::ErrLet(bindings.len()));
+        let let_span = lst[0].span.clone();
+        let pairs = lst.remove(0).take_list_no_white().unwrap();
+        if (pairs.len() % 2) != 0 {
+            return Err(ASTError::ErrLet(pairs.len()));
+        }
+        let mut bindings:Vec<CExpr> = vec![];
+        //recur rebinds loop slots, so loop patterns are destructured in an inner let
+        let mut loop_patterns:Vec<CExpr> = vec![];
+        let mut pair_iter = pairs.into_iter();
+        while let (Some(pattern),Some(value)) = (pair_iter.next(),pair_iter.next()) {
+            if is_loop && pattern.cast_sym().is_none() {
+                let loop_sym = CExpr::new(CExprKind::Symbol(gensym("loop")),pattern.span.clone());
+                bindings.push(loop_sym.clone());
+                bindings.push(value);
+                loop_patterns.push(pattern);
+                loop_patterns.push(loop_sym);
+            } else {
+                destructure(pattern, value, &mut bindings)?;
+            }
+        }
+        if !loop_patterns.is_empty() {
+            lst = vec![wrap_let(loop_patterns, lst, &let_span)];
         }
 
         let mut bind_vecs:Vec<Expr> = vec![];
@@ -373,10 +663,11 @@ impl TranslateToAST {
             bind_vecs.push(val_expr);
         }
         let body_expr = self.parse_do_expr_(lst)?;
-        Ok(Expr::Let(bind_vecs,Box::new(body_expr),is_loop))
+        Ok(ExprKind::Let(bind_vecs,Box::new(body_expr),is_loop))
     }
 
     fn parse_do_expr_(&mut self,cexprs:Vec<CExpr>) -> Result<Expr,ASTError> {
+        let span = self.span_of(&cexprs);
         let mut exprs:Vec<Expr> = vec![];
         for cexpr in cexprs {
             if let Some(e) = self.analyze(cexpr) {
@@ -384,10 +675,21 @@ impl TranslateToAST {
             }
            
         }
-        Ok(Expr::Body(exprs))
+        //an empty body still leaves a value, (do) and (try) are nil
+        if exprs.is_empty() {
+            exprs.push(Expr::new(ExprKind::Nil,span.clone()));
+        }
+        Ok(Expr::new(ExprKind::Body(exprs),span))
     }
 
-    fn parse_invoke(&mut self,cexpr:CExpr) -> Result<Expr,ASTError> {
+    fn span_of(&self,cexprs:&[CExpr]) -> Span {
+        match (cexprs.first(),cexprs.last()) {
+            (Some(fst),Some(lst)) => Span::new(fst.span.file.clone(),fst.span.start,lst.span.end),
+            _ => Span::default()
+        }
+    }
+
+    fn parse_invoke(&mut self,cexpr:CExpr) -> Result<ExprKind,ASTError> {
         let mut exprs:Vec<Expr> = vec![];
         for cexpr in cexpr.take_list_no_white().unwrap() {
             if let Some(e) = self.analyze(cexpr) {
@@ -395,24 +697,41 @@ impl TranslateToAST {
             }
             
         }
-        Ok(Expr::Invoke(exprs))
+        Ok(ExprKind::Invoke(exprs))
     }
 }
  
-pub fn parse_ast(file_name:String,code_string:&str) -> Result<ASTModule,ASTError> {
-    let mut parser_cst = ParseCST::new(&code_string);
-    let cexprs = parser_cst.parse_exprs();
-    match cexprs  {
-        Ok(cexprs) => {
-            let meta_table = parser_cst.take();
-            let trans = TranslateToAST::new(file_name.to_string(), cexprs, meta_table);
-            let ast_mod = trans.translate();
-            Ok(ast_mod)
+fn wrap_let(bindings:Vec<CExpr>,mut body:Vec<CExpr>,span:&Span) -> CExpr {
+    body.insert(0, CExpr::new(CExprKind::Vector(bindings),span.clone()));
+    body.insert(0, CExpr::symbol(None, "let", span));
+    CExpr::list(body, span)
+}
+
+fn ns_from_file_name(file_name:&str) -> String {
+    if file_name.is_empty() {
+        return String::from("user");
+    }
+    let name = file_name.strip_suffix(".clj").unwrap_or(file_name);
+    name.replace(['/', '\\'], ".")
+}
+ 
+pub fn parse_cexprs(file_name:&str,code_string:&str) -> Result<(Vec<CExpr>,MetaTable<CExpr>),ASTError> {
+    let mut parser_cst = ParseCST::with_file(file_name,code_string);
+    match parser_cst.parse_exprs()  {
+        Ok(cexprs) => Ok((cexprs,parser_cst.take())),
+        Err(err) => {
+            let span = Span::point(parser_cst.file_name(),parser_cst.pos());
+            return Err(ASTError::CSTError(err).at(&span));
         }
-        Err(err) => {return Err(ASTError::CSTError(err));}
     }
 }
 
+pub fn parse_ast(file_name:String,code_string:&str) -> Result<ASTModule,ASTError> {
+    let (cexprs,meta_table) = parse_cexprs(&file_name,code_string)?;
+    let trans = TranslateToAST::new(file_name, cexprs, meta_table);
+    Ok(trans.translate())
+}
+
 #[test]
 fn test_trans() {
    use super::cst::ParseCST;
@@ -425,4 +744,22 @@ fn test_trans() {
    let  trans = TranslateToAST::new(file_name.to_string(), cexprs, meta_table);
    let ast_mod = trans.translate();
    dbg!(ast_mod.exprs);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_fn_arity_error() {
+   let check = |code:&str| {
+      let (mut cexprs,meta_table) = parse_cexprs("f.clj",code).unwrap();
+      let mut trans = TranslateToAST::new("f.clj".to_string(), vec![], meta_table);
+      trans.translate_form(cexprs.remove(0),&mut NoMacro).unwrap().map(|_| ()).map_err(|err| err.to_string())
+   };
+   assert!(check("(fn ([a] 1) ([a & r] 2))").is_ok());
+   let err = check("(fn ([a b c] 1) ([a & r] 2))").unwrap_err();
+   assert!(err.ends_with("Can't have fixed arity function with more params than variadic function"));
+}
+
+#[test]
+fn test_error_span() {
+   let err = parse_ast("err.clj".to_string(),"(def a 1)\n(def b [1 2)").err().unwrap();
+   assert_eq!(err.span().unwrap().start.line,2);
+}
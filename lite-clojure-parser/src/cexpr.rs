@@ -1,10 +1,15 @@
 use std::{ fmt};
 
-use super::{meta::{Meta, MetaTable}, value::{Keyword, Symbol}};
+use super::{meta::{Meta, MetaTable}, span::Span, value::{Keyword, Symbol}};
 
+#[derive(Debug,Clone)]
+pub struct CExpr {
+    pub kind:CExprKind,
+    pub span:Span
+}
 
 #[derive(Debug,Clone)]
-pub enum CExpr {
+pub enum CExprKind {
     Nil,
     Boolean(bool),
     Keyword(Keyword),
@@ -16,6 +21,7 @@ pub enum CExpr {
     List(Vec<CExpr>),
     Vector(Vec<CExpr>),
     Map(Vec<CExpr>),
+    Set(Vec<CExpr>),
     Meta(Vec<CExpr>),
     Quote(Box<CExpr>),
     QuoteVar(Symbol),
@@ -27,9 +33,22 @@ pub enum CExpr {
 
 
 impl CExpr {
+    pub fn new(kind:CExprKind,span:Span) -> CExpr {
+        CExpr { kind, span }
+    }
+
+    pub fn symbol(ns:Option<&str>,name:&str,span:&Span) -> CExpr {
+        let sym = Symbol::intern(ns.map(|s| s.to_string()), name.to_string());
+        CExpr::new(CExprKind::Symbol(sym),span.clone())
+    }
+
+    pub fn list(lst:Vec<CExpr>,span:&Span) -> CExpr {
+        CExpr::new(CExprKind::List(lst),span.clone())
+    }
+
     pub fn set_meta(&mut self,meta:Meta<CExpr>,table:&mut MetaTable<CExpr>) {
-        match self {
-            CExpr::Symbol(sym) => {
+        match &mut self.kind {
+            CExprKind::Symbol(sym) => {
                 let index = table.add_meta(meta);
                 sym.set_meta(index)
             }
@@ -38,128 +57,137 @@ impl CExpr {
     }
 
     pub fn is_iseq(&self) -> bool {
-        match self {
-            CExpr::List(_) =>  true,
-            CExpr::Quote(_) => true,
-            CExpr::UnQuote(_) => true,
-            CExpr::Dref(_) => true,
+        match self.kind {
+            CExprKind::List(_) =>  true,
+            CExprKind::Quote(_) => true,
+            CExprKind::UnQuote(_) => true,
+            CExprKind::Dref(_) => true,
             _ => false
         }
     }
 
     pub fn is_string(&self) -> bool {
-        match self {
-            CExpr::String(_) => true,
+        match self.kind {
+            CExprKind::String(_) => true,
             _ => false
         }
     }
 
     pub fn is_comment(&self) -> bool {
-        match  self {
-            CExpr::Comment(_) => true,
+        match self.kind {
+            CExprKind::Comment(_) => true,
             _ => false
         }
     }
 
     pub fn is_vec(&self) -> bool {
-        match self {
-            CExpr::Vector(_) => true,
+        match self.kind {
+            CExprKind::Vector(_) => true,
             _ => false
         }
     }
 
     pub fn cast_string(self) -> Result<String,Self> {
-        match self {
-            CExpr::String(s) => Ok(s),
-            _ => Err(self)
+        match self.kind {
+            CExprKind::String(s) => Ok(s),
+            kind => Err(CExpr::new(kind,self.span))
         }
     } 
 
     pub fn cast_symbol(self) -> Result<Symbol,Self> {
-        match self {
-            CExpr::Symbol(s) => Ok(s),
-            _ => Err(self)
+        match self.kind {
+            CExprKind::Symbol(s) => Ok(s),
+            kind => Err(CExpr::new(kind,self.span))
         }
     }
 
     pub fn take_list(self) -> Option<Vec<CExpr>>  {
-        match self {
-            CExpr::Vector(lst) => Some(lst),
-            CExpr::List(vec) => Some(vec),
-            CExpr::Quote(b) => (*b).take_list(),
-            CExpr::Dref(b) => (*b).take_list(),
-            CExpr::UnQuote(b) => (*b).take_list(),
-            CExpr::UnQuoteS(b) => (*b).take_list(),
+        match self.kind {
+            CExprKind::Vector(lst) => Some(lst),
+            CExprKind::List(vec) => Some(vec),
+            CExprKind::Quote(b) => (*b).take_list(),
+            CExprKind::Dref(b) => (*b).take_list(),
+            CExprKind::UnQuote(b) => (*b).take_list(),
+            CExprKind::UnQuoteS(b) => (*b).take_list(),
             _ => None
         }
     }
 
     pub fn take_list_no_white(self) -> Option<Vec<CExpr>>  {
-        match self {
-            CExpr::Vector(mut lst) => Some(lst.drain(..).filter(|e| !e.is_comment()).collect() ),
-            CExpr::List(mut vec) => Some(vec.drain(..).filter(|e| !e.is_comment()).collect() ),
-            CExpr::Quote(b) => (*b).take_list_no_white(),
-            CExpr::Dref(b) => (*b).take_list_no_white(),
-            CExpr::UnQuote(b) => (*b).take_list_no_white(),
-            CExpr::UnQuoteS(b) => (*b).take_list_no_white(),
+        match self.kind {
+            CExprKind::Vector(mut lst) => Some(lst.drain(..).filter(|e| !e.is_comment()).collect() ),
+            CExprKind::List(mut vec) => Some(vec.drain(..).filter(|e| !e.is_comment()).collect() ),
+            CExprKind::Quote(b) => (*b).take_list_no_white(),
+            CExprKind::Dref(b) => (*b).take_list_no_white(),
+            CExprKind::UnQuote(b) => (*b).take_list_no_white(),
+            CExprKind::UnQuoteS(b) => (*b).take_list_no_white(),
             _ => None
         }
     }
 
     pub fn seq_first(&self) -> Option<&CExpr>  {
-        match self {
-            CExpr::List(lst) => lst.first(),
+        match &self.kind {
+            CExprKind::List(lst) => lst.first(),
             _ => None
         }
     }
 
     pub fn cast_sym(&self) -> Option<&Symbol> {
-        match self {
-            CExpr::Symbol(sym) => Some(sym),
+        match &self.kind {
+            CExprKind::Symbol(sym) => Some(sym),
             _ => None
         }
     }
 }
 
 impl fmt::Display for CExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,"{}",self.kind)
+    }
+}
+
+impl fmt::Display for CExprKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            CExpr::Nil => write!(f,"nil"),
-            CExpr::QuoteVar(s) => write!(f,"#'{}",s),
-            CExpr::Boolean(b) => write!(f,"{}",b),
-            CExpr::Keyword(kv) => write!(f,"{}",kv),
-            CExpr::String(str) => write!(f,"\"{}\"",str),
-            CExpr::Comment(comment) => write!(f,";{}\r\n",comment),
-            CExpr::Number(raw,_) => write!(f,"{}",raw),
-            CExpr::Symbol(sym) => {
+            CExprKind::Nil => write!(f,"nil"),
+            CExprKind::QuoteVar(s) => write!(f,"#'{}",s),
+            CExprKind::Boolean(b) => write!(f,"{}",b),
+            CExprKind::Keyword(kv) => write!(f,"{}",kv),
+            CExprKind::String(str) => write!(f,"\"{}\"",str),
+            CExprKind::Comment(comment) => write!(f,";{}\r\n",comment),
+            CExprKind::Number(raw,_) => write!(f,"{}",raw),
+            CExprKind::Symbol(sym) => {
                 if let Some(m) = sym.meta {
                     write!(f,"sym({},{})",sym,m)
                 } else {
                     write!(f,"{}",sym)
                 }
             },
-            CExpr::Char(chr) => write!(f,"'{}'",chr),
-            CExpr::List(lst) => {
+            CExprKind::Char(chr) => write!(f,"'{}'",chr),
+            CExprKind::List(lst) => {
                 write!(f,"{}",display_vec(lst, '(', ')'))
             },
-            CExpr::Vector(lst) => {
+            CExprKind::Vector(lst) => {
                 write!(f,"{}",display_vec(lst, '[', ']'))
             },
-            CExpr::Map(lst) => {
+            CExprKind::Map(lst) => {
                 write!(f,"{}",display_vec(lst, '{', '}'))
             },
-            CExpr::Meta(lst) => {
+            CExprKind::Set(lst) => {
+                write!(f,"#{}",display_vec(lst, '{', '}'))
+            },
+            CExprKind::Meta(lst) => {
                 write!(f,"meta{}",display_vec(lst, '(', ')'))
             },
-            CExpr::Quote(expr) => {
+            CExprKind::Quote(expr) => {
                 write!(f,"{}",expr)
             },
-            CExpr::Dref(expr) => {
+            CExprKind::Dref(expr) => {
                 write!(f,"@{}",expr)
             },
-            CExpr::SyntaxQuote(expr) => write!(f,"`{}",expr),
-            CExpr::UnQuote(expr) => write!(f,"`~{}",expr),
-            CExpr::UnQuoteS(expr) => write!(f,"`~@{}",expr)
+            CExprKind::SyntaxQuote(expr) => write!(f,"`{}",expr),
+            CExprKind::UnQuote(expr) => write!(f,"`~{}",expr),
+            CExprKind::UnQuoteS(expr) => write!(f,"`~@{}",expr)
         }
     }
 }
@@ -0,0 +1,275 @@
+use super::{cexpr::{CExpr, CExprKind}, errors::ASTError, span::Span, syntax_quote::gensym, value::CORE_NS};
+
+pub const CORE_MACROS:&[&str] = &["when","when-not","if-not","if-let","when-let","cond","condp","case",
+                                  "->","->>","as->","some->","doto","lazy-seq"];
+
+pub fn is_core_macro(name:&str) -> bool {
+    CORE_MACROS.contains(&name)
+}
+
+pub fn expand_core_macro(name:&str,args:Vec<CExpr>,span:&Span) -> Result<CExpr,ASTError> {
+    let args:Vec<CExpr> = args.into_iter().filter(|e| !e.is_comment()).collect();
+    match name {
+        "when" => ex_when(args, span, false),
+        "when-not" => ex_when(args, span, true),
+        "if-not" => ex_if_not(args, span),
+        "if-let" => ex_if_let(args, span, false),
+        "when-let" => ex_if_let(args, span, true),
+        "cond" => ex_cond(args, span),
+        "condp" => ex_condp(args, span),
+        "case" => ex_case(args, span),
+        "->" => ex_thread(args, span, false),
+        "->>" => ex_thread(args, span, true),
+        "as->" => ex_as_thread(args, span),
+        "some->" => ex_some_thread(args, span),
+        "doto" => ex_doto(args, span),
+        "lazy-seq" => ex_lazy_seq(args, span),
+        _ => Err(ASTError::ErrMacro(name.to_string()))
+    }
+}
+
+fn sym(name:&str,span:&Span) -> CExpr {
+    CExpr::symbol(None, name, span)
+}
+
+fn core_sym(name:&str,span:&Span) -> CExpr {
+    CExpr::symbol(Some(CORE_NS), name, span)
+}
+
+fn nil(span:&Span) -> CExpr {
+    CExpr::new(CExprKind::Nil,span.clone())
+}
+
+fn gensym_expr(prefix:&str,span:&Span) -> CExpr {
+    CExpr::new(CExprKind::Symbol(gensym(prefix)),span.clone())
+}
+
+fn if_expr(test:CExpr,then:CExpr,other:CExpr,span:&Span) -> CExpr {
+    CExpr::list(vec![sym("if", span),test,then,other], span)
+}
+
+fn do_expr(mut body:Vec<CExpr>,span:&Span) -> CExpr {
+    body.insert(0, sym("do", span));
+    CExpr::list(body, span)
+}
+
+fn let_expr(bindings:Vec<CExpr>,mut body:Vec<CExpr>,span:&Span) -> CExpr {
+    body.insert(0, CExpr::new(CExprKind::Vector(bindings),span.clone()));
+    body.insert(0, sym("let", span));
+    CExpr::list(body, span)
+}
+
+fn ex_when(mut args:Vec<CExpr>,span:&Span,is_not:bool) -> Result<CExpr,ASTError> {
+    //(when test body ...) -> (if test (do body ...) nil)
+    if args.is_empty() {
+        return Err(ASTError::ErrMacroArgs);
+    }
+    let test = args.remove(0);
+    let body = do_expr(args, span);
+    if is_not {
+        Ok(if_expr(test, nil(span), body, span))
+    } else {
+        Ok(if_expr(test, body, nil(span), span))
+    }
+}
+
+fn ex_if_not(mut args:Vec<CExpr>,span:&Span) -> Result<CExpr,ASTError> {
+    //(if-not test then else) -> (if test else then)
+    if args.len() < 2 || args.len() > 3 {
+        return Err(ASTError::ErrMacroArgs);
+    }
+    let test = args.remove(0);
+    let then = args.remove(0);
+    let other = if args.is_empty() { nil(span) } else { args.remove(0) };
+    Ok(if_expr(test, other, then, span))
+}
+
+fn ex_if_let(mut args:Vec<CExpr>,span:&Span,is_when:bool) -> Result<CExpr,ASTError> {
+    //(if-let [x test] then else) -> (let [temp test] (if temp (let [x temp] then) else))
+    if args.len() < 2 || (!is_when && args.len() > 3) {
+        return Err(ASTError::ErrMacroArgs);
+    }
+    let mut binding = args.remove(0).take_list_no_white().filter(|b| b.len() == 2).ok_or(ASTError::BadBindingForm)?;
+    let test = binding.remove(1);
+    let pattern = binding.remove(0);
+    let temp = gensym_expr("temp", span);
+    let (then,other) = if is_when {
+        (do_expr(args, span),nil(span))
+    } else {
+        let then = args.remove(0);
+        (then,args.pop().unwrap_or_else(|| nil(span)))
+    };
+    let then = let_expr(vec![pattern,temp.clone()], vec![then], span);
+    Ok(let_expr(vec![temp.clone(),test], vec![if_expr(temp, then, other, span)], span))
+}
+
+fn ex_cond(args:Vec<CExpr>,span:&Span) -> Result<CExpr,ASTError> {
+    //(cond t1 e1 t2 e2 :else e3) -> (if t1 e1 (if t2 e2 e3))
+    if !args.len().is_multiple_of(2) {
+        return Err(ASTError::ErrMacroArgs);
+    }
+    let mut ret = nil(span);
+    let mut pairs:Vec<(CExpr,CExpr)> = vec![];
+    let mut iter = args.into_iter();
+    while let (Some(test),Some(then)) = (iter.next(),iter.next()) {
+        pairs.push((test,then));
+    }
+    for (test,then) in pairs.into_iter().rev() {
+        ret = match test.kind {
+            CExprKind::Keyword(_) | CExprKind::Boolean(true) => then,
+            _ => if_expr(test, then, ret, span)
+        };
+    }
+    Ok(ret)
+}
+
+fn ex_condp(mut args:Vec<CExpr>,span:&Span) -> Result<CExpr,ASTError> {
+    //(condp pred expr c1 r1 c2 r2 default) -> (let [p pred e expr] (if (p c1 e) r1 (if (p c2 e) r2 default)))
+    if args.len() < 2 {
+        return Err(ASTError::ErrMacroArgs);
+    }
+    let pred = gensym_expr("pred", span);
+    let value = gensym_expr("expr", span);
+    let bindings = vec![pred.clone(),args.remove(0),value.clone(),args.remove(0)];
+    let mut ret = if args.len() % 2 == 1 { args.pop().unwrap() } else { no_matching_clause(&value, span) };
+    while args.len() >= 2 {
+        let then = args.pop().unwrap();
+        let test = args.pop().unwrap();
+        let call = CExpr::list(vec![pred.clone(),test,value.clone()], span);
+        ret = if_expr(call, then, ret, span);
+    }
+    Ok(let_expr(bindings, vec![ret], span))
+}
+
+fn ex_case(mut args:Vec<CExpr>,span:&Span) -> Result<CExpr,ASTError> {
+    //(case e k1 r1 (k2 k3) r2 default) -> (let [v e] (if (= v 'k1) r1 (if (contains? '#{k2 k3} v) r2 default)))
+    if args.is_empty() {
+        return Err(ASTError::ErrMacroArgs);
+    }
+    let value = gensym_expr("case", span);
+    let bindings = vec![value.clone(),args.remove(0)];
+    let mut ret = if args.len() % 2 == 1 { args.pop().unwrap() } else { no_matching_clause(&value, span) };
+    while args.len() >= 2 {
+        let then = args.pop().unwrap();
+        let test = args.pop().unwrap();
+        let test_span = test.span.clone();
+        let test = match test.kind {
+            CExprKind::List(lst) => {
+                let set = CExpr::new(CExprKind::Set(lst),test_span.clone());
+                let quoted = CExpr::new(CExprKind::Quote(Box::new(set)),test_span);
+                CExpr::list(vec![core_sym("contains?", span),quoted,value.clone()], span)
+            },
+            kind => {
+                let quoted = CExpr::new(CExprKind::Quote(Box::new(CExpr::new(kind,test_span.clone()))),test_span);
+                CExpr::list(vec![core_sym("=", span),value.clone(),quoted], span)
+            }
+        };
+        ret = if_expr(test, then, ret, span);
+    }
+    Ok(let_expr(bindings, vec![ret], span))
+}
+
+fn no_matching_clause(value:&CExpr,span:&Span) -> CExpr {
+    //(throw (ex-info (str "No matching clause: " v) {}))
+    let msg = CExpr::new(CExprKind::String(String::from("No matching clause: ")),span.clone());
+    let msg = CExpr::list(vec![core_sym("str", span),msg,value.clone()], span);
+    let info = CExpr::list(vec![core_sym("ex-info", span),msg,CExpr::new(CExprKind::Map(vec![]),span.clone())], span);
+    CExpr::list(vec![sym("throw", span),info], span)
+}
+
+fn thread_form(value:CExpr,form:CExpr,is_last:bool) -> CExpr {
+    let span = form.span.clone();
+    match form.kind {
+        CExprKind::List(mut lst) => {
+            if is_last {
+                lst.push(value);
+            } else {
+                lst.insert(1.min(lst.len()), value);
+            }
+            CExpr::list(lst, &span)
+        },
+        kind => CExpr::list(vec![CExpr::new(kind,span.clone()),value], &span)
+    }
+}
+
+fn ex_thread(mut args:Vec<CExpr>,_span:&Span,is_last:bool) -> Result<CExpr,ASTError> {
+    //(-> x (f a) g) -> (g (f x a)), (->> x (f a)) -> (f a x)
+    if args.is_empty() {
+        return Err(ASTError::ErrMacroArgs);
+    }
+    let mut ret = args.remove(0);
+    for form in args {
+        ret = thread_form(ret, form, is_last);
+    }
+    Ok(ret)
+}
+
+fn ex_as_thread(mut args:Vec<CExpr>,span:&Span) -> Result<CExpr,ASTError> {
+    //(as-> x v (f v) (g 1 v)) -> (let [v x v (f v) v (g 1 v)] v)
+    if args.len() < 2 {
+        return Err(ASTError::ErrMacroArgs);
+    }
+    let value = args.remove(0);
+    let name = args.remove(0);
+    if name.cast_sym().is_none() {
+        return Err(ASTError::BadBindingForm);
+    }
+    let mut bindings = vec![name.clone(),value];
+    for form in args {
+        bindings.push(name.clone());
+        bindings.push(form);
+    }
+    Ok(let_expr(bindings, vec![name], span))
+}
+
+fn ex_some_thread(mut args:Vec<CExpr>,span:&Span) -> Result<CExpr,ASTError> {
+    //(some-> x f g) -> (let [v x v (if (nil? v) nil (f v)) v (if (nil? v) nil (g v))] v)
+    if args.is_empty() {
+        return Err(ASTError::ErrMacroArgs);
+    }
+    let value = gensym_expr("some", span);
+    let mut bindings = vec![value.clone(),args.remove(0)];
+    for form in args {
+        let is_nil = CExpr::list(vec![core_sym("nil?", span),value.clone()], span);
+        bindings.push(value.clone());
+        bindings.push(if_expr(is_nil, nil(span), thread_form(value.clone(), form, false), span));
+    }
+    Ok(let_expr(bindings, vec![value], span))
+}
+
+fn ex_doto(mut args:Vec<CExpr>,span:&Span) -> Result<CExpr,ASTError> {
+    //(doto x (f 1) g) -> (let [v x] (f v 1) (g v) v)
+    if args.is_empty() {
+        return Err(ASTError::ErrMacroArgs);
+    }
+    let value = gensym_expr("doto", span);
+    let bindings = vec![value.clone(),args.remove(0)];
+    let mut body:Vec<CExpr> = args.into_iter().map(|form| thread_form(value.clone(), form, false)).collect();
+    body.push(value);
+    Ok(let_expr(bindings, body, span))
+}
+
+fn ex_lazy_seq(mut args:Vec<CExpr>,span:&Span) -> Result<CExpr,ASTError> {
+    //(lazy-seq body ...) -> (clojure.core/lazy-seq* (fn [] body ...))
+    args.insert(0, CExpr::new(CExprKind::Vector(vec![]),span.clone()));
+    args.insert(0, sym("fn", span));
+    Ok(CExpr::list(vec![core_sym("lazy-seq*", span),CExpr::list(args, span)], span))
+}
+
+#[test]
+fn test_core_macros() {
+    use super::cst::ParseCST;
+    let expand = |code:&str| {
+        let lst = ParseCST::new(code).parse().unwrap().take_list().unwrap();
+        let name = lst[0].cast_sym().unwrap().name.clone();
+        format!("{}",expand_core_macro(&name, lst[1..].to_vec(), &Span::default()).unwrap())
+    };
+    assert_eq!(expand("(when a b c)"),"(if a (do b c) nil)");
+    assert_eq!(expand("(if-not a b)"),"(if a nil b)");
+    assert_eq!(expand("(cond a 1 b 2 :else 3)"),"(if a 1 (if b 2 3))");
+    assert_eq!(expand("(-> x (f 1) g)"),"(g (f x 1))");
+    assert_eq!(expand("(->> x (f 1) g)"),"(g (f 1 x))");
+    assert_eq!(expand("(as-> 1 v (+ v 1))"),"(let [v 1 v (+ v 1)] v)");
+    assert_eq!(expand("(lazy-seq (cons 1 nil))"),"(clojure.core/lazy-seq* (fn [] (cons 1 nil)))");
+    assert!(expand("(case x 1 :a (2 3) :b :c)").contains("(clojure.core/contains? #{2 3} case__"));
+}
@@ -1,17 +1,25 @@
-use std::{char};
+use std::{char, collections::HashSet, rc::Rc};
 
-use super::{cexpr::{self, CExpr, Number}, errors::CSTError, lex_string::LexString, meta::{Meta, MetaTable, Metakey}, utils, value::{Keyword, Symbol}};
+use super::{cexpr::{self, CExpr, CExprKind, Number}, errors::CSTError, lex_string::LexString, meta::{Meta, MetaTable, Metakey}, span::{Pos, Span}, utils, value::{Keyword, Symbol}};
 
 pub struct ParseCST<'a> {
+    file:Rc<str>,
     source:LexString<'a>,
-    meta_table:MetaTable<CExpr>
+    meta_table:MetaTable<CExpr>,
+    in_anon_fn:bool
 }
 
 impl<'a> ParseCST<'a> {
     pub fn new(code_string:&'a str) -> ParseCST<'a> {
+        ParseCST::with_file("", code_string)
+    }
+
+    pub fn with_file(file_name:&str,code_string:&'a str) -> ParseCST<'a> {
         ParseCST {
+            file:Rc::from(file_name),
             source:LexString::new(code_string),
-            meta_table:MetaTable::new()
+            meta_table:MetaTable::new(),
+            in_anon_fn:false
         }
     }
 
@@ -30,6 +38,12 @@ impl<'a> ParseCST<'a> {
 
     pub fn parse(&mut self) -> Result<CExpr,CSTError> {
         self.skip_whitespace();
+        let start = self.pos();
+        let kind = self.parse_kind()?;
+        Ok(CExpr::new(kind, Span::new(self.file.clone(), start, self.pos())))
+    }
+
+    fn parse_kind(&mut self) -> Result<CExprKind,CSTError> {
         if let Some(chr) = self.next() {
             let ret = match chr {
                 '\"' => self.parse_string(),
@@ -38,7 +52,7 @@ impl<'a> ParseCST<'a> {
                 '\\' => self.parse_char(),
                 '(' => self.parse_list(),
                 '`' => self.parse_syntax_quote(),
-                '\'' => Ok(CExpr::Quote(Box::new(self.parse()?))),
+                '\'' => Ok(CExprKind::Quote(Box::new(self.parse()?))),
                 '[' => self.parse_vector(),
                 '@' => self.parse_deref_quote(),
                 '~' => self.parse_un_quote(),
@@ -50,7 +64,7 @@ impl<'a> ParseCST<'a> {
                         self.next();
                         return  self.parse_number(nchr.unwrap(),true);
                     } else {
-                        Ok(CExpr::Symbol(Symbol::intern_name("-") ))
+                        self.parse_symbol('-')
                     }
                 },
                 chr => {
@@ -67,65 +81,101 @@ impl<'a> ParseCST<'a> {
         return Err(CSTError::ErrEof);
     }
 
-    fn parse_dispatch(&mut self) -> Result<CExpr,CSTError> {
+    pub fn pos(&self) -> Pos {
+        Pos::new(self.source.line() as u32, self.source.col() as u32 + 1, self.source.offset() as u32)
+    }
+
+    pub fn file_name(&self) -> Rc<str> {
+        self.file.clone()
+    }
+
+    fn parse_dispatch(&mut self) -> Result<CExprKind,CSTError> {
         match self.source.next() {
             Some('^') => self.parse_meta(),
             Some('_') => self.parse_discard(),
             Some('\'') => {
                 match self.parse() {
-                    Ok(CExpr::Symbol(sym)) => {
-                       Ok(CExpr::QuoteVar(sym))
+                    Ok(CExpr {kind:CExprKind::Symbol(sym),..}) => {
+                       Ok(CExprKind::QuoteVar(sym))
                     }
                     Ok(_) => return Err(CSTError::ErrQuoteVar),
                     Err(err) => return Err(err)
                 }
             },
+            Some('(') => self.parse_anon_fn(),
+            Some('{') => self.parse_set(),
             Some(c) => Err(CSTError::InvalidChar(c)),
             None => Err(CSTError::ErrEof)
         }
     }
 
-    fn parse_discard(&mut self) -> Result<CExpr,CSTError> {
+    fn parse_anon_fn(&mut self) -> Result<CExprKind,CSTError> {
+        //#(+ % %2) to (fn [%1 %2] (+ %1 %2))
+        if self.in_anon_fn {
+            return Err(CSTError::ErrNestedAnonFn);
+        }
+        let start = self.pos();
+        self.in_anon_fn = true;
+        let body = self.read_list(')');
+        self.in_anon_fn = false;
+        let mut body = body?;
+        let span = Span::new(self.file.clone(), start, self.pos());
+
+        let mut arg_count = 0;
+        let mut has_rest = false;
+        for cexpr in body.iter_mut() {
+            anon_fn_args(cexpr, &mut arg_count, &mut has_rest);
+        }
+        let mut args:Vec<CExpr> = (1..=arg_count).map(|idx| CExpr::symbol(None, &format!("%{}",idx), &span)).collect();
+        if has_rest {
+            args.push(CExpr::symbol(None, "&", &span));
+            args.push(CExpr::symbol(None, "%&", &span));
+        }
+        let fn_list = vec![CExpr::symbol(None, "fn", &span),CExpr::new(CExprKind::Vector(args),span.clone()),CExpr::list(body, &span)];
+        Ok(CExprKind::List(fn_list))
+    }
+
+    fn parse_discard(&mut self) -> Result<CExprKind,CSTError> {
         let skip_expr = self.parse()?;
-        Ok(CExpr::Comment(format!("{}",skip_expr)))
+        Ok(CExprKind::Comment(format!("{}",skip_expr)))
     }
 
-    fn parse_un_quote(&mut self) -> Result<CExpr,CSTError> {
+    fn parse_un_quote(&mut self) -> Result<CExprKind,CSTError> {
         if let Some(chr) = self.source.lookahead(1) {
             if chr == '@' {
                 self.next();
-                return  Ok(CExpr::UnQuoteS(Box::new(self.parse()?)))
+                return  Ok(CExprKind::UnQuoteS(Box::new(self.parse()?)))
             } else {
-                return  Ok(CExpr::UnQuote(Box::new(self.parse()?)))
+                return  Ok(CExprKind::UnQuote(Box::new(self.parse()?)))
             }
         }
         return Err(CSTError::ErrEof);
     }
 
-    fn parse_deref_quote(&mut self) -> Result<CExpr,CSTError> {
+    fn parse_deref_quote(&mut self) -> Result<CExprKind,CSTError> {
         let expr = self.parse()?;
-        Ok(CExpr::Dref(Box::new(expr)))
+        Ok(CExprKind::Dref(Box::new(expr)))
     }
 
-    fn parse_syntax_quote(&mut self) -> Result<CExpr,CSTError> {
+    fn parse_syntax_quote(&mut self) -> Result<CExprKind,CSTError> {
         let expr = self.parse()?;
-        Ok(CExpr::SyntaxQuote(Box::new(expr)))
+        Ok(CExprKind::SyntaxQuote(Box::new(expr)))
     }
 
-    fn parse_meta(&mut self) -> Result<CExpr,CSTError> {
-        let cexpr = self.parse()?;
+    fn parse_meta(&mut self) -> Result<CExprKind,CSTError> {
+        let CExpr {kind,span} = self.parse()?;
         let mut meta = Meta::new();
-        match cexpr {
-            CExpr::String(str) => {
-                meta.insert(Metakey::Keyword(Keyword::key_tag()), CExpr::String(str));
+        match kind {
+            CExprKind::String(str) => {
+                meta.insert(Metakey::Keyword(Keyword::key_tag()), CExpr::new(CExprKind::String(str),span));
             },
-            CExpr::Symbol(sym) => {
-                meta.insert(Metakey::Keyword(Keyword::key_tag()), CExpr::Symbol(sym));
+            CExprKind::Symbol(sym) => {
+                meta.insert(Metakey::Keyword(Keyword::key_tag()), CExpr::new(CExprKind::Symbol(sym),span));
             },
-            CExpr::Keyword(k) => {
-                meta.insert(Metakey::Keyword(k.clone()), CExpr::Boolean(true));
+            CExprKind::Keyword(k) => {
+                meta.insert(Metakey::Keyword(k.clone()), CExpr::new(CExprKind::Boolean(true),span));
             },
-            CExpr::Map(map_lst) => {
+            CExprKind::Map(map_lst) => {
                  for idx in 0..map_lst.len() / 2 {
                      let start = idx * 2;
                      let k = &map_lst[start];
@@ -139,12 +189,11 @@ impl<'a> ParseCST<'a> {
         let mut with_expr = self.parse()?;
         with_expr.set_meta(meta,&mut self.meta_table);
         
-       Ok(with_expr)
+       Ok(with_expr.kind)
     }
 
-    fn parse_symbol(&mut self,chr_start:char) -> Result<CExpr,CSTError> {
+    fn parse_symbol(&mut self,chr_start:char) -> Result<CExprKind,CSTError> {
         if !utils::is_sym_char_start(chr_start) {
-            println!("line:{} col:{}",self.source.line(),self.source.col());
             return Err(CSTError::InvalidSymbolChar(chr_start));
         }
         if chr_start == ':' {
@@ -173,17 +222,17 @@ impl<'a> ParseCST<'a> {
         }
         if ns_name == "" {
             match last_name.as_str() {
-                "nil" => return Ok(CExpr::Nil),
-                "true" => return Ok(CExpr::Boolean(true)),
-                "false" => return Ok(CExpr::Boolean(false)),
+                "nil" => return Ok(CExprKind::Nil),
+                "true" => return Ok(CExprKind::Boolean(true)),
+                "false" => return Ok(CExprKind::Boolean(false)),
                 _ => ()
             }
         }
         let sym = Symbol::intern(if is_ns {Some(ns_name)} else {None }, last_name);
-        Ok(CExpr::Symbol(sym))
+        Ok(CExprKind::Symbol(sym))
     }
 
-    fn parse_keyword(&mut self) -> Result<CExpr,CSTError> {
+    fn parse_keyword(&mut self) -> Result<CExprKind,CSTError> {
         let mut join_sym = String::from(":");
         if let Some(take_string) = self.source.take_while(|chr| !utils::is_whitespace(chr) && utils::is_sym_char(chr) && chr != '/') {
             join_sym.push_str(take_string);
@@ -197,55 +246,66 @@ impl<'a> ParseCST<'a> {
         }
         let mut keyword = Keyword::intern(Symbol::intern(None, join_sym));
         keyword.is_local = is_local;
-        return Ok(CExpr::Keyword(keyword));
+        return Ok(CExprKind::Keyword(keyword));
     }
 
   
 
-    fn parse_char(&mut self) -> Result<CExpr,CSTError> {
-       let mtoken = self.source.take_while(|chr| chr == ',' || (utils::is_sym_char(chr) && !chr.is_whitespace()));
-       match mtoken {
-           Some(tok) => {
-               let len = tok.chars().count();
-               if len == 1 {
-                   let c = tok.chars().next().unwrap();
-                   return Ok(CExpr::Char(c));
-               }
-               match tok {
-                   "newline" => return Ok(CExpr::Char('\n')),
-                   "space" => return Ok(CExpr::Char(' ')),
-                   "tab" => return Ok(CExpr::Char('\t')),
-                   "return" => return Ok(CExpr::Char('\r')),
-                   _ if tok.starts_with('u') => {
-                       unimplemented!()
-                   }
-                   s => return Err(CSTError::UnsupportedCharacter(s.to_string()))
-               }
-           },
-           None => Err(CSTError::ErrEof) 
+    fn parse_char(&mut self) -> Result<CExprKind,CSTError> {
+       let chr = self.next().ok_or(CSTError::ErrEof)?;
+       if chr.is_whitespace() {
+           return Err(CSTError::UnsupportedCharacter(chr.to_string()));
+       }
+       //\( \" \; are single chars that can't start a token
+       if !utils::is_sym_char(chr) {
+           return Ok(CExprKind::Char(chr));
+       }
+       let rest = self.source.take_while(|c| c != ',' && utils::is_sym_char(c) && !c.is_whitespace()).unwrap_or_default();
+       if rest.is_empty() {
+           return Ok(CExprKind::Char(chr));
+       }
+       let mut tok = String::from(chr);
+       tok.push_str(rest);
+       match tok.as_str() {
+           "newline" => Ok(CExprKind::Char('\n')),
+           "space" => Ok(CExprKind::Char(' ')),
+           "tab" => Ok(CExprKind::Char('\t')),
+           "return" => Ok(CExprKind::Char('\r')),
+           "backspace" => Ok(CExprKind::Char('\u{8}')),
+           "formfeed" => Ok(CExprKind::Char('\u{c}')),
+           s if s.starts_with('u') => utils::unicode_char(&s[1..]).map(CExprKind::Char).ok_or(CSTError::ErrUnicodeEscape(s.to_string())),
+           s if s.starts_with('o') => utils::octal_char(&s[1..]).map(CExprKind::Char).ok_or(CSTError::ErrOctalEscape(s.to_string())),
+           s => Err(CSTError::UnsupportedCharacter(s.to_string()))
        }
     }
 
-    fn parse_comment(&mut self) -> Result<CExpr,CSTError> {
+    fn parse_comment(&mut self) -> Result<CExprKind,CSTError> {
         let str = self.source.take_while(|c| c != '\r' && c != '\n').unwrap_or_default();
-        Ok(CExpr::Comment(String::from(str)))
+        Ok(CExprKind::Comment(String::from(str)))
     }
 
-    pub fn parse_list(&mut self) -> Result<CExpr,CSTError> {
+    pub fn parse_list(&mut self) -> Result<CExprKind,CSTError> {
         let expr_list = self.read_list(')')?;
-        Ok(CExpr::List(expr_list))
+        Ok(CExprKind::List(expr_list))
     }
-    pub fn parse_vector(&mut self) -> Result<CExpr,CSTError> {
+    pub fn parse_vector(&mut self) -> Result<CExprKind,CSTError> {
         let expr_list = self.read_list(']')?;
-        Ok(CExpr::Vector(expr_list))
+        Ok(CExprKind::Vector(expr_list))
     }
 
-    pub fn parse_map(&mut self) -> Result<CExpr,CSTError> {
+    pub fn parse_map(&mut self) -> Result<CExprKind,CSTError> {
         let expr_list = self.read_list('}')?;
-        Ok(CExpr::Map(expr_list))
+        check_duplicates(expr_list.iter().filter(|e| !e.is_comment()).step_by(2))?;
+        Ok(CExprKind::Map(expr_list))
     }
 
 
+    pub fn parse_set(&mut self) -> Result<CExprKind,CSTError> {
+        let expr_list = self.read_list('}')?;
+        check_duplicates(expr_list.iter().filter(|e| !e.is_comment()))?;
+        Ok(CExprKind::Set(expr_list))
+    }
+
     fn read_list(&mut self,end_char:char) -> Result<Vec<CExpr>,CSTError> {
         let mut lsts:Vec<CExpr> = vec![];
         loop {
@@ -269,7 +329,7 @@ impl<'a> ParseCST<'a> {
         }
     }
 
-    fn parse_string(&mut self) -> Result<CExpr,CSTError> {
+    fn parse_string(&mut self) -> Result<CExprKind,CSTError> {
         let mut acc:String = String::default();
         loop {
             let normals = self.source.take_while(utils::is_normal_string_char);
@@ -277,7 +337,7 @@ impl<'a> ParseCST<'a> {
             match self.source.lookahead(1) {
                 Some('"') => {
                     self.next(); 
-                    return  Ok(CExpr::String(acc))
+                    return  Ok(CExprKind::String(acc))
                 },
                 Some('\\') => {
                     self.next();
@@ -302,6 +362,27 @@ impl<'a> ParseCST<'a> {
                             self.next();
                             acc.push('\\');
                         },
+                        Some('b') => {
+                            self.next();
+                            acc.push('\u{8}');
+                        },
+                        Some('f') => {
+                            self.next();
+                            acc.push('\u{c}');
+                        },
+                        Some('u') => {
+                            self.next();
+                            let mut count = 0;
+                            let hex = self.source.take_while(|c| { count += 1; count <= 4 && c.is_ascii_hexdigit() }).unwrap_or_default();
+                            let chr = utils::unicode_char(hex).ok_or(CSTError::ErrUnicodeEscape(format!("u{}",hex)))?;
+                            acc.push(chr);
+                        },
+                        Some(chr) if chr.is_digit(8) => {
+                            let mut count = 0;
+                            let oct = self.source.take_while(|c| { count += 1; count <= 3 && c.is_digit(8) }).unwrap_or_default();
+                            let chr = utils::octal_char(oct).ok_or(CSTError::ErrOctalEscape(oct.to_string()))?;
+                            acc.push(chr);
+                        },
                         Some(chr ) => {
                             return  Err(CSTError::ErrCharInGap(chr));
                         }
@@ -314,7 +395,7 @@ impl<'a> ParseCST<'a> {
         }
     }
 
-    pub fn parse_number(&mut self,chr1:char,is_neg:bool) -> Result<CExpr,CSTError> {
+    pub fn parse_number(&mut self,chr1:char,is_neg:bool) -> Result<CExprKind,CSTError> {
         let chr2 = self.source.lookahead(1);
         match (chr1,chr2) {
             ('0',Some('x')) => {
@@ -329,7 +410,7 @@ impl<'a> ParseCST<'a> {
                 if is_neg {
                     raw.insert(0, '-')
                 }
-                let lit = CExpr::Number(raw,Number::Int(if is_neg { -n } else { n }));
+                let lit = CExprKind::Number(raw,Number::Int(if is_neg { -n } else { n }));
                 return Ok(lit);
             },
             _ => {
@@ -350,13 +431,13 @@ impl<'a> ParseCST<'a> {
                                   if is_neg {
                                     rawe.insert(0, '-');
                                   }
-                                  let lit = CExpr::Number(rawe,Number::Float(if is_neg { -f } else { f }));
+                                  let lit = CExprKind::Number(rawe,Number::Float(if is_neg { -f } else { f }));
                                   return Ok(lit);
                                } else {
                                   return Err(CSTError::ErrNumberOutOfRange);
                                }
                             },
-                            None => return Ok(CExpr::Number(raw,Number::Int(if is_neg { -int } else { int })))
+                            None => return Ok(CExprKind::Number(raw,Number::Int(if is_neg { -int } else { int })))
                          }
                     },
                     (Some((mut raw, sint)),Some((rawf,frac))) => {
@@ -374,14 +455,14 @@ impl<'a> ParseCST<'a> {
                                   raw.push_str(rawf.as_str());
                                   raw.push_str(estr.as_str());
                                   
-                                  return Ok(CExpr::Number(raw,Number::Float(if is_neg { -f } else { f })));
+                                  return Ok(CExprKind::Number(raw,Number::Float(if is_neg { -f } else { f })));
                                } else {
                                   return Err(CSTError::ErrNumberOutOfRange);
                                }
                             },
                             None => {
                                raw.push_str(rawf.as_str());
-                               return Ok(CExpr::Number(raw,Number::Float(if is_neg { -val } else { val })));
+                               return Ok(CExprKind::Number(raw,Number::Float(if is_neg { -val } else { val })));
                             }
                          }
                     },
@@ -522,6 +603,52 @@ impl<'a> ParseCST<'a> {
 }
 
 
+//literal sets and map keys may not repeat a form, numbers compare by value so 0x10 and 16 clash
+fn check_duplicates<'a>(items:impl Iterator<Item=&'a CExpr>) -> Result<(),CSTError> {
+    let mut seen:HashSet<String> = HashSet::new();
+    for item in items {
+        let key = match &item.kind {
+            CExprKind::Number(_,n) => format!("{:?}",n),
+            kind => kind.to_string()
+        };
+        if !seen.insert(key) {
+            return Err(CSTError::ErrDuplicateKey(item.to_string()));
+        }
+    }
+    Ok(())
+}
+
+//renames % to %1 and collects the highest %n and %& used in the body
+fn anon_fn_args(cexpr:&mut CExpr,arg_count:&mut usize,has_rest:&mut bool) {
+    match &mut cexpr.kind {
+        CExprKind::Symbol(sym) => {
+            if sym.sym_ns().is_some() || !sym.name.starts_with('%') {
+                return;
+            }
+            match &sym.name[1..] {
+                "" => {
+                    sym.name.push('1');
+                    *arg_count = (*arg_count).max(1);
+                },
+                "&" => *has_rest = true,
+                n => {
+                    if let Ok(idx) = n.parse::<usize>() {
+                        *arg_count = (*arg_count).max(idx);
+                    }
+                }
+            }
+        },
+        CExprKind::List(lst) | CExprKind::Vector(lst) | CExprKind::Map(lst) | CExprKind::Set(lst) | CExprKind::Meta(lst) => {
+            for item in lst.iter_mut() {
+                anon_fn_args(item, arg_count, has_rest);
+            }
+        },
+        CExprKind::Quote(inner) | CExprKind::SyntaxQuote(inner) | CExprKind::Dref(inner) |
+        CExprKind::UnQuote(inner) | CExprKind::UnQuoteS(inner) => anon_fn_args(inner, arg_count, has_rest),
+        _ => ()
+    }
+}
+
 #[test]
 fn test_parse() {
    let code_string = std::fs::read_to_string("tests/test2.clj").unwrap();
@@ -534,3 +661,45 @@ fn test_parse() {
    dbg!(parser.meta_table);
    
 }
+
+#[test]
+fn test_span() {
+   let code_string = "(def a 1)\n  [x \"∀\" :k]";
+   let mut parser = ParseCST::with_file("span.clj",code_string);
+   let exprs = parser.parse_exprs().unwrap();
+   assert_eq!(exprs[0].span.start,Pos::new(1,1,0));
+   assert_eq!(exprs[0].span.end,Pos::new(1,10,9));
+   assert_eq!(exprs[1].span.start,Pos::new(2,3,12));
+   let items = exprs[1].clone().take_list().unwrap();
+   assert_eq!(items[2].span.start,Pos::new(2,10,21));
+   assert_eq!(format!("{}",items[1].span),"span.clj:2:6");
+}
+
+#[test]
+fn test_duplicate_key() {
+   assert!(ParseCST::new("#{1 2 :a \"a\"} {:a 1 :b 1}").parse_exprs().is_ok());
+   assert!(matches!(ParseCST::new("#{1 ;one\n 1}").parse_exprs(),Err(CSTError::ErrDuplicateKey(key)) if key == "1"));
+   assert!(matches!(ParseCST::new("#{[1 :a] [1 :a]}").parse_exprs(),Err(CSTError::ErrDuplicateKey(_))));
+   assert!(matches!(ParseCST::new("{:a 1 :b 2 :a 3}").parse_exprs(),Err(CSTError::ErrDuplicateKey(key)) if key == ":a"));
+}
+
+#[test]
+fn test_anon_fn() {
+   let mut parser = ParseCST::new("#(+ % %3 (apply list %&)) #(println %2)");
+   let exprs = parser.parse_exprs().unwrap();
+   assert_eq!(format!("{}",exprs[0]),"(fn [%1 %2 %3 & %&] (+ %1 %3 (apply list %&)))");
+   assert_eq!(format!("{}",exprs[1]),"(fn [%1 %2] (println %2))");
+   assert!(ParseCST::new("#(map #(+ % 1) %)").parse().is_err());
+}
+
+#[test]
+fn test_char() {
+   let mut parser = ParseCST::new("[\\A \\u0041 \\o101 \\( \\newline \\a,\\b] \"\\u00e9t\\u00E9 \\101\\t\"");
+   let exprs = parser.parse_exprs().unwrap();
+   let chars:Vec<String> = exprs[0].clone().take_list().unwrap().iter().map(|e| format!("{}",e)).collect();
+   assert_eq!(chars,vec!["'A'","'A'","'A'","'('","'\n'","'a'","'b'"]);
+   assert_eq!(format!("{}",exprs[1]),"\"été A\t\"");
+   assert!(ParseCST::new("\\u00G1").parse().is_err());
+   assert!(ParseCST::new("\\o400").parse().is_err());
+   assert!(ParseCST::new("\"\\u12\"").parse().is_err());
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use super::{cexpr::{CExpr, CExprKind, Number}, errors::ASTError, span::Span, syntax_quote::gensym, value::{Keyword, Symbol, CORE_NS}};
+
+/*
+  (let [[a b & more :as all] v] ...)
+  to
+  (let [vec__1__auto__ v
+        a (clojure.core/nth vec__1__auto__ 0 nil)
+        b (clojure.core/nth vec__1__auto__ 1 nil)
+        more (clojure.core/nthnext vec__1__auto__ 2)
+        all vec__1__auto__] ...)
+
+  (let [{:keys [hp] :or {hp 100} :as m} v] ...)
+  to
+  (let [map__2__auto__ v
+        hp (clojure.core/get map__2__auto__ :hp 100)
+        m map__2__auto__] ...)
+*/
+pub fn destructure(pattern:CExpr,value:CExpr,binds:&mut Vec<CExpr>) -> Result<(),ASTError> {
+    let span = pattern.span.clone();
+    match pattern.kind {
+        CExprKind::Symbol(_) => {
+            binds.push(pattern);
+            binds.push(value);
+        },
+        CExprKind::Vector(items) => {
+            let vec_sym = CExpr::new(CExprKind::Symbol(gensym("vec")),span.clone());
+            binds.push(vec_sym.clone());
+            binds.push(value);
+            let mut items = items.into_iter().filter(|e| !e.is_comment());
+            let mut idx = 0;
+            while let Some(item) = items.next() {
+                match &item.kind {
+                    CExprKind::Symbol(sym) if sym.name == "&" => {
+                        let rest = items.next().ok_or(ASTError::BadBindingForm.at(&item.span))?;
+                        let nthnext = core_call("nthnext", vec![vec_sym.clone(),int(idx,&span)], &span);
+                        destructure(rest, nthnext, binds)?;
+                    },
+                    CExprKind::Keyword(k) if k.sym.name == ":as" => {
+                        let as_sym = items.next().ok_or(ASTError::BadBindingForm.at(&item.span))?;
+                        if as_sym.cast_sym().is_none() {
+                            return Err(ASTError::BadBindingForm.at(&as_sym.span));
+                        }
+                        binds.push(as_sym);
+                        binds.push(vec_sym.clone());
+                    },
+                    _ => {
+                        let nil = CExpr::new(CExprKind::Nil,span.clone());
+                        let nth = core_call("nth", vec![vec_sym.clone(),int(idx,&span),nil], &span);
+                        destructure(item, nth, binds)?;
+                        idx += 1;
+                    }
+                }
+            }
+        },
+        CExprKind::Map(items) => {
+            let map_sym = CExpr::new(CExprKind::Symbol(gensym("map")),span.clone());
+            binds.push(map_sym.clone());
+            binds.push(value);
+            let items:Vec<CExpr> = items.into_iter().filter(|e| !e.is_comment()).collect();
+            if !items.len().is_multiple_of(2) {
+                return Err(ASTError::BadBindingForm.at(&span));
+            }
+            let defaults = or_defaults(&items)?;
+            let mut items = items.into_iter();
+            while let (Some(key),Some(val)) = (items.next(),items.next()) {
+                let key_name = match &key.kind {
+                    CExprKind::Keyword(k) => Some(k.sym.name.clone()),
+                    _ => None
+                };
+                match key_name.as_deref() {
+                    Some(":keys") | Some(":strs") | Some(":syms") => {
+                        let syms = val.take_list_no_white().ok_or(ASTError::BadBindingForm.at(&span))?;
+                        for sym_expr in syms {
+                            let sym_span = sym_expr.span.clone();
+                            let sym = sym_expr.cast_symbol().map_err(|e| ASTError::BadBindingForm.at(&e.span))?;
+                            let lookup = match key_name.as_deref() {
+                                Some(":keys") => CExprKind::Keyword(Keyword::intern(Symbol::intern(None, format!(":{}",sym.name)))),
+                                Some(":strs") => CExprKind::String(sym.name.clone()),
+                                _ => CExprKind::Quote(Box::new(CExpr::new(CExprKind::Symbol(sym.clone()),sym_span.clone())))
+                            };
+                            let get = map_get(&map_sym, CExpr::new(lookup,sym_span.clone()), defaults.get(&sym.name), &span);
+                            binds.push(CExpr::new(CExprKind::Symbol(Symbol::intern(None, sym.name)),sym_span));
+                            binds.push(get);
+                        }
+                    },
+                    Some(":as") => {
+                        if val.cast_sym().is_none() {
+                            return Err(ASTError::BadBindingForm.at(&val.span));
+                        }
+                        binds.push(val);
+                        binds.push(map_sym.clone());
+                    },
+                    Some(":or") => (),
+                    _ => {
+                        //{x :x [a b] :pos}
+                        let default = key.cast_sym().and_then(|sym| defaults.get(&sym.name));
+                        let get = map_get(&map_sym, val, default, &span);
+                        destructure(key, get, binds)?;
+                    }
+                }
+            }
+        },
+        _ => return Err(ASTError::BadBindingForm.at(&span))
+    }
+    Ok(())
+}
+
+fn or_defaults(items:&[CExpr]) -> Result<HashMap<String,CExpr>,ASTError> {
+    let mut defaults:HashMap<String,CExpr> = HashMap::new();
+    for pair in items.chunks(2) {
+        match (&pair[0].kind,&pair[1].kind) {
+            (CExprKind::Keyword(k),CExprKind::Map(or_items)) if k.sym.name == ":or" => {
+                let or_items:Vec<&CExpr> = or_items.iter().filter(|e| !e.is_comment()).collect();
+                for kv in or_items.chunks(2) {
+                    match (kv[0].cast_sym(),kv.get(1)) {
+                        (Some(sym),Some(default)) => { defaults.insert(sym.name.clone(), (*default).clone()); },
+                        _ => return Err(ASTError::BadBindingForm.at(&kv[0].span))
+                    }
+                }
+            },
+            (CExprKind::Keyword(k),_) if k.sym.name == ":or" => return Err(ASTError::BadBindingForm.at(&pair[1].span)),
+            _ => ()
+        }
+    }
+    Ok(defaults)
+}
+
+fn map_get(map_sym:&CExpr,key:CExpr,default:Option<&CExpr>,span:&Span) -> CExpr {
+    let mut args = vec![map_sym.clone(),key];
+    if let Some(default) = default {
+        args.push(default.clone());
+    }
+    core_call("get", args, span)
+}
+
+fn core_call(name:&str,mut args:Vec<CExpr>,span:&Span) -> CExpr {
+    args.insert(0, CExpr::symbol(Some(CORE_NS), name, span));
+    CExpr::list(args, span)
+}
+
+fn int(n:i64,span:&Span) -> CExpr {
+    CExpr::new(CExprKind::Number(n.to_string(),Number::Int(n)),span.clone())
+}
+
+#[test]
+fn test_destructure() {
+    use super::cst::ParseCST;
+    let mut parser = ParseCST::new("[[a [b] & more :as all] {:keys [hp] :or {hp 100} x :x}]");
+    let pats = parser.parse().unwrap().take_list().unwrap();
+    let mut binds:Vec<CExpr> = vec![];
+    for pat in pats {
+        let value = CExpr::symbol(None, "v", &Span::default());
+        destructure(pat, value, &mut binds).unwrap();
+    }
+    let show:Vec<String> = binds.iter().map(|e| format!("{}",e)).collect();
+    let names:Vec<&str> = show.iter().step_by(2).map(|s| s.split("__").next().unwrap()).collect();
+    assert_eq!(names,vec!["vec","a","vec","b","more","all","map","hp","x"]);
+    assert!(show[3].starts_with("(clojure.core/nth vec__") && show[3].ends_with(" 0 nil)"));
+    assert!(show[9].starts_with("(clojure.core/nthnext vec__") && show[9].ends_with(" 2)"));
+    assert!(show[15].ends_with(" :hp 100)"));
+    assert!(show[17].ends_with(" :x)"));
+    assert!(destructure(CExpr::new(CExprKind::Nil,Span::default()), CExpr::new(CExprKind::Nil,Span::default()), &mut binds).is_err());
+}
@@ -1,5 +1,7 @@
 use std::fmt::{Display, Formatter};
 
+use crate::span::Span;
+
 #[derive(Debug)]
 pub enum CSTError {
     InvalidSymbolChar(char),
@@ -10,13 +12,18 @@ pub enum CSTError {
     ErrNumberOutOfRange,
     ErrLineFeedInString,
     ErrCharInGap(char),
+    ErrUnicodeEscape(String),
+    ErrOctalEscape(String),
     ErrLexeme(Option<String>),
     UnsupportedCharacter(String),
     ErrSymbol(String),
     ErrMetadata,
     ErrEof,
     ErrQuoteVar,
+    ErrNestedAnonFn,
+    ErrDuplicateKey(String),
     ExMacroDefrecord,
+    ExMacroDefn,
     ExMacroObjectCall
 }
 
@@ -28,12 +35,44 @@ pub enum ASTError {
     BadBindingForm,
     ErrLet(usize),
     ErrIf,
-    ErrFn
+    ErrFn,
+    ErrFnArity,
+    ErrFnFixedArity,
+    ErrTry,
+    ErrThrow,
+    ErrQuote,
+    ErrUnQuote,
+    ErrUnQuoteSplicing,
+    ErrDefMacro,
+    ErrMacro(String),
+    ErrMacroArgs,
+    Located(Span,Box<ASTError>)
+}
+
+impl ASTError {
+    //keeps the innermost location when an error bubbles up through nested forms
+    pub fn at(self,span:&Span) -> ASTError {
+        match self {
+            ASTError::Located(_,_) => self,
+            err => ASTError::Located(span.clone(),Box::new(err))
+        }
+    }
+
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            ASTError::Located(span,_) => Some(span),
+            _ => None
+        }
+    }
 }
 
 impl Display for ASTError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            ASTError::Located(span,err) => write!(f, "{} {}", span, err),
+            ASTError::ErrFnFixedArity => write!(f, "Can't have fixed arity function with more params than variadic function"),
+            err => write!(f, "{:?}", err)
+        }
     }
 }
 
@@ -1,40 +1,82 @@
-use super::{cexpr::Number, value::{Keyword, Symbol}};
+use super::{cexpr::{CExpr, Number}, span::Span, value::{Keyword, Symbol}};
 
 #[derive(Debug,Clone)]
-pub enum Expr {
+pub struct Expr {
+    pub kind:ExprKind,
+    pub span:Span
+}
+
+#[derive(Debug,Clone)]
+pub enum ExprKind {
     Nil,
-    Fn(Vec<Symbol>,Vec<Expr>),
+    Fn(Option<Symbol>,Vec<FnArity>),
     Number(Number),
     Boolean(bool),
     Symbol(Symbol),
     Keyword(Keyword),
     String(String),
+    Char(char),
     QuoteVar(Symbol),
+    Quote(Box<CExpr>),
     Recur(Vec<Expr>),
 
     Def(Option<String>,Symbol,Option<Box<Expr>>),
+    DefMacro(Symbol,Box<Expr>),
     Let(Vec<Expr>,Box<Expr>,bool),
     Body(Vec<Expr>),
     Invoke(Vec<Expr>),
     If(Box<Expr>,Box<Expr>,Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Try(Box<Expr>,Vec<Catch>,Option<Box<Expr>>),
+    Throw(Box<Expr>),
 
     Map(Vec<Expr>),
+    Set(Vec<Expr>),
     Vector(Vec<Expr>)
 }
 
+#[derive(Debug,Clone)]
+pub struct Catch {
+    pub class:String,
+    pub bind:Symbol,
+    pub body:Expr
+}
+
+#[derive(Debug,Clone)]
+pub struct FnArity {
+    pub args:Vec<Symbol>,
+    pub rest:Option<Symbol>,
+    pub body:Vec<Expr>
+}
+
+impl FnArity {
+    pub fn is_match(&self,arg_count:usize) -> bool {
+        if self.rest.is_some() {
+            arg_count >= self.args.len()
+        } else {
+            arg_count == self.args.len()
+        }
+    }
+}
+
 impl Expr {
+    pub fn new(kind:ExprKind,span:Span) -> Expr {
+        Expr { kind, span }
+    }
+
     pub fn case_vector(self) -> Option<Vec<Expr>> {
-        match self {
-            Expr::Vector(lst) => Some(lst),
+        match self.kind {
+            ExprKind::Vector(lst) => Some(lst),
             _ => None
         }
     }
 
     pub fn case_sym(self) -> Option<Symbol> {
-        match self {
-            Expr::Symbol(sym) => Some(sym),
+        match self.kind {
+            ExprKind::Symbol(sym) => Some(sym),
             _ => None
         }
     }
 
-}
\ No newline at end of file
+}
@@ -14,7 +14,8 @@ pub struct LexString<'a> {
     char_count:usize,
 
     line:u64,
-    col:u64
+    col:u64,
+    offset:usize
 }
 
 impl<'a> LexString<'a> {
@@ -28,6 +29,7 @@ impl<'a> LexString<'a> {
             char_count:str.chars().count(),
             line:1,
             col:0,
+            offset:0
         }
     }
 
@@ -43,6 +45,9 @@ impl<'a> LexString<'a> {
         } else {
             self.col += 1;
         }
+        if let Some(c) = chr {
+            self.offset += c.len_utf8();
+        }
 
         chr
     }
@@ -55,6 +60,10 @@ impl<'a> LexString<'a> {
         self.col
     }
 
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
     fn _next(&mut self) -> Option<char> {
       if self.ahead_count > 0 {
           self.cur_index += 1;
@@ -159,6 +168,8 @@ impl<'a> LexString<'a> {
     pub fn put_back(&mut self,chr:char) {
         self.cache_list.push_back(chr);
         self.ahead_count += 1;
+        self.col = self.col.saturating_sub(1);
+        self.offset -= chr.len_utf8();
     }
 
     pub fn slice(&self,s:usize,e:usize) -> &str {
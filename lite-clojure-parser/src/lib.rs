@@ -8,18 +8,9 @@ pub mod utils;
 pub mod value;
 pub mod env;
 pub mod meta;
+pub mod span;
+pub mod syntax_quote;
+pub mod macros;
+pub mod destructure;
+pub mod core_macros;
 
-use std::{sync::Mutex};
-
-use cexpr::CExpr;
-use lazy_static::lazy_static;
-
-type MacroFunc = fn(&mut CExpr);
-
-lazy_static! {
-    static ref GLOBAL_MACRO_HOOKS:Mutex<Vec<MacroFunc>> = Mutex::new(Vec::new());
-}
-
-pub fn add_macro_func(func:MacroFunc) {
-    GLOBAL_MACRO_HOOKS.lock().unwrap().push(func);
-}
\ No newline at end of file
@@ -0,0 +1,96 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use super::{cexpr::CExpr, errors::ASTError, span::Span};
+
+pub type MacroExpandFn = dyn Fn(&[CExpr],&Span) -> Result<CExpr,ASTError>;
+pub type FormHookFn = dyn Fn(&mut CExpr);
+
+#[derive(Default)]
+struct MacroTable {
+    macros:HashMap<String,Rc<MacroExpandFn>>,
+    hooks:Vec<(String,Rc<FormHookFn>)>
+}
+
+/*
+  host side macros, shared by the TranslateToAST instances of one runtime
+  (name args ...) is replaced by the expander result, hooks rewrite every top-level form
+*/
+#[derive(Default,Clone)]
+pub struct MacroRegistry {
+    table:Rc<RefCell<MacroTable>>
+}
+
+impl MacroRegistry {
+    pub fn new() -> Self {
+        MacroRegistry::default()
+    }
+
+    pub fn add_macro(&self,name:&str,f:Box<MacroExpandFn>) {
+        self.table.borrow_mut().macros.insert(name.to_string(), Rc::from(f));
+    }
+
+    pub fn remove_macro(&self,name:&str) -> bool {
+        self.table.borrow_mut().macros.remove(name).is_some()
+    }
+
+    pub fn has_macro(&self,name:&str) -> bool {
+        self.table.borrow().macros.contains_key(name)
+    }
+
+    pub fn add_hook(&self,name:&str,f:Box<FormHookFn>) {
+        let mut table = self.table.borrow_mut();
+        table.hooks.retain(|(hook_name,_)| hook_name != name);
+        table.hooks.push((name.to_string(),Rc::from(f)));
+    }
+
+    pub fn remove_hook(&self,name:&str) -> bool {
+        let mut table = self.table.borrow_mut();
+        let len = table.hooks.len();
+        table.hooks.retain(|(hook_name,_)| hook_name != name);
+        table.hooks.len() != len
+    }
+
+    pub fn find_macro(&self,name:&str) -> Option<Rc<MacroExpandFn>> {
+        self.table.borrow().macros.get(name).cloned()
+    }
+
+    pub fn apply_hooks(&self,cexpr:&mut CExpr) {
+        let hooks:Vec<Rc<FormHookFn>> = self.table.borrow().hooks.iter().map(|(_,f)| f.clone()).collect();
+        for hook in hooks {
+            hook(cexpr);
+        }
+    }
+}
+
+#[test]
+fn test_registry() {
+    use super::{ast::TranslateToAST, cst::ParseCST, cexpr::CExprKind};
+    let registry = MacroRegistry::new();
+    registry.add_macro("unless", Box::new(|args,span| {
+        let mut lst = vec![CExpr::symbol(None, "if", span),args[0].clone()];
+        lst.push(args[2].clone());
+        lst.push(args[1].clone());
+        Ok(CExpr::list(lst, span))
+    }));
+    registry.add_hook("upper", Box::new(|cexpr| {
+        if let CExprKind::String(s) = &mut cexpr.kind {
+            *s = s.to_uppercase();
+        }
+    }));
+    let translate = |registry:MacroRegistry| {
+        let mut parser = ParseCST::new("(unless true 1 2) \"abc\"");
+        let cexprs = parser.parse_exprs().unwrap();
+        TranslateToAST::with_macros(String::default(), cexprs, parser.take(), registry).translate().exprs
+    };
+    let exprs = translate(registry.clone());
+    assert!(format!("{:?}",exprs[0].kind).starts_with("If("));
+    assert!(format!("{:?}",exprs[1].kind) == "String(\"ABC\")");
+
+    let exprs = translate(MacroRegistry::new());
+    assert!(format!("{:?}",exprs[0].kind).starts_with("Invoke("));
+    assert!(format!("{:?}",exprs[1].kind) == "String(\"abc\")");
+
+    assert!(registry.remove_macro("unless"));
+    assert!(registry.remove_hook("upper"));
+    assert!(!registry.has_macro("unless"));
+}
@@ -1,5 +1,5 @@
 use std::{collections::HashMap, ops::Deref};
-use super::{cexpr::CExpr, value::{Keyword, Symbol}};
+use super::{cexpr::{CExpr, CExprKind}, value::{Keyword, Symbol}};
 #[derive(Debug,Hash,PartialEq,Eq,Clone)]
 pub enum Metakey {
     String(String),
@@ -9,10 +9,10 @@ pub enum Metakey {
 
 impl Metakey {
     pub fn from_c_expr(k:&CExpr) -> Option<Metakey> {
-        match k {
-            CExpr::String(str) => Some(Metakey::String(str.clone())),
-            CExpr::Symbol(sym) => Some(Metakey::Symbol(sym.deref().clone())),
-            CExpr::Keyword(k) => Some(Metakey::Keyword(k.deref().clone())),
+        match &k.kind {
+            CExprKind::String(str) => Some(Metakey::String(str.clone())),
+            CExprKind::Symbol(sym) => Some(Metakey::Symbol(sym.deref().clone())),
+            CExprKind::Keyword(k) => Some(Metakey::Keyword(k.deref().clone())),
             _ => None
         }
     }
@@ -0,0 +1,47 @@
+use std::{fmt, rc::Rc};
+
+#[derive(Debug,Clone,Copy,Default,PartialEq,Eq)]
+pub struct Pos {
+    pub line:u32,
+    pub col:u32,
+    pub offset:u32
+}
+
+impl Pos {
+    pub fn new(line:u32,col:u32,offset:u32) -> Pos {
+        Pos { line, col, offset }
+    }
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,"{}:{}",self.line,self.col)
+    }
+}
+
+#[derive(Debug,Clone,Default,PartialEq,Eq)]
+pub struct Span {
+    pub file:Rc<str>,
+    pub start:Pos,
+    pub end:Pos
+}
+
+impl Span {
+    pub fn new(file:Rc<str>,start:Pos,end:Pos) -> Span {
+        Span { file, start, end }
+    }
+
+    pub fn point(file:Rc<str>,pos:Pos) -> Span {
+        Span { file, start:pos, end:pos }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.file.is_empty() {
+            write!(f,"{}",self.start)
+        } else {
+            write!(f,"{}:{}",self.file,self.start)
+        }
+    }
+}
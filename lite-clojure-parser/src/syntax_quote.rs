@@ -0,0 +1,137 @@
+use std::{collections::HashMap, sync::atomic::{AtomicUsize, Ordering}};
+
+use super::{cexpr::{CExpr, CExprKind}, core_macros::is_core_macro, errors::ASTError, span::Span, value::{Symbol, CORE_NS}};
+
+static GENSYM_ID:AtomicUsize = AtomicUsize::new(0);
+
+pub const SPECIAL_FORMS:&[&str] = &["def","loop","recur","if","and","or","let","do","fn","quote","var","try","catch","finally","throw","&","."];
+
+pub fn gensym(prefix:&str) -> Symbol {
+    let id = GENSYM_ID.fetch_add(1, Ordering::Relaxed);
+    Symbol::intern(None, format!("{}__{}__auto__",prefix,id))
+}
+
+/*
+  `(a ~b ~@c)
+  to
+  (clojure.core/seq (clojure.core/concat (clojure.core/list (quote ns/a)) (clojure.core/list b) c))
+  names resolving to core, like `+, are qualified as clojure.core/+
+  `#{a} to (clojure.core/apply clojure.core/hash-set (clojure.core/concat (clojure.core/list (quote ns/a))))
+*/
+pub struct SyntaxQuote<'a> {
+    ns:&'a str,
+    is_core:&'a dyn Fn(&str) -> bool,
+    gensyms:HashMap<String,Symbol>
+}
+
+impl<'a> SyntaxQuote<'a> {
+    //only the core macros are known to resolve to core
+    pub fn new(ns:&'a str) -> Self {
+        SyntaxQuote::with_core(ns, &is_core_macro)
+    }
+
+    pub fn with_core(ns:&'a str,is_core:&'a dyn Fn(&str) -> bool) -> Self {
+        SyntaxQuote { ns, is_core, gensyms:HashMap::new() }
+    }
+
+    pub fn expand(&mut self,cexpr:CExpr) -> Result<CExpr,ASTError> {
+        let span = cexpr.span.clone();
+        match cexpr.kind {
+            CExprKind::Symbol(sym) => {
+                let sym = self.qualify(sym);
+                Ok(quote(CExpr::new(CExprKind::Symbol(sym),span.clone()),&span))
+            },
+            CExprKind::UnQuote(inner) => Ok(*inner),
+            CExprKind::UnQuoteS(_) => Err(ASTError::ErrUnQuoteSplicing),
+            CExprKind::SyntaxQuote(inner) => {
+                let inner_expand = SyntaxQuote::with_core(self.ns, self.is_core).expand(*inner)?;
+                self.expand(inner_expand)
+            },
+            CExprKind::Quote(inner) => {
+                let quote_list = vec![CExpr::symbol(None, "quote", &span),*inner];
+                self.expand(CExpr::list(quote_list, &span))
+            },
+            CExprKind::List(lst) => {
+                if lst.iter().all(|e| e.is_comment()) {
+                    return Ok(CExpr::list(vec![CExpr::symbol(Some(CORE_NS), "list", &span)], &span));
+                }
+                let concat = self.expand_items(lst, &span)?;
+                Ok(CExpr::list(vec![CExpr::symbol(Some(CORE_NS), "seq", &span),concat], &span))
+            },
+            CExprKind::Vector(lst) => {
+                let concat = self.expand_items(lst, &span)?;
+                Ok(CExpr::list(vec![CExpr::symbol(Some(CORE_NS), "vec", &span),concat], &span))
+            },
+            CExprKind::Map(lst) => {
+                let concat = self.expand_items(lst, &span)?;
+                let hash_map = CExpr::symbol(Some(CORE_NS), "hash-map", &span);
+                Ok(CExpr::list(vec![CExpr::symbol(Some(CORE_NS), "apply", &span),hash_map,concat], &span))
+            },
+            CExprKind::Set(lst) => {
+                let concat = self.expand_items(lst, &span)?;
+                let hash_set = CExpr::symbol(Some(CORE_NS), "hash-set", &span);
+                Ok(CExpr::list(vec![CExpr::symbol(Some(CORE_NS), "apply", &span),hash_set,concat], &span))
+            },
+            kind => Ok(CExpr::new(kind,span))
+        }
+    }
+
+    fn expand_items(&mut self,lst:Vec<CExpr>,span:&Span) -> Result<CExpr,ASTError> {
+        let mut concat_list:Vec<CExpr> = vec![CExpr::symbol(Some(CORE_NS), "concat", span)];
+        for item in lst {
+            let item_span = item.span.clone();
+            match item.kind {
+                CExprKind::Comment(_) => continue,
+                CExprKind::UnQuoteS(inner) => concat_list.push(*inner),
+                CExprKind::UnQuote(inner) => {
+                    concat_list.push(CExpr::list(vec![CExpr::symbol(Some(CORE_NS), "list", &item_span),*inner], &item_span));
+                },
+                kind => {
+                    let expand_item = self.expand(CExpr::new(kind,item_span.clone()))?;
+                    concat_list.push(CExpr::list(vec![CExpr::symbol(Some(CORE_NS), "list", &item_span),expand_item], &item_span));
+                }
+            }
+        }
+        Ok(CExpr::list(concat_list, span))
+    }
+
+    fn qualify(&mut self,sym:Symbol) -> Symbol {
+        let name = sym.sym_name();
+        if sym.sym_ns().is_some() || SPECIAL_FORMS.contains(&name) || name.starts_with('.') || name.ends_with('.') {
+            return sym;
+        }
+        if name.len() > 1 && name.ends_with('#') {
+            let prefix = &name[..name.len() - 1];
+            return self.gensyms.entry(name.to_string()).or_insert_with(|| gensym(prefix)).clone();
+        }
+        if (self.is_core)(name) {
+            return Symbol::intern(Some(CORE_NS.to_string()), name.to_string());
+        }
+        Symbol::intern(Some(self.ns.to_string()), name.to_string())
+    }
+}
+
+fn quote(cexpr:CExpr,span:&Span) -> CExpr {
+    CExpr::list(vec![CExpr::symbol(None, "quote", span),cexpr], span)
+}
+
+#[test]
+fn test_syntax_quote() {
+    use super::cst::ParseCST;
+    let mut parser = ParseCST::new("`(let [a# ~b] (+ a# ~@c) (f) [x#] {:k a#})");
+    let cexpr = parser.parse().unwrap();
+    let expand = match cexpr.kind {
+        CExprKind::SyntaxQuote(inner) => SyntaxQuote::with_core("user", &|name| name == "+").expand(*inner).unwrap(),
+        _ => panic!("not syntax quote")
+    };
+    let show = format!("{}",expand);
+    assert!(show.starts_with("(clojure.core/seq (clojure.core/concat (clojure.core/list (quote let))"));
+    assert!(show.contains("(clojure.core/list b)"));
+    assert!(show.contains("(quote clojure.core/+)"));
+    assert!(show.contains("(quote user/f)"));
+    assert!(show.contains(" c)"));
+    let gensyms:Vec<&str> = show.split([' ', ')']).filter(|s| s.starts_with("a__")).collect();
+    assert_eq!(gensyms.len(),3);
+    assert!(gensyms.iter().all(|s| *s == gensyms[0]));
+    assert!(!show.contains("x#"));
+}
@@ -7,6 +7,25 @@ pub fn digits_to_integer_base(base:i64,str:&str) -> i64 {
     n
  }
 
+ //\u0041 and \o101 escapes, surrogates and values above \o377 are rejected
+ pub fn unicode_char(hex:&str) -> Option<char> {
+    if hex.len() != 4 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+       return None;
+    }
+    char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+ }
+
+ pub fn octal_char(oct:&str) -> Option<char> {
+    if oct.is_empty() || oct.len() > 3 || !oct.chars().all(|c| c.is_digit(8)) {
+       return None;
+    }
+    let n = u32::from_str_radix(oct, 8).ok()?;
+    if n > 0o377 {
+       return None;
+    }
+    char::from_u32(n)
+ }
+
  pub fn digits_to_integer(str:&str) -> i64 {
     digits_to_integer_base(10, str)
  }
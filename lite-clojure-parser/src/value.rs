@@ -3,6 +3,8 @@ use std::hash::{Hash};
 
 use super::{meta::{MetaIndex}};
 
+pub const CORE_NS:&str = "clojure.core";
+
 
 #[derive(Debug,Clone,Hash,Eq,PartialEq)]
 pub struct Symbol {